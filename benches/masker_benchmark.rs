@@ -0,0 +1,65 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
+
+use keyai_desktop::masker::{Masker, PATTERN_SET_BR, PATTERN_SET_US};
+
+fn typical_text() -> String {
+    "reunião amanhã às 10h para revisar o relatório trimestral e o orçamento do projeto "
+        .repeat(8)
+}
+
+/// Texto comum sem dígitos, `@` ou `:`, descartado antes do `RegexSet`
+fn trigger_free_text() -> String {
+    "reunião amanhã cedo para revisar o relatório trimestral e o orçamento do projeto "
+        .repeat(8)
+}
+
+fn pii_heavy_text() -> String {
+    "email joao@exemplo.com, CPF 123.456.789-01, cartão 4111 1111 1111 1111, \
+     tel (11) 99999-1234, IBAN DE89 3704 0044 0532 0130 00, SSN 123-45-6789 "
+        .repeat(4)
+}
+
+fn masker_benchmark(c: &mut Criterion) {
+    let masker = Masker::new_with_sets(&[PATTERN_SET_BR, PATTERN_SET_US]);
+    let inputs = [
+        ("sem_pii", typical_text()),
+        ("com_pii", pii_heavy_text()),
+        ("tecla", "a".to_string()),
+    ];
+
+    let mut group = c.benchmark_group("mask_text");
+    for (name, text) in &inputs {
+        group.bench_with_input(BenchmarkId::from_parameter(name), text, |b, text| {
+            b.iter(|| black_box(masker.mask_text(black_box(text))))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("mask_text_detailed");
+    for (name, text) in &inputs {
+        group.bench_with_input(BenchmarkId::from_parameter(name), text, |b, text| {
+            b.iter(|| black_box(masker.mask_text_detailed(black_box(text))))
+        });
+    }
+    group.finish();
+
+    // Qualquer padrão customizado desativa o descarte rápido; este é barato e
+    // não casa no texto, então a diferença é o custo evitado do `RegexSet`
+    let mut without_quick_reject = masker.clone();
+    without_quick_reject
+        .add_custom_pattern("ramal".to_string(), r"\bramal \d{4}\b".to_string())
+        .unwrap();
+    let text = trigger_free_text();
+
+    let mut group = c.benchmark_group("quick_reject");
+    group.bench_function("com_descarte_rapido", |b| {
+        b.iter(|| black_box(masker.mask_text(black_box(&text))))
+    });
+    group.bench_function("so_regex_set", |b| {
+        b.iter(|| black_box(without_quick_reject.mask_text(black_box(&text))))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, masker_benchmark);
+criterion_main!(benches);
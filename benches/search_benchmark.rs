@@ -5,7 +5,7 @@ use tokio::runtime::Runtime;
 
 use keyai_desktop::db::Database;
 use keyai_desktop::search::{SearchEngine, SearchOptions};
-use keyai_desktop::agent::KeyEvent;
+use keyai_desktop::agent::{KeyEvent, WindowInfo};
 
 async fn setup_test_data(database: Arc<Database>, event_count: usize) {
     let mut events = Vec::new();
@@ -21,8 +21,16 @@ async fn setup_test_data(database: Arc<Database>, event_count: usize) {
             timestamp: i as u64,
             key: word.to_string(),
             event_type: "press".to_string(),
-            window_title: Some(format!("Test Window {}", i)),
-            application: Some("Test App".to_string()),
+            window_info: Some(WindowInfo {
+                title: format!("Test Window {}", i),
+                application: "Test App".to_string(),
+                process_id: None,
+                timestamp: i as u64,
+            }),
+            is_modifier: false,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
         });
         
         // Store in batches to avoid memory issues
@@ -84,6 +92,36 @@ fn search_benchmark(c: &mut Criterion) {
     }
     group.finish();
     
+    // Benchmark concurrent text search (reads run in parallel on the read pool)
+    let mut group = c.benchmark_group("concurrent_text_search");
+    for &concurrency in &[1usize, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("parallel_searches", concurrency),
+            &concurrency,
+            |b, &concurrency| {
+                let database = databases[1].clone();
+                
+                b.to_async(&rt).iter(|| {
+                    let database = database.clone();
+                    async move {
+                        let handles: Vec<_> = (0..concurrency)
+                            .map(|_| {
+                                let database = database.clone();
+                                tokio::spawn(async move {
+                                    database.search_text(black_box("test"), 50).await.unwrap()
+                                })
+                            })
+                            .collect();
+                        for handle in handles {
+                            black_box(handle.await.unwrap());
+                        }
+                    }
+                });
+            },
+        );
+    }
+    group.finish();
+    
     // Benchmark semantic search
     let mut group = c.benchmark_group("semantic_search");
     for (i, &size) in sizes.iter().enumerate() {
@@ -129,6 +167,26 @@ fn search_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_events(count: u64) -> Vec<KeyEvent> {
+    (0..count)
+        .map(|i| KeyEvent {
+            timestamp: i,
+            key: "a".to_string(),
+            event_type: "press".to_string(),
+            window_info: Some(WindowInfo {
+                title: "Bench".to_string(),
+                application: "bench".to_string(),
+                process_id: None,
+                timestamp: i,
+            }),
+            is_modifier: false,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
+        })
+        .collect()
+}
+
 fn database_benchmark(c: &mut Criterion) {
     let rt = Runtime::new().unwrap();
     
@@ -145,8 +203,16 @@ fn database_benchmark(c: &mut Criterion) {
                     timestamp: i,
                     key: format!("key_{}", i),
                     event_type: "press".to_string(),
-                    window_title: Some("Test Window".to_string()),
-                    application: Some("Test App".to_string()),
+                    window_info: Some(WindowInfo {
+                        title: "Test Window".to_string(),
+                        application: "Test App".to_string(),
+                        process_id: None,
+                        timestamp: i,
+                    }),
+                    is_modifier: false,
+                    is_function_key: false,
+                    session_id: None,
+                    modifiers: Vec::new(),
                 })
                 .collect();
             
@@ -154,6 +220,35 @@ fn database_benchmark(c: &mut Criterion) {
         });
     });
     
+    // Prepared-statement batch insert vs. re-parsing the statement for every row
+    let events = bench_events(10_000);
+    group.bench_function("store_events_batch_10000", |b| {
+        b.to_async(&rt).iter(|| async {
+            let temp_file = NamedTempFile::new().unwrap();
+            let database = Database::new(temp_file.path()).await.unwrap();
+            database.store_events(black_box(&events)).await.unwrap();
+        });
+    });
+
+    group.bench_function("naive_insert_per_row_10000", |b| {
+        b.to_async(&rt).iter(|| async {
+            let temp_file = NamedTempFile::new().unwrap();
+            // Creates the schema, then inserts through a plain connection
+            drop(Database::new(temp_file.path()).await.unwrap());
+            let conn = rusqlite::Connection::open(temp_file.path()).unwrap();
+            let tx = conn.unchecked_transaction().unwrap();
+            for event in black_box(&events) {
+                tx.execute(
+                    "INSERT OR IGNORE INTO events 
+                    (timestamp, key, event_type, window_title, application, text_content)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    rusqlite::params![event.timestamp, event.key, event.event_type, "Bench", "bench", event.key],
+                ).unwrap();
+            }
+            tx.commit().unwrap();
+        });
+    });
+    
     // Benchmark database stats
     group.bench_function("get_stats", |b| {
         b.to_async(&rt).iter(|| async {
@@ -23,7 +23,7 @@ export default function SearchResults({ results, searchType }: SearchResultsProp
   }
 
   const formatTimestamp = (timestamp: number): string => {
-    const date = new Date(timestamp * 1000)
+    const date = new Date(timestamp)
     return format(date, "dd/MM/yyyy 'às' HH:mm:ss", { locale: ptBR })
   }
 
@@ -66,13 +66,13 @@ export default function StatusPanel({ stats, onRefresh }: StatusPanelProps) {
 
   const formatTimestamp = (timestamp?: number): string => {
     if (!timestamp) return 'N/A'
-    const date = new Date(timestamp * 1000)
+    const date = new Date(timestamp)
     return format(date, 'dd/MM/yyyy HH:mm', { locale: ptBR })
   }
 
   const formatRelativeTime = (timestamp?: number): string => {
     if (!timestamp) return 'N/A'
-    const date = new Date(timestamp * 1000)
+    const date = new Date(timestamp)
     return formatDistanceToNow(date, { addSuffix: true, locale: ptBR })
   }
 
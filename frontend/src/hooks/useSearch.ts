@@ -20,6 +20,8 @@ export function useSearch(options?: UseSearchOptions) {
   const [textWeight, setTextWeight] = useState(options?.defaultTextWeight ?? 0.7)
   const [semanticWeight, setSemanticWeight] = useState(options?.defaultSemanticWeight ?? 0.3)
   const [limit, setLimit] = useState(options?.defaultLimit ?? 50)
+  // Falso quando o backend não tem modelo de embeddings
+  const [semanticAvailable, setSemanticAvailable] = useState(true)
 
   // Executa a busca conforme o tipo
   const search = useCallback(async (q?: string, type?: SearchType) => {
@@ -37,6 +39,7 @@ export function useSearch(options?: UseSearchOptions) {
       } else if (searchMode === 'semantic') {
         response = await invoke('search_semantic', { query: searchQuery, limit })
         setResults(response.results)
+        setSemanticAvailable(true)
       } else {
         response = await invoke('search_hybrid', {
           query: searchQuery,
@@ -45,9 +48,13 @@ export function useSearch(options?: UseSearchOptions) {
           semantic_weight: semanticWeight,
         })
         setResults(response.results)
+        setSemanticAvailable(response.semantic_available)
       }
       setSearchTime(response.search_time_ms)
     } catch (err: any) {
+      if (err?.code === 'unavailable') {
+        setSemanticAvailable(false)
+      }
       setError(err?.message || String(err))
     } finally {
       setIsLoading(false)
@@ -86,6 +93,7 @@ export function useSearch(options?: UseSearchOptions) {
     setSemanticWeight,
     limit,
     setLimit,
+    semanticAvailable,
   }
 }
 
@@ -1,15 +1,23 @@
 export interface SearchResult {
   id: number
   content: string
+  // Milissegundos desde a época; timestamp_iso traz o mesmo instante em ISO-8601
   timestamp: number
+  timestamp_iso: string
   relevance_score: number
   context?: string
+  highlight?: string
+  window_title?: string
+  // Eventos vizinhos da mesma sessão, quando a busca pede context_size
+  context_before?: StoredEvent[]
+  context_after?: StoredEvent[]
 }
 
 export interface HybridSearchResult {
   id: number
   content: string
   timestamp: number
+  timestamp_iso: string
   text_score: number
   semantic_score: number
   combined_score: number
@@ -18,27 +26,171 @@ export interface HybridSearchResult {
 
 export interface SearchResponse {
   results: SearchResult[]
+  // Total de ocorrências da consulta; next_offset vem preenchido quando has_more
   total_count: number
   search_time_ms: number
+  has_more: boolean
+  next_offset?: number
 }
 
 export interface HybridSearchResponse {
   results: HybridSearchResult[]
   total_count: number
   search_time_ms: number
+  semantic_available: boolean
 }
 
 export interface AgentStatus {
   is_running: boolean
+  is_paused: boolean
   uptime_seconds: number
   events_captured: number
 }
 
+export interface WindowInfo {
+  title: string
+  application: string
+  process_id?: number
+  timestamp: number
+}
+
+// Payload do evento Tauri `keyai://event` (já mascarado)
+export interface KeyEvent {
+  timestamp: number
+  key: string
+  event_type: string
+  window_info?: WindowInfo
+  is_modifier: boolean
+  is_function_key: boolean
+  session_id?: string
+  modifiers: string[]
+}
+
+// Payload do evento Tauri `keyai://capture-error` (listener de teclas parou)
+export interface CaptureError {
+  reason: string
+  timestamp: number
+}
+
+// Evento gravado, retornado por `poll_new_events` em ordem crescente de id
+export interface StoredEvent {
+  id: number
+  timestamp: number
+  timestamp_iso: string
+  key: string
+  event_type: string
+  window_title?: string
+  application?: string
+  text_content?: string
+  created_at: string
+  session_id?: string
+  modifiers?: string
+}
+
+export interface PermissionStatus {
+  accessibility: boolean
+  window_detection: boolean
+  platform: string
+}
+
 export interface DatabaseStats {
   total_events: number
   total_size_bytes: number
   oldest_event?: number
   newest_event?: number
+  events_bytes: number
+  embeddings_bytes: number
+  fts_bytes: number
+  events_by_application: ApplicationCount[]
+}
+
+export interface EventSummary {
+  event_count: number
+  oldest_event?: number
+  newest_event?: number
+  events_by_application: ApplicationCount[]
+}
+
+// Resultado de clear_data/delete_events; em preview nada foi removido
+export interface DeletionReport extends EventSummary {
+  preview: boolean
+}
+
+// Resultado de compact_history: teclas avulsas juntadas em segmentos
+export interface CompactionReport {
+  preview: boolean
+  segments: number
+  events_merged: number
+}
+
+// Backup agendado em backup_dir (list_backups); created_at em milissegundos
+export interface BackupInfo {
+  path: string
+  created_at: number
+  size_bytes: number
+}
+
+// Resultado de preview_masking; nada é gravado
+export interface MaskingResult {
+  masked_text: string
+  detected_patterns: string[]
+  pattern_counts: Record<string, number>
+}
+
+// Resultado de validate_masker_pattern; só padrões `valid` são aceitos
+export interface PatternValidation {
+  valid: boolean
+  compile_error?: string
+  estimated_risk: 'low' | 'high'
+}
+
+// Perfil de captura com banco próprio (list_profiles/switch_profile)
+export interface ProfileInfo {
+  name: string
+  active: boolean
+}
+
+export interface ApplicationCount {
+  application?: string
+  event_count: number
+}
+
+export interface SessionSummary {
+  session_id: string
+  started_at: number
+  ended_at: number
+  event_count: number
+}
+
+export interface ShortcutMatch {
+  id: number
+  timestamp: number
+  key: string
+  modifiers: string[]
+  window_title?: string
+  application?: string
+}
+
+// Offsets em bytes UTF-8 dentro de `content`
+export interface MatchSpan {
+  start: number
+  end: number
+}
+
+export interface RegexMatch {
+  id: number
+  timestamp: number
+  content: string
+  application?: string
+  window_title?: string
+  spans: MatchSpan[]
+}
+
+export interface EmbeddingInfo {
+  model_name?: string
+  dimension?: number
+  indexed_events: number
+  stale_embeddings: number
 }
 
 export interface AppStats {
@@ -48,8 +200,48 @@ export interface AppStats {
 
 export interface SearchOptions {
   limit?: number
+  offset?: number
   text_weight?: number
   semantic_weight?: number
+  advanced?: boolean
+  column_weights?: ColumnWeights
+  snippet_tokens?: number
+  highlight_start?: string
+  highlight_end?: string
+  window_title_contains?: string
+  // false exige os mesmos acentos da consulta (padrão: true)
+  diacritic_insensitive?: boolean
+  // Fusão híbrida (RRF): constante k e quantos resultados de cada fonte entram
+  rrf_k?: number
+  text_top_k?: number
+  semantic_top_k?: number
+  // Tipos de evento considerados; sem o campo, "press", "paste" e "segment"
+  event_types?: string[]
+  // Eventos vizinhos anexados a cada resultado textual (0 = nenhum)
+  context_size?: number
+}
+
+export interface ColumnWeights {
+  content: number
+  application: number
+  window_title: number
+}
+
+// Erro retornado pelos comandos Tauri
+export type CommandErrorCode =
+  | 'database'
+  | 'search'
+  | 'agent'
+  | 'permission'
+  | 'validation'
+  | 'unavailable'
+  | 'cancelled'
+  | 'timeout'
+  | 'internal'
+
+export interface CommandError {
+  code: CommandErrorCode
+  message: string
 }
 
 export type SearchType = 'text' | 'semantic' | 'hybrid'
@@ -0,0 +1,150 @@
+//! Captura de texto colado (Ctrl/Cmd+V).
+//!
+//! O rdev só enxerga o `v` da combinação, então o conteúdo colado é lido da
+//! área de transferência no momento em que a combinação é detectada.
+
+use std::collections::HashSet;
+use tracing::debug;
+
+use super::KeyEvent;
+
+/// Tipo de evento usado para inserções vindas da área de transferência
+pub const PASTE_EVENT_TYPE: &str = "paste";
+
+/// Limite de caracteres armazenados por colagem
+pub const MAX_PASTE_CHARS: usize = 10_000;
+
+/// Fonte do texto da área de transferência
+pub trait ClipboardSource: Send + Sync {
+    fn read_text(&self) -> Option<String>;
+}
+
+/// Área de transferência do sistema via `arboard`
+pub struct SystemClipboard;
+
+impl ClipboardSource for SystemClipboard {
+    fn read_text(&self) -> Option<String> {
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) => Some(text),
+            Err(e) => {
+                debug!("📋 Não foi possível ler a área de transferência: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Acompanha Ctrl/Cmd pressionados para reconhecer a combinação de colar
+#[derive(Debug, Default)]
+pub struct PasteDetector {
+    held: HashSet<String>,
+}
+
+impl PasteDetector {
+    /// Atualiza o estado com o evento e retorna `true` se ele completa Ctrl/Cmd+V
+    pub fn observe(&mut self, event: &KeyEvent) -> bool {
+        if Self::is_paste_modifier(&event.key) {
+            match event.event_type.as_str() {
+                "press" => {
+                    self.held.insert(event.key.clone());
+                }
+                "release" => {
+                    self.held.remove(&event.key);
+                }
+                _ => {}
+            }
+            return false;
+        }
+
+        event.event_type == "press" && event.key.eq_ignore_ascii_case("v") && !self.held.is_empty()
+    }
+
+    fn is_paste_modifier(key: &str) -> bool {
+        matches!(key, "CtrlLeft" | "CtrlRight" | "MetaLeft" | "MetaRight")
+    }
+}
+
+/// Monta o evento de colagem a partir do `v` que disparou a combinação
+pub fn paste_event(trigger: &KeyEvent, text: String) -> Option<KeyEvent> {
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    let text = if text.chars().count() > MAX_PASTE_CHARS {
+        text.chars().take(MAX_PASTE_CHARS).collect()
+    } else {
+        text
+    };
+
+    Some(KeyEvent {
+        timestamp: trigger.timestamp,
+        key: text,
+        event_type: PASTE_EVENT_TYPE.to_string(),
+        window_info: trigger.window_info.clone(),
+        is_modifier: false,
+        is_function_key: false,
+        session_id: trigger.session_id.clone(),
+        modifiers: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str, event_type: &str, is_modifier: bool) -> KeyEvent {
+        KeyEvent {
+            timestamp: 1,
+            key: key.to_string(),
+            event_type: event_type.to_string(),
+            window_info: None,
+            is_modifier,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_detects_ctrl_and_cmd_v() {
+        let mut detector = PasteDetector::default();
+
+        assert!(!detector.observe(&key("v", "press", false)));
+
+        assert!(!detector.observe(&key("CtrlLeft", "press", true)));
+        assert!(detector.observe(&key("v", "press", false)));
+        assert!(!detector.observe(&key("v", "release", false)));
+        assert!(!detector.observe(&key("CtrlLeft", "release", true)));
+
+        assert!(!detector.observe(&key("v", "press", false)));
+
+        assert!(!detector.observe(&key("MetaRight", "press", true)));
+        assert!(detector.observe(&key("V", "press", false)));
+    }
+
+    #[test]
+    fn test_other_modifiers_do_not_trigger() {
+        let mut detector = PasteDetector::default();
+
+        detector.observe(&key("ShiftLeft", "press", true));
+        assert!(!detector.observe(&key("v", "press", false)));
+
+        detector.observe(&key("CtrlRight", "press", true));
+        assert!(!detector.observe(&key("c", "press", false)));
+    }
+
+    #[test]
+    fn test_paste_event_from_trigger() {
+        let trigger = key("v", "press", false);
+
+        let event = paste_event(&trigger, "texto colado".to_string()).unwrap();
+        assert_eq!(event.event_type, PASTE_EVENT_TYPE);
+        assert_eq!(event.key, "texto colado");
+        assert_eq!(event.timestamp, trigger.timestamp);
+
+        assert!(paste_event(&trigger, "   ".to_string()).is_none());
+
+        let long = "x".repeat(MAX_PASTE_CHARS + 10);
+        assert_eq!(paste_event(&trigger, long).unwrap().key.chars().count(), MAX_PASTE_CHARS);
+    }
+}
@@ -0,0 +1,146 @@
+//! Transmissão ao vivo dos eventos capturados para a interface.
+//!
+//! Cada evento já mascarado é enviado ao frontend como um evento Tauri,
+//! com limite de eventos por segundo para não sobrecarregar a interface
+//! durante rajadas de digitação.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+use tracing::debug;
+
+use super::{CaptureError, KeyEvent};
+
+/// Nome do evento Tauri escutado pelo frontend
+pub const LIVE_EVENT_NAME: &str = "keyai://event";
+
+/// Evento Tauri emitido quando o listener de teclas para com erro
+pub const CAPTURE_ERROR_EVENT_NAME: &str = "keyai://capture-error";
+
+/// Destino dos eventos ao vivo
+pub trait LiveEventEmitter: Send + Sync {
+    fn emit(&self, event: &KeyEvent);
+
+    /// Falha do listener de captura; por padrão é ignorada
+    fn emit_capture_error(&self, _error: &CaptureError) {}
+}
+
+/// Emite para todas as janelas da aplicação Tauri
+pub struct TauriEmitter {
+    app: tauri::AppHandle,
+}
+
+impl TauriEmitter {
+    pub fn new(app: tauri::AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl LiveEventEmitter for TauriEmitter {
+    fn emit(&self, event: &KeyEvent) {
+        if let Err(e) = self.app.emit_all(LIVE_EVENT_NAME, event) {
+            debug!("📡 Falha ao emitir evento ao vivo: {}", e);
+        }
+    }
+
+    fn emit_capture_error(&self, error: &CaptureError) {
+        if let Err(e) = self.app.emit_all(CAPTURE_ERROR_EVENT_NAME, error) {
+            debug!("📡 Falha ao emitir erro de captura: {}", e);
+        }
+    }
+}
+
+/// Emissor com limite de eventos por segundo, usado pelo processador
+pub(super) struct LiveFeed {
+    emitter: Option<Arc<dyn LiveEventEmitter>>,
+    window_start: Option<Instant>,
+    sent_in_window: u32,
+}
+
+impl LiveFeed {
+    pub(super) fn new(emitter: Option<Arc<dyn LiveEventEmitter>>) -> Self {
+        Self {
+            emitter,
+            window_start: None,
+            sent_in_window: 0,
+        }
+    }
+
+    /// Emite o evento se o limite do segundo atual permitir (0 = sem limite).
+    /// Retorna `true` quando o evento foi emitido
+    pub(super) fn publish(&mut self, event: &KeyEvent, max_per_sec: u32, now: Instant) -> bool {
+        let Some(emitter) = &self.emitter else {
+            return false;
+        };
+
+        let window_expired = self
+            .window_start
+            .map(|start| now.duration_since(start) >= Duration::from_secs(1))
+            .unwrap_or(true);
+        if window_expired {
+            self.window_start = Some(now);
+            self.sent_in_window = 0;
+        }
+
+        if max_per_sec > 0 && self.sent_in_window >= max_per_sec {
+            return false;
+        }
+
+        self.sent_in_window += 1;
+        emitter.emit(event);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct CountingEmitter(Mutex<usize>);
+
+    impl LiveEventEmitter for CountingEmitter {
+        fn emit(&self, _event: &KeyEvent) {
+            *self.0.lock().unwrap() += 1;
+        }
+    }
+
+    fn event() -> KeyEvent {
+        KeyEvent {
+            timestamp: 1,
+            key: "a".to_string(),
+            event_type: "press".to_string(),
+            window_info: None,
+            is_modifier: false,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_publish_throttles_per_second() {
+        let emitter = Arc::new(CountingEmitter::default());
+        let mut feed = LiveFeed::new(Some(emitter.clone()));
+        let start = Instant::now();
+
+        let sent = (0..5).filter(|_| feed.publish(&event(), 2, start)).count();
+        assert_eq!(sent, 2);
+
+        // Um novo segundo libera a cota novamente
+        assert!(feed.publish(&event(), 2, start + Duration::from_secs(1)));
+        assert_eq!(*emitter.0.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_publish_unlimited_and_without_emitter() {
+        let emitter = Arc::new(CountingEmitter::default());
+        let mut feed = LiveFeed::new(Some(emitter.clone()));
+        let now = Instant::now();
+        assert!((0..100).all(|_| feed.publish(&event(), 0, now)));
+
+        let mut silent = LiveFeed::new(None);
+        assert!(!silent.publish(&event(), 0, now));
+    }
+}
@@ -1,15 +1,19 @@
 use std::sync::{Arc, atomic::{AtomicBool, AtomicU64, Ordering}};
 use std::time::{SystemTime, UNIX_EPOCH, Duration, Instant};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinHandle;
 use tokio::time::{sleep, interval};
-use tracing::{info, warn, error, debug, trace};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn, error, debug, trace, instrument};
 use rdev::{listen, Event, EventType, Key};
 use anyhow::{Result, anyhow};
 use serde::{Serialize, Deserialize};
+use regex::Regex;
 
-use crate::masker::Masker;
-use crate::db::Database;
+use crate::masker::{Masker, MaskingLevel};
+use crate::db::{BackupInfo, Database};
 
 // Platform-specific imports
 #[cfg(target_os = "windows")]
@@ -26,17 +30,52 @@ use winapi::shared::windef::HWND;
 #[cfg(target_os = "linux")]
 use x11::xlib::{Display, XOpenDisplay, XGetWindowProperty, XFree, XDefaultRootWindow};
 
+mod clipboard;
+pub use clipboard::{ClipboardSource, SystemClipboard, PASTE_EVENT_TYPE};
+use clipboard::PasteDetector;
+
+pub mod shortcuts;
+
+mod permissions;
+pub use permissions::{PermissionDenied, PermissionStatus};
+
+mod typing;
+use typing::TypingActivity;
+
+mod live;
+pub use live::{LiveEventEmitter, TauriEmitter, CAPTURE_ERROR_EVENT_NAME, LIVE_EVENT_NAME};
+
+mod sink;
+pub use sink::{CompositeSink, EventSink, SqliteSink};
+use live::LiveFeed;
+use shortcuts::ModifierTracker;
+
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(target_os = "linux")]
+use wayland::LinuxWindowBackend;
+
 /// Configurações do agente de captura
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AgentConfig {
     /// Intervalo de flush do buffer em segundos
     pub flush_interval_secs: u64,
-    /// Tamanho máximo do buffer antes do flush forçado
+    /// Quantidade de eventos no buffer que dispara o flush
     pub buffer_size: usize,
+    /// Limite rígido do buffer. Enquanto um lote é gravado o processador
+    /// continua acumulando; ao atingir o limite ele espera a gravação e grava o
+    /// buffer antes de aceitar novos eventos (flush forçado). Também é a
+    /// capacidade da fila do listener, que só descarta eventos se encher durante
+    /// essa espera (`events_dropped_overflow`); vale a partir do próximo `start`
+    pub max_buffer_size: usize,
     /// Capturar apenas teclas de texto (ignorar modificadores)
     pub text_keys_only: bool,
     /// Lista de aplicações a ignorar
     pub ignored_applications: Vec<String>,
+    /// Capturar somente nestas aplicações. Quando definida, tem prioridade sobre
+    /// `ignored_applications`, que deixa de ser consultada
+    pub allowed_applications: Option<Vec<String>>,
     /// Lista de títulos de janela a ignorar (regex)
     pub ignored_window_patterns: Vec<String>,
     /// Ativar captura de modificadores (Ctrl, Alt, etc.)
@@ -47,6 +86,33 @@ pub struct AgentConfig {
     pub enable_window_detection: bool,
     /// Intervalo de atualização de janela ativa em ms
     pub window_update_interval_ms: u64,
+    /// Janela em ms para colapsar repetições da mesma tecla segurada (0 desativa)
+    pub collapse_repeats_ms: u64,
+    /// Armazenar o texto colado com Ctrl/Cmd+V (lê a área de transferência)
+    pub capture_clipboard: bool,
+    /// Segundos sem teclas que encerram a sessão atual e iniciam uma nova
+    pub session_idle_secs: u64,
+    /// Enviar cada evento mascarado ao frontend (`keyai://event`)
+    pub emit_live_events: bool,
+    /// Máximo de eventos ao vivo por segundo (0 = sem limite)
+    pub live_events_per_sec: u32,
+    /// Dias de histórico mantidos; eventos mais antigos são removidos (0 = manter tudo)
+    pub retention_days: u32,
+    /// Pasta (ex.: uma unidade sincronizada) que recebe cópias periódicas do banco
+    pub backup_dir: Option<PathBuf>,
+    /// Intervalo entre backups em horas (0 desativa)
+    pub backup_interval_hours: u64,
+    /// Backups mantidos em `backup_dir`; os mais antigos são removidos
+    pub backups_kept: usize,
+    /// Nível de mascaramento por aplicação (parte do nome, sem diferenciar
+    /// maiúsculas); as demais usam `MaskingLevel::Standard`
+    pub app_masking_policies: HashMap<String, MaskingLevel>,
+    /// Grava o buffer ao trocar de janela, para que cada lote pertença a uma só janela
+    pub flush_on_window_change: bool,
+    /// PID do próprio KeyAI, lido ao iniciar; teclas digitadas nas suas janelas
+    /// nunca são gravadas, qualquer que seja o nome reportado da aplicação
+    #[serde(skip)]
+    pub self_process_id: Option<u32>,
 }
 
 impl Default for AgentConfig {
@@ -54,6 +120,7 @@ impl Default for AgentConfig {
         Self {
             flush_interval_secs: 5,
             buffer_size: 100,
+            max_buffer_size: 1_000,
             text_keys_only: false,
             ignored_applications: vec![
                 "keyai-desktop".to_string(),
@@ -62,6 +129,7 @@ impl Default for AgentConfig {
                 "bitwarden".to_string(),
                 "lastpass".to_string(),
             ],
+            allowed_applications: None,
             ignored_window_patterns: vec![
                 r".*[Pp]assword.*".to_string(),
                 r".*[Ll]ogin.*".to_string(),
@@ -71,6 +139,18 @@ impl Default for AgentConfig {
             capture_function_keys: true,
             enable_window_detection: true,
             window_update_interval_ms: 500,
+            collapse_repeats_ms: 0,
+            capture_clipboard: false,
+            session_idle_secs: 300,
+            emit_live_events: false,
+            live_events_per_sec: 30,
+            retention_days: 0,
+            backup_dir: None,
+            backup_interval_hours: 24,
+            backups_kept: 7,
+            app_masking_policies: HashMap::new(),
+            flush_on_window_change: true,
+            self_process_id: Some(std::process::id()),
         }
     }
 }
@@ -84,6 +164,24 @@ pub struct WindowInfo {
     pub timestamp: u64,
 }
 
+/// Falha do listener de teclas, enviada ao frontend para que ele ofereça
+/// conceder a permissão e reiniciar a captura
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureError {
+    pub reason: String,
+    pub timestamp: u64,
+}
+
+impl CaptureError {
+    fn new(reason: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        Self { reason, timestamp }
+    }
+}
+
 /// Evento de tecla capturado
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyEvent {
@@ -93,16 +191,43 @@ pub struct KeyEvent {
     pub window_info: Option<WindowInfo>,
     pub is_modifier: bool,
     pub is_function_key: bool,
+    /// Sessão de digitação atribuída pelo processador (nova após inatividade)
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Modificadores segurados quando a tecla foi pressionada (ex.: `["Ctrl"]`)
+    #[serde(default)]
+    pub modifiers: Vec<String>,
 }
 
 /// Métricas do agente
 #[derive(Debug, Default)]
 pub struct AgentMetrics {
+    /// Eventos recebidos do listener
     pub events_captured: AtomicU64,
+    /// Eventos que passaram pelos filtros e foram mascarados
     pub events_processed: AtomicU64,
+    /// Eventos persistidos com sucesso no banco de dados
+    pub events_stored: AtomicU64,
+    /// Eventos descartados pelos filtros de configuração
+    pub events_filtered: AtomicU64,
+    /// Eventos perdidos por falha ao armazenar
     pub events_discarded: AtomicU64,
+    /// Mudanças de janela ativa detectadas
+    pub window_updates: AtomicU64,
+    /// Eventos descartados na origem enquanto a captura está pausada
+    pub events_dropped_paused: AtomicU64,
+    /// Repetições automáticas de tecla colapsadas
+    pub events_deduplicated: AtomicU64,
+    /// Colagens capturadas da área de transferência
+    pub events_pasted: AtomicU64,
+    /// Flushes disparados por atingir `max_buffer_size`
+    pub forced_flushes: AtomicU64,
+    /// Eventos descartados com a fila do processador cheia (`max_buffer_size`)
+    pub events_dropped_overflow: AtomicU64,
     pub last_event_timestamp: AtomicU64,
     pub uptime_start: AtomicU64,
+    /// Ritmo das teclas pressionadas, para `keys_per_minute` e `active_typing_seconds`
+    typing: std::sync::Mutex<TypingActivity>,
 }
 
 impl AgentMetrics {
@@ -117,98 +242,402 @@ impl AgentMetrics {
         let mut summary = HashMap::new();
         summary.insert("events_captured".to_string(), self.events_captured.load(Ordering::Relaxed));
         summary.insert("events_processed".to_string(), self.events_processed.load(Ordering::Relaxed));
+        summary.insert("events_stored".to_string(), self.events_stored.load(Ordering::Relaxed));
+        summary.insert("events_filtered".to_string(), self.events_filtered.load(Ordering::Relaxed));
         summary.insert("events_discarded".to_string(), self.events_discarded.load(Ordering::Relaxed));
+        summary.insert("window_updates".to_string(), self.window_updates.load(Ordering::Relaxed));
+        summary.insert("events_dropped_paused".to_string(), self.events_dropped_paused.load(Ordering::Relaxed));
+        summary.insert("events_deduplicated".to_string(), self.events_deduplicated.load(Ordering::Relaxed));
+        summary.insert("events_pasted".to_string(), self.events_pasted.load(Ordering::Relaxed));
+        summary.insert("forced_flushes".to_string(), self.forced_flushes.load(Ordering::Relaxed));
+        summary.insert("events_dropped_overflow".to_string(), self.events_dropped_overflow.load(Ordering::Relaxed));
         summary.insert("last_event_timestamp".to_string(), self.last_event_timestamp.load(Ordering::Relaxed));
-        summary.insert("uptime_seconds".to_string(), self.uptime_start.load(Ordering::Relaxed) as u64);
+        summary.insert("uptime_seconds".to_string(), self.uptime_seconds());
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let typing = self.typing();
+        summary.insert("keys_per_minute".to_string(), typing.keys_per_minute(now));
+        summary.insert("active_typing_seconds".to_string(), typing.active_seconds());
         
         summary
     }
+
+    /// Registra uma tecla pressionada, com `timestamp` em milissegundos, para
+    /// o cálculo do ritmo de digitação
+    pub fn record_keystroke(&self, timestamp: u64) {
+        self.typing().record(timestamp / 1000);
+    }
+
+    fn typing(&self) -> std::sync::MutexGuard<'_, TypingActivity> {
+        self.typing.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Segundos desde o início da captura (0 quando o agente está parado)
+    pub fn uptime_seconds(&self) -> u64 {
+        let start = self.uptime_start.load(Ordering::Relaxed);
+        if start == 0 {
+            return 0;
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        now.saturating_sub(start)
+    }
+}
+
+/// Colapsa as repetições automáticas geradas ao segurar uma tecla
+#[derive(Debug, Default)]
+struct RepeatCollapser {
+    /// Tecla segurada e timestamp (ms) do último press dela
+    last_press: Option<(String, u64)>,
+}
+
+impl RepeatCollapser {
+    /// Retorna `true` quando o evento repete a última tecla pressionada
+    /// dentro da janela configurada e deve ser descartado. O intervalo vem dos
+    /// timestamps de captura, não do momento em que o processador recebe o
+    /// evento, que pode chegar atrasado em rajadas
+    fn is_repeat(&mut self, event: &KeyEvent, window: Duration) -> bool {
+        // Qualquer mudança de modificador encerra a sequência de repetição
+        if event.is_modifier {
+            self.last_press = None;
+            return false;
+        }
+
+        if event.event_type == "release" {
+            // Soltar a tecla encerra a repetição: o próximo press é digitação
+            if self.last_press.as_ref().is_some_and(|(key, _)| *key == event.key) {
+                self.last_press = None;
+            }
+            return false;
+        }
+
+        if window.is_zero() || event.event_type != "press" {
+            return false;
+        }
+
+        if let Some((key, last_seen)) = &mut self.last_press {
+            let elapsed = Duration::from_millis(event.timestamp.saturating_sub(*last_seen));
+            if *key == event.key && elapsed <= window {
+                *last_seen = event.timestamp;
+                return true;
+            }
+        }
+
+        self.last_press = Some((event.key.clone(), event.timestamp));
+        false
+    }
+}
+
+/// Gravação do buffer fora do laço do processador, com no máximo um lote em
+/// andamento: enquanto o banco está lento, a fila continua sendo drenada
+struct BackgroundFlush {
+    sink: Arc<dyn EventSink>,
+    metrics: Arc<AgentMetrics>,
+    in_flight: Option<JoinHandle<()>>,
+}
+
+impl BackgroundFlush {
+    fn new(sink: Arc<dyn EventSink>, metrics: Arc<AgentMetrics>) -> Self {
+        Self { sink, metrics, in_flight: None }
+    }
+
+    fn busy(&self) -> bool {
+        self.in_flight.as_ref().is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Passa o buffer para uma gravação em segundo plano; com outra ainda em
+    /// andamento, espera por ela antes, para manter a ordem dos lotes
+    async fn start(&mut self, buffer: &mut Vec<KeyEvent>) {
+        self.wait().await;
+        if buffer.is_empty() {
+            return;
+        }
+        let mut batch = std::mem::take(buffer);
+        let (sink, metrics) = (self.sink.clone(), self.metrics.clone());
+        self.in_flight = Some(tokio::spawn(async move {
+            Agent::flush_events(&sink, &mut batch, &metrics).await;
+        }));
+    }
+
+    async fn wait(&mut self) {
+        if let Some(handle) = self.in_flight.take() {
+            let _ = handle.await;
+        }
+    }
+
+    /// Espera a gravação em andamento e grava o buffer antes de retornar
+    async fn flush_now(&mut self, buffer: &mut Vec<KeyEvent>) {
+        self.wait().await;
+        Agent::flush_events(&self.sink, buffer, &self.metrics).await;
+    }
+
+    /// Adiciona o evento ao buffer. No limite `max_buffer_size` o processador
+    /// para aqui até o banco alcançá-lo, e os eventos novos esperam na fila
+    async fn push_bounded(&mut self, buffer: &mut Vec<KeyEvent>, event: KeyEvent, max_buffer_size: usize) {
+        if buffer.len() >= max_buffer_size.max(1) {
+            self.metrics.forced_flushes.fetch_add(1, Ordering::Relaxed);
+            warn!("⚠️ Buffer atingiu o limite de {} eventos; flush forçado", max_buffer_size);
+            self.flush_now(buffer).await;
+        }
+        buffer.push(event);
+    }
+}
+
+/// Tentativas de gravação de um lote antes de descartá-lo
+const FLUSH_MAX_ATTEMPTS: u32 = 3;
+
+/// Espera antes da primeira nova tentativa (dobra a cada falha)
+const FLUSH_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Frequência da limpeza por retenção
+const RETENTION_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Timestamp (ms) a partir do qual os eventos são mantidos (`None` quando a
+/// retenção está desativada)
+fn retention_cutoff(now: u64, retention_days: u32) -> Option<u64> {
+    if retention_days == 0 {
+        return None;
+    }
+    Some(now.saturating_sub(retention_days as u64 * 24 * 60 * 60 * 1000))
+}
+
+/// Frequência com que o agendador verifica se um backup está pendente
+const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Faz o backup agendado se `backup_interval_hours` já passou desde o último
+/// backup da pasta. `now` em milissegundos; retorna `None` quando não era hora
+pub(crate) async fn run_scheduled_backup(
+    database: &Database,
+    config: &AgentConfig,
+    now: u64,
+) -> Result<Option<BackupInfo>> {
+    let Some(dir) = &config.backup_dir else {
+        return Ok(None);
+    };
+    if config.backup_interval_hours == 0 {
+        return Ok(None);
+    }
+
+    // The newest file in the folder survives app restarts, unlike an in-memory timer
+    let interval_ms = config.backup_interval_hours.saturating_mul(60 * 60 * 1000);
+    if let Some(last) = crate::db::list_backups(dir)?.first() {
+        if now.saturating_sub(last.created_at) < interval_ms {
+            return Ok(None);
+        }
+    }
+
+    database.backup_into_dir(dir, now, config.backups_kept).await.map(Some)
+}
+
+/// Agrupa eventos em sessões separadas por períodos de inatividade
+#[derive(Debug, Default)]
+struct SessionTracker {
+    current: Option<(String, u64)>,
+}
+
+impl SessionTracker {
+    /// Retorna a sessão do evento (`timestamp` em ms), iniciando uma nova quando
+    /// o intervalo desde o último evento excede `idle_secs`
+    fn assign(&mut self, timestamp: u64, idle_secs: u64) -> String {
+        if let Some((session_id, last_seen)) = &mut self.current {
+            if timestamp.saturating_sub(*last_seen) <= idle_secs.saturating_mul(1000) {
+                *last_seen = (*last_seen).max(timestamp);
+                return session_id.clone();
+            }
+        }
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        debug!("🆕 Nova sessão de digitação: {}", session_id);
+        self.current = Some((session_id.clone(), timestamp));
+        session_id
+    }
 }
 
 /// Agente principal de captura de teclas
 pub struct Agent {
     config: Arc<RwLock<AgentConfig>>,
-    masker: Masker,
+    window_patterns: Arc<RwLock<Vec<Regex>>>,
+    masker: Arc<RwLock<Masker>>,
     database: Arc<Database>,
     is_running: Arc<AtomicBool>,
-    event_sender: Option<mpsc::UnboundedSender<KeyEvent>>,
+    is_paused: Arc<AtomicBool>,
+    listener_alive: Arc<AtomicBool>,
+    event_sender: Option<mpsc::Sender<KeyEvent>>,
     current_window: Arc<RwLock<Option<WindowInfo>>>,
     metrics: Arc<AgentMetrics>,
     shutdown_signal: Arc<AtomicBool>,
+    processor_handle: Option<JoinHandle<()>>,
+    /// Cancelamento das tarefas periódicas; novo a cada `start`, para que
+    /// uma tarefa de um `start` anterior nunca volte a rodar
+    worker_cancel: CancellationToken,
+    worker_handles: Vec<JoinHandle<()>>,
+    clipboard: Arc<dyn ClipboardSource>,
+    live_emitter: Option<Arc<dyn LiveEventEmitter>>,
+    last_capture_error: Arc<std::sync::Mutex<Option<CaptureError>>>,
+    sink: Arc<dyn EventSink>,
 }
 
 impl Agent {
     /// Cria uma nova instância do agente
     pub async fn new(masker: Masker, database: Arc<Database>) -> Result<Self> {
-        Ok(Self {
-            config: Arc::new(RwLock::new(AgentConfig::default())),
-            masker,
-            database,
-            is_running: Arc::new(AtomicBool::new(false)),
-            event_sender: None,
-            current_window: Arc::new(RwLock::new(None)),
-            metrics: Arc::new(AgentMetrics::new()),
-            shutdown_signal: Arc::new(AtomicBool::new(false)),
-        })
+        let config = Self::load_saved_config(&database).await;
+        Self::with_config(masker, database, config).await
+    }
+
+    /// Configuração salva no banco, ou a padrão se não houver uma válida
+    async fn load_saved_config(database: &Database) -> AgentConfig {
+        let saved = match database.load_agent_config().await {
+            Ok(saved) => saved,
+            Err(e) => {
+                warn!("⚠️ Configuração salva do agente ilegível, usando padrão: {}", e);
+                None
+            }
+        };
+
+        match saved {
+            Some(config) if Self::compile_window_patterns(&config.ignored_window_patterns).is_ok() => {
+                info!("🔧 Configuração do agente restaurada");
+                config
+            }
+            Some(_) => {
+                warn!("⚠️ Configuração salva do agente contém padrões inválidos, usando padrão");
+                AgentConfig::default()
+            }
+            None => AgentConfig::default(),
+        }
     }
 
     /// Cria uma nova instância com configuração customizada
     pub async fn with_config(masker: Masker, database: Arc<Database>, config: AgentConfig) -> Result<Self> {
+        let window_patterns = Self::compile_window_patterns(&config.ignored_window_patterns)?;
+
         Ok(Self {
             config: Arc::new(RwLock::new(config)),
-            masker,
+            window_patterns: Arc::new(RwLock::new(window_patterns)),
+            masker: Arc::new(RwLock::new(masker)),
+            sink: Arc::new(SqliteSink::new(database.clone())),
             database,
             is_running: Arc::new(AtomicBool::new(false)),
+            is_paused: Arc::new(AtomicBool::new(false)),
+            listener_alive: Arc::new(AtomicBool::new(false)),
             event_sender: None,
             current_window: Arc::new(RwLock::new(None)),
             metrics: Arc::new(AgentMetrics::new()),
             shutdown_signal: Arc::new(AtomicBool::new(false)),
+            processor_handle: None,
+            worker_cancel: CancellationToken::new(),
+            worker_handles: Vec::new(),
+            clipboard: Arc::new(SystemClipboard),
+            live_emitter: None,
+            last_capture_error: Arc::new(std::sync::Mutex::new(None)),
         })
     }
 
+    /// Substitui a fonte da área de transferência usada na captura de colagens
+    pub fn set_clipboard_source(&mut self, source: Arc<dyn ClipboardSource>) {
+        self.clipboard = source;
+    }
+
+    /// Define o destino dos eventos ao vivo; vale a partir do próximo `start`
+    pub fn set_live_emitter(&mut self, emitter: Arc<dyn LiveEventEmitter>) {
+        self.live_emitter = Some(emitter);
+    }
+
+    /// Define onde os eventos são gravados (padrão: o banco do agente); vale a
+    /// partir do próximo `start`. `set_database` volta ao banco
+    pub fn set_sink(&mut self, sink: Arc<dyn EventSink>) {
+        self.sink = sink;
+    }
+
+    /// Contadores do agente, para leitura sem travar o agente
+    pub fn shared_metrics(&self) -> Arc<AgentMetrics> {
+        self.metrics.clone()
+    }
+
+    /// Masker compartilhado com os comandos, permitindo alterar padrões em tempo de execução
+    pub fn shared_masker(&self) -> Arc<RwLock<Masker>> {
+        self.masker.clone()
+    }
+
+    /// Compila os padrões de título de janela ignorados, falhando no primeiro inválido
+    pub fn compile_window_patterns(patterns: &[String]) -> Result<Vec<Regex>> {
+        patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .map_err(|e| anyhow!("Padrão de janela inválido '{}': {}", pattern, e))
+            })
+            .collect()
+    }
+
     /// Verifica se as permissões necessárias estão disponíveis
-    pub fn check_permissions() -> bool {
+    pub fn check_permissions() -> PermissionStatus {
         #[cfg(target_os = "macos")]
         {
-            info!("🔍 Verificando permissões de acessibilidade no macOS...");
-            
-            // Tentativa simples de verificar se temos permissões
-            // Vamos tentar capturar um evento de teste
-            let test_result = std::panic::catch_unwind(|| {
-                // Teste rápido para ver se o rdev funciona
-                let (tx, _rx) = std::sync::mpsc::channel();
-                
-                // Timeout muito curto para teste
-                let start = std::time::Instant::now();
-                std::thread::spawn(move || {
-                    if let Err(_) = rdev::listen(move |event| {
-                        let _ = tx.send(event);
-                    }) {
-                        // Erro esperado se não houver permissões
-                    }
-                });
-                
-                // Aguarda um pouco para ver se há erro imediato
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                start.elapsed() < std::time::Duration::from_millis(200)
-            });
-            
-            match test_result {
-                Ok(_) => {
-                    info!("✅ Permissões de acessibilidade parecem estar OK");
-                    true
-                },
-                Err(_) => {
-                    error!("❌ Erro ao verificar permissões - provavelmente sem acesso de acessibilidade");
-                    Self::show_macos_permission_dialog();
-                    false
-                }
-            }
+            PermissionStatus::macos(Self::probe_macos_accessibility())
         }
-        
-        #[cfg(not(target_os = "macos"))]
+
+        #[cfg(target_os = "linux")]
+        {
+            let window_backend_available = wayland::detect_backend() != LinuxWindowBackend::Unsupported;
+            PermissionStatus::linux(wayland::has_x11_display(), window_backend_available)
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            PermissionStatus::windows(Self::is_process_elevated())
+        }
+
+        #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
         {
-            true
+            PermissionStatus::other(std::env::consts::OS)
+        }
+    }
+
+    /// Consulta a permissão de Acessibilidade sem iniciar um listener, então
+    /// pode ser chamada a cada `get_permission_status`
+    #[cfg(target_os = "macos")]
+    fn probe_macos_accessibility() -> bool {
+        #[link(name = "ApplicationServices", kind = "framework")]
+        extern "C" {
+            fn AXIsProcessTrusted() -> u8;
+        }
+
+        let trusted = unsafe { AXIsProcessTrusted() } != 0;
+        debug!("🔍 Acessibilidade no macOS concedida: {}", trusted);
+        trusted
+    }
+
+    /// Sem elevação o Windows não expõe título/processo de aplicações elevadas
+    #[cfg(target_os = "windows")]
+    fn is_process_elevated() -> bool {
+        use winapi::um::securitybaseapi::GetTokenInformation;
+        use winapi::um::winnt::{TokenElevation, HANDLE, TOKEN_ELEVATION, TOKEN_QUERY};
+
+        unsafe {
+            let mut token: HANDLE = std::ptr::null_mut();
+            if processthreadsapi::OpenProcessToken(processthreadsapi::GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+                return false;
+            }
+
+            let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+            let mut returned = 0;
+            let ok = GetTokenInformation(
+                token,
+                TokenElevation,
+                &mut elevation as *mut _ as *mut _,
+                std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+                &mut returned,
+            );
+            handleapi::CloseHandle(token);
+
+            ok != 0 && elevation.TokenIsElevated != 0
         }
     }
     
@@ -222,20 +651,21 @@ impl Agent {
         error!("   4. Reinicie o aplicativo após conceder as permissões");
         
         // Tentar abrir as configurações automaticamente
-        std::process::Command::new("open")
+        let opened = std::process::Command::new("open")
             .arg("x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility")
             .spawn()
-            .unwrap_or_else(|_| {
-                warn!("Não foi possível abrir as configurações automaticamente");
-                std::process::Command::new("sh")
-                    .arg("-c")
-                    .arg("osascript -e 'tell app \"System Preferences\" to activate' -e 'tell app \"System Preferences\" to reveal anchor \"Privacy_Accessibility\" of pane id \"com.apple.preference.security\"'")
-                    .spawn()
-                    .unwrap_or_else(|_| {
-                        error!("Abra manualmente: Configurações do Sistema > Privacidade e Segurança > Acessibilidade");
-                        std::process::Command::new("echo").spawn().unwrap()
-                    })
-            });
+            .is_ok();
+        if !opened {
+            warn!("Não foi possível abrir as configurações automaticamente");
+            let revealed = std::process::Command::new("sh")
+                .arg("-c")
+                .arg("osascript -e 'tell app \"System Preferences\" to activate' -e 'tell app \"System Preferences\" to reveal anchor \"Privacy_Accessibility\" of pane id \"com.apple.preference.security\"'")
+                .spawn()
+                .is_ok();
+            if !revealed {
+                error!("Abra manualmente: Configurações do Sistema > Privacidade e Segurança > Acessibilidade");
+            }
+        }
     }
     
     #[cfg(not(target_os = "macos"))]
@@ -252,13 +682,18 @@ impl Agent {
 
         info!("🎯 Iniciando agente de captura de teclas...");
         
-        // Check permissions first
-        if !Self::check_permissions() {
-            return Err(anyhow!("Permissões insuficientes para captura de teclas"));
+        // Only macOS refuses the listener outright; elsewhere a listener that
+        // cannot start is reported through the capture error instead
+        #[cfg(target_os = "macos")]
+        if !Self::check_permissions().accessibility {
+            Self::show_macos_permission_dialog();
+            return Err(PermissionDenied.into());
         }
         
-        // Reset shutdown signal
+        // Reset shutdown and pause signals
         self.shutdown_signal.store(false, Ordering::Relaxed);
+        self.is_paused.store(false, Ordering::Relaxed);
+        self.worker_cancel = CancellationToken::new();
         
         // Set uptime start
         let now = SystemTime::now()
@@ -267,12 +702,13 @@ impl Agent {
             .as_secs();
         self.metrics.uptime_start.store(now, Ordering::Relaxed);
         
-        // Create channel for key events
-        let (tx, rx) = mpsc::unbounded_channel::<KeyEvent>();
+        // Create channel for key events, bounded so a stalled store cannot grow it
+        let capacity = self.config.read().await.max_buffer_size.max(1);
+        let (tx, rx) = mpsc::channel::<KeyEvent>(capacity);
         self.event_sender = Some(tx.clone());
         
         // Start event processing task
-        self.start_event_processor(rx).await?;
+        self.processor_handle = Some(self.start_event_processor(rx).await?);
         
         // Start window detection task if enabled
         let config = self.config.read().await;
@@ -284,8 +720,18 @@ impl Agent {
         // Start metrics reporter
         self.start_metrics_reporter().await?;
 
+        // Start retention cleanup
+        let retention = self.start_retention_worker().await?;
+        self.worker_handles.push(retention);
+
+        // Start scheduled backups
+        let backup = self.start_backup_worker().await?;
+        self.worker_handles.push(backup);
+
         // Start key listener (with special handling for macOS)
-        match self.start_key_listener(tx).await {
+        *self.last_capture_error.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+        let status_tx = self.start_status_forwarder();
+        match self.start_key_listener(tx, status_tx).await {
             Ok(()) => {
                 info!("✅ Listener de teclas iniciado");
                 #[cfg(target_os = "macos")]
@@ -324,32 +770,158 @@ impl Agent {
         self.shutdown_signal.store(true, Ordering::Relaxed);
         self.is_running.store(false, Ordering::Relaxed);
         self.event_sender = None;
+        self.metrics.uptime_start.store(0, Ordering::Relaxed);
+
+        // Wait for the processor to flush buffered events
+        if let Some(handle) = self.processor_handle.take() {
+            match tokio::time::timeout(Duration::from_secs(5), handle).await {
+                Ok(Ok(())) => debug!("✅ Buffer de eventos descarregado"),
+                Ok(Err(e)) => error!("❌ Processador de eventos falhou ao encerrar: {}", e),
+                Err(_) => warn!("⚠️ Timeout aguardando flush do processador de eventos"),
+            }
+        }
 
-        // Give tasks time to shutdown gracefully
+        self.stop_workers().await;
+
+        // Give remaining tasks time to shutdown gracefully
         sleep(Duration::from_millis(100)).await;
 
         info!("✅ Agente parado com sucesso");
         Ok(())
     }
 
+    /// Cancela as tarefas periódicas e espera que terminem; a que não
+    /// terminar a tempo é abortada para não usar o banco depois do `stop`
+    async fn stop_workers(&mut self) {
+        self.worker_cancel.cancel();
+        for mut handle in self.worker_handles.drain(..) {
+            if tokio::time::timeout(Duration::from_secs(5), &mut handle).await.is_err() {
+                warn!("⚠️ Timeout aguardando tarefa periódica, abortando");
+                handle.abort();
+                let _ = handle.await;
+            }
+        }
+    }
+
+    /// Encerra o agente de forma graciosa: para a captura, descarrega o
+    /// buffer e faz checkpoint do WAL antes de a aplicação fechar
+    pub async fn shutdown(&mut self) -> Result<()> {
+        info!("🔄 Encerrando agente graciosamente...");
+
+        self.stop().await?;
+        self.database.checkpoint_wal().await?;
+
+        info!("✅ Agente encerrado e WAL sincronizado");
+        Ok(())
+    }
+
+    /// Envia um evento diretamente para o processador (usado por fontes
+    /// de eventos além do listener de teclado). Falha sem esperar se a fila
+    /// estiver cheia
+    pub fn submit_event(&self, event: KeyEvent) -> Result<()> {
+        match &self.event_sender {
+            Some(sender) => sender.try_send(event).map_err(|e| {
+                if matches!(e, mpsc::error::TrySendError::Full(_)) {
+                    self.metrics.events_dropped_overflow.fetch_add(1, Ordering::Relaxed);
+                }
+                anyhow!("Erro ao enviar evento: {}", e)
+            }),
+            None => Err(anyhow!("Agente não está em execução")),
+        }
+    }
+
     /// Verifica se o agente está em execução
     pub fn is_running(&self) -> bool {
         self.is_running.load(Ordering::Relaxed)
     }
 
+    /// Pausa a captura sem encerrar a thread do listener
+    pub fn pause(&self) -> Result<()> {
+        if !self.is_running() {
+            return Err(anyhow!("Agente não está em execução"));
+        }
+
+        if !self.is_paused.swap(true, Ordering::Relaxed) {
+            info!("⏸️ Captura de teclas pausada");
+        }
+        Ok(())
+    }
+
+    /// Retoma a captura pausada
+    pub fn resume(&self) -> Result<()> {
+        if !self.is_running() {
+            return Err(anyhow!("Agente não está em execução"));
+        }
+
+        if self.is_paused.swap(false, Ordering::Relaxed) {
+            info!("▶️ Captura de teclas retomada");
+        }
+        Ok(())
+    }
+
+    /// Verifica se a captura está pausada
+    pub fn is_paused(&self) -> bool {
+        self.is_paused.load(Ordering::Relaxed)
+    }
+
+    /// Indica se a thread do rdev está de fato escutando o teclado
+    pub fn is_listener_alive(&self) -> bool {
+        self.listener_alive.load(Ordering::Relaxed)
+    }
+
+    /// Estado do agente e do listener de captura, para o health check
+    pub fn health(&self) -> HashMap<String, String> {
+        let mut status = HashMap::new();
+        let agent = if self.is_running() { "running" } else { "stopped" };
+        let listener = if self.is_listener_alive() { "alive" } else { "dead" };
+        status.insert("agent".to_string(), agent.to_string());
+        status.insert("capture_listener".to_string(), listener.to_string());
+        if let Some(error) = self.last_capture_error() {
+            status.insert("capture_error".to_string(), error.reason);
+        }
+        status
+    }
+
+    /// Última falha do listener desde o `start`, se houver
+    pub fn last_capture_error(&self) -> Option<CaptureError> {
+        self.last_capture_error.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
     /// Obtém as métricas atuais
     pub fn get_metrics(&self) -> HashMap<String, u64> {
         self.metrics.get_summary()
     }
 
-    /// Atualiza a configuração do agente
+    /// Atualiza a configuração do agente e a salva para as próximas execuções
     pub async fn update_config(&self, new_config: AgentConfig) -> Result<()> {
+        // Compile before applying so an invalid pattern leaves the old config intact
+        let window_patterns = Self::compile_window_patterns(&new_config.ignored_window_patterns)?;
+        self.database.save_agent_config(&new_config).await?;
+
         let mut config = self.config.write().await;
+        let mut patterns = self.window_patterns.write().await;
         *config = new_config;
+        *patterns = window_patterns;
         info!("🔧 Configuração do agente atualizada");
         Ok(())
     }
 
+    /// Passa a gravar em outro banco (troca de perfil), adotando a configuração
+    /// salva nele. O agente precisa estar parado
+    pub async fn set_database(&mut self, database: Arc<Database>) -> Result<()> {
+        if self.is_running() {
+            return Err(anyhow!("Pare o agente antes de trocar o banco de dados"));
+        }
+
+        let config = Self::load_saved_config(&database).await;
+        let window_patterns = Self::compile_window_patterns(&config.ignored_window_patterns)?;
+        *self.config.write().await = config;
+        *self.window_patterns.write().await = window_patterns;
+        self.sink = Arc::new(SqliteSink::new(database.clone()));
+        self.database = database;
+        Ok(())
+    }
+
     /// Obtém a configuração atual
     pub async fn get_config(&self) -> AgentConfig {
         self.config.read().await.clone()
@@ -361,62 +933,143 @@ impl Agent {
     }
 
     /// Inicia o processador de eventos
-    async fn start_event_processor(&self, mut rx: mpsc::UnboundedReceiver<KeyEvent>) -> Result<()> {
+    async fn start_event_processor(&self, mut rx: mpsc::Receiver<KeyEvent>) -> Result<JoinHandle<()>> {
         let masker = self.masker.clone();
-        let database = self.database.clone();
+        let sink = self.sink.clone();
         let config = self.config.clone();
+        let window_patterns = self.window_patterns.clone();
         let metrics = self.metrics.clone();
         let shutdown_signal = self.shutdown_signal.clone();
+        let clipboard_source = self.clipboard.clone();
+        let live_emitter = self.live_emitter.clone();
 
-        tokio::spawn(async move {
+        let handle = tokio::spawn(async move {
             let mut buffer = Vec::new();
             let mut last_flush = Instant::now();
+            let mut repeats = RepeatCollapser::default();
+            let mut paste_detector = PasteDetector::default();
+            let mut sessions = SessionTracker::default();
+            let mut modifier_state = ModifierTracker::default();
+            let mut live_feed = LiveFeed::new(live_emitter);
+            let mut flusher = BackgroundFlush::new(sink, metrics.clone());
+
+            loop {
+                let next = if shutdown_signal.load(Ordering::Relaxed) {
+                    // Drain events still queued when shutdown was requested
+                    match rx.try_recv() {
+                        Ok(event) => Some(event),
+                        Err(_) => break,
+                    }
+                } else {
+                    // Try to receive events with timeout
+                    match tokio::time::timeout(Duration::from_millis(100), rx.recv()).await {
+                        Ok(Some(event)) => Some(event),
+                        Ok(None) => break, // Channel closed
+                        Err(_) => None, // Timeout, check shutdown signal
+                    }
+                };
 
-            while !shutdown_signal.load(Ordering::Relaxed) {
-                // Try to receive events with timeout
-                match tokio::time::timeout(Duration::from_millis(100), rx.recv()).await {
-                    Ok(Some(event)) => {
-                        trace!("📝 Evento recebido: {:?}", event);
-                        metrics.events_captured.fetch_add(1, Ordering::Relaxed);
-                        
-                        // Check if event should be filtered
-                        let config_guard = config.read().await;
-                        if Self::should_filter_event(&event, &config_guard) {
-                            metrics.events_discarded.fetch_add(1, Ordering::Relaxed);
-                            continue;
-                        }
-                        drop(config_guard);
+                let Some(mut event) = next else {
+                    continue;
+                };
 
-                        // Apply PII masking
-                        let masked_event = masker.mask_event(event);
-                        buffer.push(masked_event);
-                        metrics.events_processed.fetch_add(1, Ordering::Relaxed);
+                trace!("📝 Evento recebido: {:?}", event);
+                metrics.events_captured.fetch_add(1, Ordering::Relaxed);
+
+                // Track modifiers before filtering, which may drop them
+                let is_paste = paste_detector.observe(&event);
+                event.modifiers = modifier_state.observe(&event);
 
-                        // Check if we need to flush
-                        let config_guard = config.read().await;
-                        let should_flush = buffer.len() >= config_guard.buffer_size || 
-                                         last_flush.elapsed() >= Duration::from_secs(config_guard.flush_interval_secs);
-                        drop(config_guard);
+                // Check if event should be filtered
+                let config_guard = config.read().await;
+                let patterns_guard = window_patterns.read().await;
+                let filtered = Self::should_filter_event(&event, &config_guard, &patterns_guard);
+                drop(patterns_guard);
+                if filtered {
+                    metrics.events_filtered.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
 
-                        if should_flush {
-                            Self::flush_events(&database, &mut buffer, &metrics).await;
+                event.session_id = Some(sessions.assign(event.timestamp, config_guard.session_idle_secs));
+                let live_rate = config_guard
+                    .emit_live_events
+                    .then_some(config_guard.live_events_per_sec);
+                let flush_on_window_change = config_guard.flush_on_window_change;
+                let masking_level = event
+                    .window_info
+                    .as_ref()
+                    .map(|window_info| Masker::level_for_application(&window_info.application, &config_guard.app_masking_policies))
+                    .unwrap_or_default();
+                let max_buffer_size = config_guard.max_buffer_size;
+                let capture_clipboard = config_guard.capture_clipboard;
+                let repeat_window = Duration::from_millis(config_guard.collapse_repeats_ms);
+                // Not held across the clipboard read or the flushes below
+                drop(config_guard);
+
+                if is_paste && capture_clipboard {
+                    let source = clipboard_source.clone();
+                    let text = tokio::task::spawn_blocking(move || source.read_text())
+                        .await
+                        .unwrap_or(None);
+
+                    if let Some(paste_event) = text.and_then(|text| clipboard::paste_event(&event, text)) {
+                        debug!("📋 Colagem capturada ({} caracteres)", paste_event.key.chars().count());
+                        let masked_paste = masker.read().await.mask_event_with_level(paste_event, masking_level);
+                        if let Some(rate) = live_rate {
+                            live_feed.publish(&masked_paste, rate, Instant::now());
+                        }
+                        if flush_on_window_change && Self::window_changed(&buffer, &masked_paste) {
+                            flusher.start(&mut buffer).await;
                             last_flush = Instant::now();
                         }
+                        flusher.push_bounded(&mut buffer, masked_paste, max_buffer_size).await;
+                        metrics.events_pasted.fetch_add(1, Ordering::Relaxed);
+                        metrics.events_processed.fetch_add(1, Ordering::Relaxed);
                     }
-                    Ok(None) => break, // Channel closed
-                    Err(_) => continue, // Timeout, check shutdown signal
+                }
+
+                // Collapse auto-repeat storms from held keys
+                if repeats.is_repeat(&event, repeat_window) {
+                    metrics.events_deduplicated.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+
+                if event.event_type == "press" {
+                    metrics.record_keystroke(event.timestamp);
+                }
+
+                // Apply PII masking
+                let masked_event = masker.read().await.mask_event_with_level(event, masking_level);
+                if let Some(rate) = live_rate {
+                    live_feed.publish(&masked_event, rate, Instant::now());
+                }
+                if flush_on_window_change && Self::window_changed(&buffer, &masked_event) {
+                    flusher.start(&mut buffer).await;
+                    last_flush = Instant::now();
+                }
+                flusher.push_bounded(&mut buffer, masked_event, max_buffer_size).await;
+                metrics.events_processed.fetch_add(1, Ordering::Relaxed);
+
+                // Check if we need to flush
+                let config_guard = config.read().await;
+                let should_flush = buffer.len() >= config_guard.buffer_size || 
+                                 last_flush.elapsed() >= Duration::from_secs(config_guard.flush_interval_secs);
+                drop(config_guard);
+
+                // A batch still being written keeps the buffer growing up to max_buffer_size
+                if should_flush && !flusher.busy() {
+                    flusher.start(&mut buffer).await;
+                    last_flush = Instant::now();
                 }
             }
 
             // Flush remaining events on shutdown
-            if !buffer.is_empty() {
-                Self::flush_events(&database, &mut buffer, &metrics).await;
-            }
+            flusher.flush_now(&mut buffer).await;
 
             info!("🔄 Processador de eventos finalizado");
         });
 
-        Ok(())
+        Ok(handle)
     }
 
     /// Inicia o detector de janelas
@@ -450,7 +1103,7 @@ impl Agent {
                     if should_update {
                         debug!("🪟 Janela ativa: {} - {}", window_info.application, window_info.title);
                         *current = Some(window_info);
-                        // Window changes are legitimate updates, not discarded events
+                        metrics.window_updates.fetch_add(1, Ordering::Relaxed);
                     }
                 }
             }
@@ -462,9 +1115,16 @@ impl Agent {
     }
 
     /// Inicia o listener de teclas com timeout e tratamento robusto para macOS
-    async fn start_key_listener(&self, tx: mpsc::UnboundedSender<KeyEvent>) -> Result<()> {
+    async fn start_key_listener(
+        &self,
+        tx: mpsc::Sender<KeyEvent>,
+        status_tx: mpsc::UnboundedSender<CaptureError>,
+    ) -> Result<()> {
         let current_window = self.current_window.clone();
         let shutdown_signal = self.shutdown_signal.clone();
+        let is_paused = self.is_paused.clone();
+        let metrics = self.metrics.clone();
+        let listener_alive = self.listener_alive.clone();
 
                 // Para macOS, usamos uma abordagem mais cautelosa
         #[cfg(target_os = "macos")]
@@ -475,15 +1135,17 @@ impl Agent {
             std::thread::spawn(move || {
                 info!("🔍 Tentando iniciar captura de teclas no macOS...");
                 
-                match listen(move |event| {
+                let result = Self::run_listener(&listener_alive, &status_tx, || listen(move |event| {
                     if shutdown_signal.load(Ordering::Relaxed) {
                         return;
                     }
 
-                    if let Err(e) = Self::handle_rdev_event(event, &tx, &current_window) {
+                    if let Err(e) = Self::handle_rdev_event(event, &tx, &current_window, &is_paused, &metrics) {
                         error!("❌ Erro ao processar evento: {}", e);
                     }
-                }) {
+                }));
+
+                match result {
                     Ok(()) => {
                         info!("✅ Listener de teclas macOS finalizado normalmente");
                     }
@@ -510,16 +1172,18 @@ impl Agent {
             std::thread::spawn(move || {
                 info!("🎯 Iniciando thread de captura de teclas...");
 
-                match listen(move |event| {
+                let result = Self::run_listener(&listener_alive, &status_tx, || listen(move |event| {
                     if shutdown_signal.load(Ordering::Relaxed) {
                         debug!("🛑 Sinal de shutdown recebido, parando listener");
                         return;
                     }
 
-                    if let Err(e) = Self::handle_rdev_event(event, &tx, &current_window) {
+                    if let Err(e) = Self::handle_rdev_event(event, &tx, &current_window, &is_paused, &metrics) {
                         error!("❌ Erro ao processar evento: {}", e);
                     }
-                }) {
+                }));
+
+                match result {
                     Ok(()) => {
                         info!("✅ Listener de teclas finalizado normalmente");
                     }
@@ -533,16 +1197,130 @@ impl Agent {
         Ok(())
     }
 
-    /// Inicia o reporter de métricas
-    async fn start_metrics_reporter(&self) -> Result<()> {
-        let metrics = self.metrics.clone();
-        let shutdown_signal = self.shutdown_signal.clone();
+    /// Repassa as falhas do listener para `last_capture_error` e para o frontend.
+    /// A tarefa termina quando a thread do listener descarta o sender
+    fn start_status_forwarder(&self) -> mpsc::UnboundedSender<CaptureError> {
+        let (status_tx, mut status_rx) = mpsc::unbounded_channel::<CaptureError>();
+        let last_capture_error = self.last_capture_error.clone();
+        let live_emitter = self.live_emitter.clone();
 
         tokio::spawn(async move {
-            let mut interval_timer = interval(Duration::from_secs(60)); // Report every minute
+            while let Some(error) = status_rx.recv().await {
+                warn!("🚨 Captura de teclas interrompida: {}", error.reason);
+                *last_capture_error.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(error.clone());
+                if let Some(emitter) = &live_emitter {
+                    emitter.emit_capture_error(&error);
+                }
+            }
+        });
 
-            while !shutdown_signal.load(Ordering::Relaxed) {
-                interval_timer.tick().await;
+        status_tx
+    }
+
+    /// Supervisiona o listener e, se ele falhar, envia o motivo por `status_tx`
+    fn run_listener<E: std::fmt::Debug>(
+        alive: &AtomicBool,
+        status_tx: &mpsc::UnboundedSender<CaptureError>,
+        listen: impl FnOnce() -> std::result::Result<(), E>,
+    ) -> std::result::Result<(), E> {
+        let result = Self::supervise_listener(alive, listen);
+        if let Err(e) = &result {
+            let _ = status_tx.send(CaptureError::new(format!("{:?}", e)));
+        }
+        result
+    }
+
+    /// Executa o `listen` do rdev mantendo `alive` verdadeiro apenas enquanto ele
+    /// roda; o flag volta a `false` quando o listener retorna, falha ou entra em pânico
+    fn supervise_listener<T>(alive: &AtomicBool, run: impl FnOnce() -> T) -> T {
+        struct AliveGuard<'a>(&'a AtomicBool);
+
+        impl Drop for AliveGuard<'_> {
+            fn drop(&mut self) {
+                self.0.store(false, Ordering::Relaxed);
+                warn!("⚠️ Listener de teclas encerrado - captura inativa");
+            }
+        }
+
+        alive.store(true, Ordering::Relaxed);
+        let _guard = AliveGuard(alive);
+        run()
+    }
+
+    /// Copia o banco para `backup_dir` no intervalo configurado
+    async fn start_backup_worker(&self) -> Result<JoinHandle<()>> {
+        let config = self.config.clone();
+        let database = self.database.clone();
+        let cancel = self.worker_cancel.clone();
+
+        let handle = tokio::spawn(async move {
+            let mut interval_timer = interval(BACKUP_CHECK_INTERVAL);
+
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = interval_timer.tick() => {}
+                }
+
+                let config = config.read().await.clone();
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+
+                match run_scheduled_backup(&database, &config, now).await {
+                    Ok(Some(backup)) => info!("💾 Backup agendado criado em {}", backup.path),
+                    Ok(None) => {}
+                    Err(e) => error!("❌ Erro no backup agendado: {}", e),
+                }
+            }
+        });
+
+        Ok(handle)
+    }
+
+    /// Inicia a limpeza periódica de eventos fora do período de retenção
+    async fn start_retention_worker(&self) -> Result<JoinHandle<()>> {
+        let config = self.config.clone();
+        let database = self.database.clone();
+        let cancel = self.worker_cancel.clone();
+
+        let handle = tokio::spawn(async move {
+            let mut interval_timer = interval(RETENTION_CHECK_INTERVAL);
+
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = interval_timer.tick() => {}
+                }
+
+                let retention_days = config.read().await.retention_days;
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+
+                if let Some(cutoff) = retention_cutoff(now, retention_days) {
+                    if let Err(e) = database.delete_older_than(cutoff).await {
+                        error!("❌ Erro na limpeza por retenção: {}", e);
+                    }
+                }
+            }
+        });
+
+        Ok(handle)
+    }
+
+    /// Inicia o reporter de métricas
+    async fn start_metrics_reporter(&self) -> Result<()> {
+        let metrics = self.metrics.clone();
+        let shutdown_signal = self.shutdown_signal.clone();
+
+        tokio::spawn(async move {
+            let mut interval_timer = interval(Duration::from_secs(60)); // Report every minute
+
+            while !shutdown_signal.load(Ordering::Relaxed) {
+                interval_timer.tick().await;
                 
                 let summary = metrics.get_summary();
                 info!("📊 Métricas do Agente: {:?}", summary);
@@ -555,13 +1333,23 @@ impl Agent {
     /// Processa evento do rdev
     fn handle_rdev_event(
         event: Event, 
-        sender: &mpsc::UnboundedSender<KeyEvent>,
-        current_window: &Arc<RwLock<Option<WindowInfo>>>
+        sender: &mpsc::Sender<KeyEvent>,
+        current_window: &Arc<RwLock<Option<WindowInfo>>>,
+        is_paused: &AtomicBool,
+        metrics: &AgentMetrics,
     ) -> Result<()> {
+        // Drop events at the source while capture is paused
+        if is_paused.load(Ordering::Relaxed) {
+            if matches!(event.event_type, EventType::KeyPress(_) | EventType::KeyRelease(_)) {
+                metrics.events_dropped_paused.fetch_add(1, Ordering::Relaxed);
+            }
+            return Ok(());
+        }
+
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .map_err(|e| anyhow!("Erro ao obter timestamp: {}", e))?
-            .as_secs();
+            .as_millis() as u64;
 
         match event.event_type {
             EventType::KeyPress(key) | EventType::KeyRelease(key) => {
@@ -588,11 +1376,20 @@ impl Agent {
                     window_info,
                     is_modifier: Self::is_modifier_key(key),
                     is_function_key: Self::is_function_key(key),
+                    session_id: None,
+                    modifiers: Vec::new(),
                 };
 
-                if let Err(e) = sender.send(key_event) {
-                    error!("❌ Erro ao enviar evento: {}", e);
-                    return Err(anyhow!("Erro ao enviar evento: {}", e));
+                match sender.try_send(key_event) {
+                    Ok(()) => {}
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        // Never block the OS input hook while the store is behind
+                        metrics.events_dropped_overflow.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => {
+                        error!("❌ Erro ao enviar evento: {}", e);
+                        return Err(anyhow!("Erro ao enviar evento: {}", e));
+                    }
                 }
             }
             _ => {} // Ignore other event types
@@ -602,7 +1399,15 @@ impl Agent {
     }
 
     /// Verifica se o evento deve ser filtrado
-    fn should_filter_event(event: &KeyEvent, config: &AgentConfig) -> bool {
+    pub fn should_filter_event(event: &KeyEvent, config: &AgentConfig, window_patterns: &[Regex]) -> bool {
+        // Never record our own search box, even under the allow-list
+        let own_window = config.self_process_id.is_some_and(|pid| {
+            event.window_info.as_ref().and_then(|window_info| window_info.process_id) == Some(pid)
+        });
+        if own_window {
+            return true;
+        }
+
         // Filter modifiers if not enabled
         if event.is_modifier && !config.capture_modifiers {
             return true;
@@ -613,49 +1418,107 @@ impl Agent {
             return true;
         }
 
+        // Allow-list mode: events outside the listed applications are dropped,
+        // including those without window information
+        if let Some(allowed) = &config.allowed_applications {
+            let is_allowed = event
+                .window_info
+                .as_ref()
+                .map(|window_info| Self::application_matches(&window_info.application, allowed))
+                .unwrap_or(false);
+            if !is_allowed {
+                return true;
+            }
+        }
+
         // Filter by application
         if let Some(window_info) = &event.window_info {
-            if config.ignored_applications.iter().any(|app| {
-                window_info.application.to_lowercase().contains(&app.to_lowercase())
-            }) {
+            if config.allowed_applications.is_none()
+                && Self::application_matches(&window_info.application, &config.ignored_applications)
+            {
                 return true;
             }
 
-            // Filter by window title patterns
-            for pattern in &config.ignored_window_patterns {
-                if let Ok(regex) = regex::Regex::new(pattern) {
-                    if regex.is_match(&window_info.title) {
-                        return true;
-                    }
-                }
+            // Filter by window title patterns (pre-compiled from config)
+            if window_patterns.iter().any(|regex| regex.is_match(&window_info.title)) {
+                return true;
             }
         }
 
         false
     }
 
-    /// Flush eventos para o banco de dados
+    /// Verifica se a aplicação contém algum dos nomes (sem diferenciar maiúsculas)
+    fn application_matches(application: &str, names: &[String]) -> bool {
+        let application = application.to_lowercase();
+        names.iter().any(|name| application.contains(&name.to_lowercase()))
+    }
+
+    /// `true` se o evento veio de uma janela diferente da do último evento no buffer
+    fn window_changed(buffer: &[KeyEvent], event: &KeyEvent) -> bool {
+        let window = |event: &KeyEvent| {
+            event.window_info.as_ref().map(|window_info| (window_info.title.clone(), window_info.application.clone()))
+        };
+        buffer.last().is_some_and(|last| window(last) != window(event))
+    }
+
+    /// Flush eventos para o destino configurado
+    #[instrument(skip_all, fields(events = buffer.len()))]
     async fn flush_events(
-        database: &Arc<Database>, 
+        sink: &Arc<dyn EventSink>, 
         buffer: &mut Vec<KeyEvent>, 
         metrics: &Arc<AgentMetrics>
     ) {
+        let sink = sink.clone();
+        Self::flush_with_retry(
+            move |events| {
+                let sink = sink.clone();
+                async move { sink.store(&events).await }
+            },
+            buffer,
+            metrics,
+            FLUSH_RETRY_BACKOFF,
+        )
+        .await;
+    }
+
+    /// Grava o buffer, repetindo falhas transitórias com espera crescente; após
+    /// `FLUSH_MAX_ATTEMPTS` o lote é descartado para não travar o processador
+    async fn flush_with_retry<F, Fut>(
+        mut store: F,
+        buffer: &mut Vec<KeyEvent>,
+        metrics: &AgentMetrics,
+        backoff: Duration,
+    ) where
+        F: FnMut(Vec<KeyEvent>) -> Fut,
+        Fut: std::future::Future<Output = Result<()>>,
+    {
         if buffer.is_empty() {
             return;
         }
 
-        match database.store_events(buffer).await {
-            Ok(_) => {
-                let count = buffer.len();
-                metrics.events_processed.fetch_add(count as u64, Ordering::Relaxed);
-                debug!("✅ {} eventos armazenados", count);
-            }
-            Err(e) => {
-                error!("❌ Erro ao armazenar eventos: {}", e);
-                metrics.events_discarded.fetch_add(buffer.len() as u64, Ordering::Relaxed);
+        let mut delay = backoff;
+        for attempt in 1..=FLUSH_MAX_ATTEMPTS {
+            match store(buffer.clone()).await {
+                Ok(_) => {
+                    let count = buffer.len();
+                    metrics.events_stored.fetch_add(count as u64, Ordering::Relaxed);
+                    debug!("✅ {} eventos armazenados", count);
+                    buffer.clear();
+                    return;
+                }
+                Err(e) if attempt < FLUSH_MAX_ATTEMPTS => {
+                    warn!("⚠️ Falha ao armazenar eventos (tentativa {}/{}): {}", attempt, FLUSH_MAX_ATTEMPTS, e);
+                    sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => {
+                    error!("❌ Erro ao armazenar eventos após {} tentativas: {}", FLUSH_MAX_ATTEMPTS, e);
+                    metrics.events_discarded.fetch_add(buffer.len() as u64, Ordering::Relaxed);
+                }
             }
         }
-        
+
         buffer.clear();
     }
 
@@ -808,7 +1671,7 @@ impl Agent {
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .ok()?
-            .as_secs();
+            .as_millis() as u64;
 
         #[cfg(target_os = "windows")]
         {
@@ -892,20 +1755,79 @@ impl Agent {
         }
     }
 
+    /// Janela da frente pela lista do Quartz, que não exige Acessibilidade. O
+    /// PID e o nome do dono sempre vêm; o título só com a permissão de Gravação
+    /// de Tela, e sem ela fica o nome da aplicação
     #[cfg(target_os = "macos")]
     fn get_macos_active_window(timestamp: u64) -> Option<WindowInfo> {
-        // Implementação simplificada para evitar crashes
-        warn!("🚧 Detecção de janela ativa no macOS em modo simplificado");
-        Some(WindowInfo {
-            title: "macOS Window".to_string(),
-            application: "Unknown App".to_string(),
-            process_id: None,
-            timestamp,
+        use std::ffi::c_void;
+        use core_foundation::base::{CFType, TCFType};
+        use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+        use core_foundation::number::CFNumber;
+        use core_foundation::string::{CFString, CFStringRef};
+        use core_graphics::window::{
+            copy_window_info, kCGNullWindowID, kCGWindowLayer, kCGWindowListExcludeDesktopElements,
+            kCGWindowListOptionOnScreenOnly, kCGWindowName, kCGWindowOwnerName, kCGWindowOwnerPID,
+        };
+
+        fn value(window: &CFDictionary<*const c_void, *const c_void>, key: CFStringRef) -> Option<CFType> {
+            window
+                .find(key as *const c_void)
+                .map(|value| unsafe { CFType::wrap_under_get_rule(*value) })
+        }
+        let number = |window: &CFDictionary<*const c_void, *const c_void>, key| {
+            value(window, key).and_then(|value| value.downcast::<CFNumber>()).and_then(|n| n.to_i64())
+        };
+        let string = |window: &CFDictionary<*const c_void, *const c_void>, key| {
+            value(window, key)
+                .and_then(|value| value.downcast::<CFString>())
+                .map(|s| s.to_string())
+                .filter(|s| !s.is_empty())
+        };
+
+        // Front to back: the first normal-layer window belongs to the active app
+        let windows = copy_window_info(
+            kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+            kCGNullWindowID,
+        )?;
+        let (layer_key, pid_key, owner_key, name_key) =
+            unsafe { (kCGWindowLayer, kCGWindowOwnerPID, kCGWindowOwnerName, kCGWindowName) };
+
+        windows.iter().find_map(|item| {
+            let window: CFDictionary<*const c_void, *const c_void> =
+                unsafe { CFDictionary::wrap_under_get_rule(*item as CFDictionaryRef) };
+            if number(&window, layer_key) != Some(0) {
+                return None;
+            }
+
+            let application = string(&window, owner_key).unwrap_or_else(|| "Unknown App".to_string());
+            Some(WindowInfo {
+                title: string(&window, name_key).unwrap_or_else(|| application.clone()),
+                application,
+                process_id: number(&window, pid_key).and_then(|pid| u32::try_from(pid).ok()),
+                timestamp,
+            })
         })
     }
 
     #[cfg(target_os = "linux")]
     fn get_linux_active_window(timestamp: u64) -> Option<WindowInfo> {
+        match wayland::detect_backend() {
+            LinuxWindowBackend::X11 => Self::get_x11_active_window(timestamp),
+            LinuxWindowBackend::Unsupported => None,
+            backend => wayland::get_active_window(backend, timestamp).or_else(|| {
+                // Aplicações XWayland ainda podem ser consultadas via X11
+                if wayland::has_x11_display() {
+                    Self::get_x11_active_window(timestamp)
+                } else {
+                    None
+                }
+            }),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn get_x11_active_window(timestamp: u64) -> Option<WindowInfo> {
         use std::ffi::CStr;
         use std::ptr;
 
@@ -1018,16 +1940,63 @@ impl Agent {
                 "Unknown".to_string()
             };
 
+            // Get process ID
+            let mut pid_data: *mut u8 = ptr::null_mut();
+            let net_wm_pid = x11::xlib::XInternAtom(
+                display,
+                c"_NET_WM_PID".as_ptr(),
+                0,
+            );
+
+            let process_id = if XGetWindowProperty(
+                display,
+                active_window,
+                net_wm_pid,
+                0,
+                1,
+                0,
+                x11::xlib::XA_CARDINAL,
+                &mut actual_type,
+                &mut actual_format,
+                &mut nitems,
+                &mut bytes_after,
+                &mut pid_data,
+            ) == 0 && !pid_data.is_null() {
+                let pid = if nitems > 0 {
+                    Some(*(pid_data as *const u64) as u32)
+                } else {
+                    None
+                };
+                x11::xlib::XFree(pid_data as *mut _);
+                pid
+            } else {
+                None
+            };
+
             x11::xlib::XCloseDisplay(display);
 
+            // Prefer the real executable name; WM_CLASS is only a fallback
+            let application = process_id
+                .and_then(Self::process_name_from_proc)
+                .unwrap_or(application);
+
             Some(WindowInfo {
                 title,
                 application,
-                process_id: None, // PID detection on Linux requires additional work
+                process_id,
                 timestamp,
             })
         }
     }
+
+    /// Resolve o nome do executável via `/proc/<pid>/comm`
+    #[cfg(target_os = "linux")]
+    fn process_name_from_proc(pid: u32) -> Option<String> {
+        std::fs::read_to_string(format!("/proc/{}/comm", pid))
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+    }
 }
 
 #[cfg(test)]
@@ -1035,6 +2004,7 @@ mod tests {
     use super::*;
     use std::sync::Arc;
     use tokio::sync::mpsc;
+    use crate::test_support::{press, press_at};
     use tempfile::NamedTempFile;
 
     async fn create_test_database() -> Result<Arc<Database>> {
@@ -1119,6 +2089,31 @@ mod tests {
         assert!(result.is_ok()); // Should not error, just warn
     }
 
+    #[tokio::test]
+    async fn test_restart_cancels_previous_workers() {
+        let database = create_test_database().await.unwrap();
+        let mut agent = Agent::new(Masker::new(), database).await.unwrap();
+
+        let idle_refs = Arc::strong_count(&agent.database);
+
+        agent.start().await.unwrap();
+        assert_eq!(agent.worker_handles.len(), 2);
+        let first = agent.worker_cancel.clone();
+        agent.stop().await.unwrap();
+        assert!(first.is_cancelled());
+        assert!(agent.worker_handles.is_empty());
+        // Nenhuma tarefa de retenção ou backup continua segurando o banco
+        assert_eq!(Arc::strong_count(&agent.database), idle_refs);
+
+        // Um novo start não reativa as tarefas do anterior
+        agent.start().await.unwrap();
+        assert!(first.is_cancelled());
+        assert!(!agent.worker_cancel.is_cancelled());
+        agent.stop().await.unwrap();
+        assert!(agent.worker_handles.is_empty());
+        assert_eq!(Arc::strong_count(&agent.database), idle_refs);
+    }
+
     #[test]
     fn test_should_filter_event() {
         let config = AgentConfig {
@@ -1128,6 +2123,7 @@ mod tests {
             ignored_window_patterns: vec![r".*[Pp]assword.*".to_string()],
             ..Default::default()
         };
+        let patterns = Agent::compile_window_patterns(&config.ignored_window_patterns).unwrap();
 
         // Test modifier filtering
         let modifier_event = KeyEvent {
@@ -1137,8 +2133,10 @@ mod tests {
             window_info: None,
             is_modifier: true,
             is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
         };
-        assert!(Agent::should_filter_event(&modifier_event, &config));
+        assert!(Agent::should_filter_event(&modifier_event, &config, &patterns));
 
         // Test function key filtering
         let function_event = KeyEvent {
@@ -1148,8 +2146,10 @@ mod tests {
             window_info: None,
             is_modifier: false,
             is_function_key: true,
+            session_id: None,
+            modifiers: Vec::new(),
         };
-        assert!(Agent::should_filter_event(&function_event, &config));
+        assert!(Agent::should_filter_event(&function_event, &config, &patterns));
 
         // Test application filtering
         let app_event = KeyEvent {
@@ -1164,8 +2164,10 @@ mod tests {
             }),
             is_modifier: false,
             is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
         };
-        assert!(Agent::should_filter_event(&app_event, &config));
+        assert!(Agent::should_filter_event(&app_event, &config, &patterns));
 
         // Test window pattern filtering
         let window_event = KeyEvent {
@@ -1180,8 +2182,10 @@ mod tests {
             }),
             is_modifier: false,
             is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
         };
-        assert!(Agent::should_filter_event(&window_event, &config));
+        assert!(Agent::should_filter_event(&window_event, &config, &patterns));
 
         // Test normal event (should not be filtered)
         let normal_event = KeyEvent {
@@ -1196,8 +2200,10 @@ mod tests {
             }),
             is_modifier: false,
             is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
         };
-        assert!(!Agent::should_filter_event(&normal_event, &config));
+        assert!(!Agent::should_filter_event(&normal_event, &config, &patterns));
     }
 
     #[tokio::test]
@@ -1210,7 +2216,88 @@ mod tests {
         
         assert_eq!(metrics.get("events_captured").unwrap_or(&0), &0);
         assert_eq!(metrics.get("events_processed").unwrap_or(&0), &0);
+        assert_eq!(metrics.get("events_stored").unwrap_or(&0), &0);
+        assert_eq!(metrics.get("events_filtered").unwrap_or(&0), &0);
+        assert_eq!(metrics.get("window_updates").unwrap_or(&0), &0);
         assert!(metrics.contains_key("uptime_seconds"));
+        assert_eq!(metrics["keys_per_minute"], 0);
+        assert_eq!(metrics["active_typing_seconds"], 0);
+    }
+
+    #[test]
+    fn test_typing_rate_in_summary() {
+        let metrics = AgentMetrics::new();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        for offset in 0..20 {
+            metrics.record_keystroke((now - 10 + offset / 2) * 1000);
+        }
+
+        let summary = metrics.get_summary();
+        assert_eq!(summary["keys_per_minute"], 20);
+        assert!((5..=15).contains(&summary["active_typing_seconds"]));
+    }
+
+    #[tokio::test]
+    async fn test_uptime_and_captured_from_metrics() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+
+        let mut agent = Agent::new(masker, database).await.unwrap();
+        assert_eq!(agent.get_metrics()["uptime_seconds"], 0);
+
+        agent.start().await.unwrap();
+        agent.metrics.events_captured.fetch_add(3, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        let metrics = agent.get_metrics();
+        assert!(metrics["uptime_seconds"] >= 1);
+        assert_eq!(metrics["events_captured"], 3);
+
+        agent.stop().await.unwrap();
+        assert_eq!(agent.get_metrics()["uptime_seconds"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_counts_stored_events_once() {
+        let database = create_test_database().await.unwrap();
+        let metrics = Arc::new(AgentMetrics::new());
+
+        let mut buffer = vec![
+            KeyEvent {
+                timestamp: 1,
+                key: "a".to_string(),
+                event_type: "press".to_string(),
+                window_info: None,
+                is_modifier: false,
+                is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
+            },
+            KeyEvent {
+                timestamp: 2,
+                key: "b".to_string(),
+                event_type: "press".to_string(),
+                window_info: None,
+                is_modifier: false,
+                is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
+            },
+        ];
+
+        let sink: Arc<dyn EventSink> = Arc::new(SqliteSink::new(database));
+        Agent::flush_events(&sink, &mut buffer, &metrics).await;
+
+        let summary = metrics.get_summary();
+        assert!(buffer.is_empty());
+        assert_eq!(summary["events_stored"], 2);
+        // Flush não deve contar os eventos como processados novamente
+        assert_eq!(summary["events_processed"], 0);
+        assert_eq!(summary["events_discarded"], 0);
     }
 
     #[tokio::test]
@@ -1233,13 +2320,77 @@ mod tests {
         assert_eq!(retrieved_config.flush_interval_secs, 10);
     }
 
+    #[tokio::test]
+    async fn test_config_survives_agent_restart() {
+        let database = create_test_database().await.unwrap();
+
+        let agent = Agent::new(Masker::new(), database.clone()).await.unwrap();
+        agent.update_config(AgentConfig {
+            buffer_size: 42,
+            ignored_applications: vec!["banco".to_string()],
+            allowed_applications: Some(vec!["editor".to_string()]),
+            retention_days: 30,
+            ..Default::default()
+        }).await.unwrap();
+        drop(agent);
+
+        let restored = Agent::new(Masker::new(), database.clone()).await.unwrap().get_config().await;
+        assert_eq!(restored.buffer_size, 42);
+        assert_eq!(restored.ignored_applications, vec!["banco".to_string()]);
+        assert_eq!(restored.allowed_applications, Some(vec!["editor".to_string()]));
+        assert_eq!(restored.retention_days, 30);
+        assert_eq!(restored.flush_interval_secs, AgentConfig::default().flush_interval_secs);
+
+        // Uma configuração inválida não é salva
+        let agent = Agent::new(Masker::new(), database.clone()).await.unwrap();
+        let invalid = AgentConfig {
+            ignored_window_patterns: vec!["(".to_string()],
+            ..Default::default()
+        };
+        assert!(agent.update_config(invalid).await.is_err());
+        assert_eq!(database.load_agent_config().await.unwrap().unwrap().buffer_size, 42);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_name_from_proc() {
+        let name = Agent::process_name_from_proc(std::process::id());
+        assert!(name.is_some());
+        assert!(!name.unwrap().is_empty());
+
+        assert!(Agent::process_name_from_proc(u32::MAX).is_none());
+    }
+
+    /// Requer uma sessão X11 com `xterm` instalado:
+    /// `cargo test x11_active_window_pid -- --ignored`
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore]
+    fn test_x11_active_window_pid() {
+        let mut child = std::process::Command::new("xterm")
+            .args(["-title", "keyai-pid-test"])
+            .spawn()
+            .expect("xterm não encontrado");
+        std::thread::sleep(Duration::from_secs(2));
+
+        let window = Agent::get_x11_active_window(0);
+        let _ = child.kill();
+        let _ = child.wait();
+
+        let window = window.expect("nenhuma janela ativa detectada");
+        assert!(window.process_id.is_some());
+        assert_eq!(window.application, "xterm");
+    }
+
     #[test]
     fn test_handle_rdev_event() {
         use std::sync::Arc;
         use tokio::sync::RwLock;
         
-        let (tx, mut rx) = mpsc::unbounded_channel::<KeyEvent>();
+        let (tx, mut rx) = mpsc::channel::<KeyEvent>(16);
         let current_window = Arc::new(RwLock::new(None));
+        let is_paused = AtomicBool::new(false);
+        let metrics = AgentMetrics::new();
         
         // Test key press event
         let event = Event {
@@ -1248,7 +2399,7 @@ mod tests {
             event_type: EventType::KeyPress(Key::KeyA),
         };
         
-        Agent::handle_rdev_event(event, &tx, &current_window).unwrap();
+        Agent::handle_rdev_event(event, &tx, &current_window, &is_paused, &metrics).unwrap();
         
         let received = rx.try_recv();
         assert!(received.is_ok());
@@ -1258,4 +2409,910 @@ mod tests {
         assert!(!key_event.is_modifier);
         assert!(!key_event.is_function_key);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_handle_rdev_event_while_paused() {
+        let (tx, mut rx) = mpsc::channel::<KeyEvent>(16);
+        let current_window = Arc::new(RwLock::new(None));
+        let is_paused = AtomicBool::new(true);
+        let metrics = AgentMetrics::new();
+
+        for key in [Key::KeyA, Key::KeyB] {
+            let event = Event {
+                time: SystemTime::now(),
+                name: None,
+                event_type: EventType::KeyPress(key),
+            };
+            Agent::handle_rdev_event(event, &tx, &current_window, &is_paused, &metrics).unwrap();
+        }
+
+        assert!(rx.try_recv().is_err());
+        assert_eq!(metrics.events_dropped_paused.load(Ordering::Relaxed), 2);
+        assert_eq!(metrics.events_captured.load(Ordering::Relaxed), 0);
+
+        // After resuming, events flow again
+        is_paused.store(false, Ordering::Relaxed);
+        let event = Event {
+            time: SystemTime::now(),
+            name: None,
+            event_type: EventType::KeyPress(Key::KeyC),
+        };
+        Agent::handle_rdev_event(event, &tx, &current_window, &is_paused, &metrics).unwrap();
+        assert_eq!(rx.try_recv().unwrap().key, "c");
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume_keeps_agent_alive() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            ..Default::default()
+        };
+
+        let mut agent = Agent::with_config(masker, database.clone(), config).await.unwrap();
+
+        // Pausing a stopped agent is an error
+        assert!(agent.pause().is_err());
+
+        agent.start().await.unwrap();
+        agent.pause().unwrap();
+        assert!(agent.is_paused());
+        assert!(agent.is_running());
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(!agent.processor_handle.as_ref().unwrap().is_finished());
+
+        agent.resume().unwrap();
+        assert!(!agent.is_paused());
+
+        agent.stop().await.unwrap();
+        let stats = database.get_stats().await.unwrap();
+        assert_eq!(stats.total_events, 0);
+    }
+
+    #[test]
+    fn test_repeat_collapser() {
+        let mut repeats = RepeatCollapser::default();
+        let window = Duration::from_millis(50);
+
+        let kept = (0..20)
+            .filter(|i| !repeats.is_repeat(&press_at("a", 1_000 + i * 5), window))
+            .count();
+        assert_eq!(kept, 1);
+
+        // A different key is never a repeat
+        assert!(!repeats.is_repeat(&press_at("b", 1_100), window));
+
+        // A modifier in between breaks the sequence
+        assert!(repeats.is_repeat(&press_at("b", 1_110), window));
+        assert!(!repeats.is_repeat(&KeyEvent { is_modifier: true, ..press_at("ShiftLeft", 1_115) }, window));
+        assert!(!repeats.is_repeat(&press_at("b", 1_120), window));
+
+        // Disabled window keeps every event
+        let mut disabled = RepeatCollapser::default();
+        assert!(!disabled.is_repeat(&press_at("a", 0), Duration::ZERO));
+        assert!(!disabled.is_repeat(&press_at("a", 0), Duration::ZERO));
+    }
+
+    #[test]
+    fn test_repeat_collapser_uses_capture_time_and_release() {
+        let mut repeats = RepeatCollapser::default();
+        let window = Duration::from_millis(50);
+
+        // Releasing the key ends the hold: typing "aa" quickly keeps both presses
+        assert!(!repeats.is_repeat(&press_at("a", 1_000), window));
+        let release = KeyEvent { event_type: "release".to_string(), ..press_at("a", 1_010) };
+        assert!(!repeats.is_repeat(&release, window));
+        assert!(!repeats.is_repeat(&press_at("a", 1_020), window));
+
+        // Events processed late still compare their capture timestamps
+        assert!(!repeats.is_repeat(&press_at("a", 1_200), window));
+        assert!(repeats.is_repeat(&press_at("a", 1_230), window));
+    }
+
+    #[tokio::test]
+    async fn test_collapse_repeats_stores_single_event() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            collapse_repeats_ms: 50,
+            enable_window_detection: false,
+            flush_interval_secs: 3600,
+            ..Default::default()
+        };
+
+        let mut agent = Agent::with_config(masker, database.clone(), config).await.unwrap();
+        agent.start().await.unwrap();
+
+        for i in 0..20 {
+            let mut event = press("a");
+            event.timestamp = 1000 + i;
+            agent.submit_event(event).unwrap();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        agent.stop().await.unwrap();
+
+        let stats = database.get_stats().await.unwrap();
+        assert_eq!(stats.total_events, 1);
+        assert_eq!(agent.get_metrics()["events_deduplicated"], 19);
+    }
+
+    #[tokio::test]
+    async fn test_window_patterns_hot_reload() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let agent = Agent::new(masker, database).await.unwrap();
+
+        let invoice_event = KeyEvent {
+            timestamp: 0,
+            key: "a".to_string(),
+            event_type: "press".to_string(),
+            window_info: Some(WindowInfo {
+                title: "Invoice 2024".to_string(),
+                application: "editor".to_string(),
+                process_id: None,
+                timestamp: 0,
+            }),
+            is_modifier: false,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
+        };
+
+        {
+            let config = agent.config.read().await;
+            let patterns = agent.window_patterns.read().await;
+            assert!(!Agent::should_filter_event(&invoice_event, &config, &patterns));
+        }
+
+        // Invalid regex surfaces an error and keeps the previous config
+        let invalid = AgentConfig {
+            ignored_window_patterns: vec!["[unclosed".to_string()],
+            ..Default::default()
+        };
+        let err = agent.update_config(invalid).await.unwrap_err();
+        assert!(err.to_string().contains("[unclosed"));
+        assert_eq!(
+            agent.get_config().await.ignored_window_patterns,
+            AgentConfig::default().ignored_window_patterns
+        );
+
+        // Valid patterns take effect immediately
+        let valid = AgentConfig {
+            ignored_window_patterns: vec![r"^Invoice".to_string()],
+            ..Default::default()
+        };
+        agent.update_config(valid).await.unwrap();
+
+        let config = agent.config.read().await;
+        let patterns = agent.window_patterns.read().await;
+        assert_eq!(patterns.len(), 1);
+        assert!(Agent::should_filter_event(&invoice_event, &config, &patterns));
+    }
+
+    #[tokio::test]
+    async fn test_with_config_rejects_invalid_pattern() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            ignored_window_patterns: vec!["(".to_string()],
+            ..Default::default()
+        };
+
+        assert!(Agent::with_config(masker, database, config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shared_masker_applies_runtime_patterns() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(masker, database.clone(), config).await.unwrap();
+
+        agent
+            .shared_masker()
+            .write()
+            .await
+            .add_custom_pattern("ticket".to_string(), r"TICKET-\d+".to_string())
+            .unwrap();
+
+        agent.start().await.unwrap();
+        agent.submit_event(KeyEvent {
+            timestamp: 0,
+            key: "TICKET-42".to_string(),
+            event_type: "press".to_string(),
+            window_info: None,
+            is_modifier: false,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
+        }).unwrap();
+        agent.stop().await.unwrap();
+
+        let stored = database.search_by_timerange(0, u64::MAX, 10).await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].key, "*********");
+    }
+
+    struct FixedClipboard(&'static str);
+
+    impl ClipboardSource for FixedClipboard {
+        fn read_text(&self) -> Option<String> {
+            Some(self.0.to_string())
+        }
+    }
+
+    async fn run_paste_sequence(capture_clipboard: bool) -> Vec<crate::db::StoredEvent> {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            capture_clipboard,
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(masker, database.clone(), config).await.unwrap();
+        agent.set_clipboard_source(Arc::new(FixedClipboard("contato joao@exemplo.com")));
+
+        agent.start().await.unwrap();
+        for (i, (key, event_type, is_modifier)) in [
+            ("CtrlLeft", "press", true),
+            ("v", "press", false),
+            ("v", "release", false),
+            ("CtrlLeft", "release", true),
+        ].into_iter().enumerate() {
+            agent.submit_event(KeyEvent {
+                timestamp: i as u64,
+                key: key.to_string(),
+                event_type: event_type.to_string(),
+                window_info: None,
+                is_modifier,
+                is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
+            }).unwrap();
+        }
+        agent.stop().await.unwrap();
+
+        database.search_by_timerange(0, u64::MAX, 10).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_paste_is_masked_before_storage() {
+        let stored = run_paste_sequence(true).await;
+
+        let paste = stored.iter().find(|e| e.event_type == PASTE_EVENT_TYPE).expect("colagem armazenada");
+        assert_eq!(paste.key, "contato j***@exemplo.com");
+        assert_eq!(paste.text_content.as_deref(), Some("contato j***@exemplo.com"));
+        assert!(!stored.iter().any(|e| e.key.contains("joao@")));
+    }
+
+    /// Fica bloqueada até receber o sinal de `release`
+    struct BlockingClipboard {
+        started: std::sync::mpsc::Sender<()>,
+        release: std::sync::Mutex<std::sync::mpsc::Receiver<()>>,
+    }
+
+    impl ClipboardSource for BlockingClipboard {
+        fn read_text(&self) -> Option<String> {
+            self.started.send(()).unwrap();
+            self.release.lock().unwrap().recv().unwrap();
+            Some("colado".to_string())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_config_update_not_blocked_by_clipboard_read() {
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            capture_clipboard: true,
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(Masker::new(), database, config.clone()).await.unwrap();
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel();
+        agent.set_clipboard_source(Arc::new(BlockingClipboard {
+            started: started_tx,
+            release: std::sync::Mutex::new(release_rx),
+        }));
+
+        agent.start().await.unwrap();
+        agent.submit_event(KeyEvent { is_modifier: true, ..press("CtrlLeft") }).unwrap();
+        agent.submit_event(press("v")).unwrap();
+        tokio::task::spawn_blocking(move || started_rx.recv().unwrap()).await.unwrap();
+
+        // The clipboard read is still pending here
+        let update = tokio::time::timeout(Duration::from_secs(2), agent.update_config(config)).await;
+        release_tx.send(()).unwrap();
+        assert!(update.expect("update_config esperou a leitura da área de transferência").is_ok());
+        agent.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_paste_capture_disabled_by_default() {
+        assert!(!AgentConfig::default().capture_clipboard);
+
+        let stored = run_paste_sequence(false).await;
+        assert!(!stored.iter().any(|e| e.event_type == PASTE_EVENT_TYPE));
+    }
+
+    fn typed_in(application: &str) -> KeyEvent {
+        let mut event = press("a");
+        event.window_info = Some(WindowInfo {
+            title: "Editor".to_string(),
+            application: application.to_string(),
+            process_id: None,
+            timestamp: 0,
+        });
+        event
+    }
+
+    #[test]
+    fn test_filter_allow_list_only() {
+        let config = AgentConfig {
+            ignored_applications: vec![],
+            ignored_window_patterns: vec![],
+            allowed_applications: Some(vec!["Code".to_string(), "Terminal".to_string()]),
+            ..Default::default()
+        };
+
+        assert!(!Agent::should_filter_event(&typed_in("Visual Studio Code"), &config, &[]));
+        assert!(!Agent::should_filter_event(&typed_in("gnome-terminal"), &config, &[]));
+        assert!(Agent::should_filter_event(&typed_in("firefox"), &config, &[]));
+        // Sem janela conhecida não há como confirmar a aplicação
+        assert!(Agent::should_filter_event(&press("a"), &config, &[]));
+    }
+
+    #[test]
+    fn test_filter_deny_list_only() {
+        let config = AgentConfig {
+            ignored_applications: vec!["bitwarden".to_string()],
+            ignored_window_patterns: vec![],
+            allowed_applications: None,
+            ..Default::default()
+        };
+
+        assert!(Agent::should_filter_event(&typed_in("Bitwarden"), &config, &[]));
+        assert!(!Agent::should_filter_event(&typed_in("firefox"), &config, &[]));
+        assert!(!Agent::should_filter_event(&press("a"), &config, &[]));
+    }
+
+    #[test]
+    fn test_filter_own_process_by_pid() {
+        assert_eq!(AgentConfig::default().self_process_id, Some(std::process::id()));
+
+        let config = AgentConfig {
+            ignored_applications: vec![],
+            allowed_applications: Some(vec!["Unknown App".to_string()]),
+            self_process_id: Some(4242),
+            ..Default::default()
+        };
+        let with_pid = |pid| {
+            let mut event = typed_in("Unknown App");
+            event.window_info.as_mut().unwrap().process_id = pid;
+            event
+        };
+
+        // O nome da aplicação não importa, só o PID
+        assert!(Agent::should_filter_event(&with_pid(Some(4242)), &config, &[]));
+        assert!(!Agent::should_filter_event(&with_pid(Some(7)), &config, &[]));
+        assert!(!Agent::should_filter_event(&with_pid(None), &config, &[]));
+
+        let config = AgentConfig { self_process_id: None, ..config };
+        assert!(!Agent::should_filter_event(&with_pid(Some(4242)), &config, &[]));
+    }
+
+    #[test]
+    fn test_filter_allow_list_takes_priority_over_deny_list() {
+        let config = AgentConfig {
+            ignored_applications: vec!["code".to_string(), "firefox".to_string()],
+            ignored_window_patterns: vec![],
+            allowed_applications: Some(vec!["code".to_string()]),
+            ..Default::default()
+        };
+
+        // Permitida mesmo constando na lista de ignoradas
+        assert!(!Agent::should_filter_event(&typed_in("code"), &config, &[]));
+        assert!(Agent::should_filter_event(&typed_in("firefox"), &config, &[]));
+        assert!(Agent::should_filter_event(&typed_in("slack"), &config, &[]));
+    }
+
+    #[test]
+    fn test_session_tracker_idle_gap() {
+        let mut tracker = SessionTracker::default();
+
+        let first = tracker.assign(100_000, 60);
+        assert_eq!(tracker.assign(130_000, 60), first);
+        assert_eq!(tracker.assign(190_000, 60), first);
+
+        // Mais de 60s sem teclas inicia outra sessão
+        let second = tracker.assign(250_001, 60);
+        assert_ne!(second, first);
+        assert_eq!(tracker.assign(260_000, 60), second);
+    }
+
+    #[tokio::test]
+    async fn test_idle_gap_yields_two_sessions() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            session_idle_secs: 10,
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(masker, database.clone(), config).await.unwrap();
+
+        agent.start().await.unwrap();
+        for (timestamp, key) in [(1_000_000, "a"), (1_002_000, "b"), (1_100_000, "c"), (1_101_000, "d")] {
+            let mut event = press(key);
+            event.timestamp = timestamp;
+            agent.submit_event(event).unwrap();
+        }
+        agent.stop().await.unwrap();
+
+        let sessions = database.list_sessions().await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_ne!(sessions[0].session_id, sessions[1].session_id);
+        assert!(sessions.iter().all(|s| s.event_count == 2));
+    }
+
+    #[tokio::test]
+    async fn test_modifiers_recorded_with_key() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(masker, database.clone(), config).await.unwrap();
+
+        agent.start().await.unwrap();
+        for (timestamp, key, event_type, is_modifier) in [
+            (1, "CtrlLeft", "press", true),
+            (2, "s", "press", false),
+            (3, "CtrlLeft", "release", true),
+            (4, "s", "press", false),
+        ] {
+            let mut event = KeyEvent { is_modifier, ..press(key) };
+            event.timestamp = timestamp;
+            event.event_type = event_type.to_string();
+            agent.submit_event(event).unwrap();
+        }
+        agent.stop().await.unwrap();
+
+        let matches = database.search_shortcuts(&["ctrl".to_string()], "s", 10).await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].timestamp, 2);
+    }
+
+    #[derive(Default)]
+    struct RecordingEmitter(std::sync::Mutex<Vec<KeyEvent>>);
+
+    impl LiveEventEmitter for RecordingEmitter {
+        fn emit(&self, event: &KeyEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    async fn run_live_sequence(emit_live_events: bool) -> (Vec<KeyEvent>, Vec<crate::db::StoredEvent>) {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            emit_live_events,
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(masker, database.clone(), config).await.unwrap();
+        let emitter = Arc::new(RecordingEmitter::default());
+        agent.set_live_emitter(emitter.clone());
+
+        agent.start().await.unwrap();
+        let mut event = press("joao@exemplo.com");
+        event.timestamp = 1;
+        agent.submit_event(event).unwrap();
+        agent.stop().await.unwrap();
+
+        let emitted = emitter.0.lock().unwrap().clone();
+        let stored = database.search_by_timerange(0, u64::MAX, 10).await.unwrap();
+        (emitted, stored)
+    }
+
+    #[tokio::test]
+    async fn test_live_events_emitted_masked() {
+        let (emitted, stored) = run_live_sequence(true).await;
+
+        assert_eq!(emitted.len(), 1);
+        assert_ne!(emitted[0].key, "joao@exemplo.com");
+        assert_eq!(emitted[0].key, stored[0].key);
+    }
+
+    #[tokio::test]
+    async fn test_live_events_disabled_by_default() {
+        assert!(!AgentConfig::default().emit_live_events);
+
+        let (emitted, stored) = run_live_sequence(false).await;
+        assert!(emitted.is_empty());
+        assert_eq!(stored.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_app_masking_policy_applied_before_storage() {
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            app_masking_policies: HashMap::from([("editor".to_string(), MaskingLevel::None)]),
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(Masker::new(), database.clone(), config).await.unwrap();
+
+        agent.start().await.unwrap();
+        for (timestamp, application) in [(1, "editor"), (2, "firefox")] {
+            let mut event = press("joao@exemplo.com");
+            event.timestamp = timestamp;
+            event.window_info = Some(WindowInfo {
+                title: "Notas".to_string(),
+                application: application.to_string(),
+                process_id: None,
+                timestamp,
+            });
+            agent.submit_event(event).unwrap();
+        }
+        agent.stop().await.unwrap();
+
+        let stored = database.search_by_timerange(0, u64::MAX, 10).await.unwrap();
+        let key_at = |timestamp| stored.iter().find(|event| event.timestamp == timestamp).unwrap().key.clone();
+        assert_eq!(key_at(1), "joao@exemplo.com");
+        assert_eq!(key_at(2), "j***@exemplo.com");
+    }
+
+    #[derive(Default)]
+    struct BatchRecorder(std::sync::Mutex<Vec<Vec<KeyEvent>>>);
+
+    #[async_trait::async_trait]
+    impl EventSink for BatchRecorder {
+        async fn store(&self, events: &[KeyEvent]) -> Result<()> {
+            self.0.lock().unwrap().push(events.to_vec());
+            Ok(())
+        }
+    }
+
+    async fn batch_titles(flush_on_window_change: bool) -> Vec<Vec<String>> {
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            flush_on_window_change,
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(Masker::new(), database, config).await.unwrap();
+        let recorder = Arc::new(BatchRecorder::default());
+        agent.set_sink(recorder.clone());
+
+        agent.start().await.unwrap();
+        for (timestamp, key, title) in [(1, "o", "Janela A"), (2, "i", "Janela A"), (3, "x", "Janela B")] {
+            let mut event = press(key);
+            event.timestamp = timestamp;
+            event.window_info = Some(WindowInfo {
+                title: title.to_string(),
+                application: "editor".to_string(),
+                process_id: None,
+                timestamp,
+            });
+            agent.submit_event(event).unwrap();
+        }
+        agent.stop().await.unwrap();
+
+        let batches = recorder.0.lock().unwrap().clone();
+        batches
+            .iter()
+            .map(|batch| batch.iter().map(|event| event.window_info.as_ref().unwrap().title.clone()).collect())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_window_change_splits_batches() {
+        assert!(AgentConfig::default().flush_on_window_change);
+
+        assert_eq!(
+            batch_titles(true).await,
+            vec![vec!["Janela A", "Janela A"], vec!["Janela B"]]
+        );
+        assert_eq!(batch_titles(false).await, vec![vec!["Janela A", "Janela A", "Janela B"]]);
+    }
+
+    struct FailingSink;
+
+    #[async_trait::async_trait]
+    impl EventSink for FailingSink {
+        async fn store(&self, _events: &[KeyEvent]) -> Result<()> {
+            Err(anyhow!("destino indisponível"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_writes_through_injected_sink() {
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig { enable_window_detection: false, ..Default::default() };
+        let mut agent = Agent::with_config(Masker::new(), database.clone(), config).await.unwrap();
+        agent.set_sink(Arc::new(CompositeSink::new(vec![
+            Arc::new(FailingSink),
+            Arc::new(SqliteSink::new(database.clone())),
+        ])));
+
+        agent.start().await.unwrap();
+        let mut event = press("a");
+        event.timestamp = 1;
+        agent.submit_event(event).unwrap();
+        agent.stop().await.unwrap();
+
+        assert_eq!(database.get_stats().await.unwrap().total_events, 1);
+        assert_eq!(agent.get_metrics()["events_stored"], 1);
+        assert_eq!(agent.get_metrics()["events_discarded"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_capture_error_survives_poisoned_lock() {
+        let database = create_test_database().await.unwrap();
+        let agent = Agent::new(Masker::new(), database).await.unwrap();
+
+        let lock = agent.last_capture_error.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = lock.lock().unwrap();
+            panic!("listener morreu segurando o lock");
+        })
+        .join();
+
+        assert!(agent.last_capture_error.is_poisoned());
+        assert_eq!(agent.last_capture_error(), None);
+    }
+
+    #[tokio::test]
+    async fn test_listener_failure_reported_as_dead() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let agent = Agent::new(masker, database).await.unwrap();
+        assert_eq!(agent.health()["capture_listener"], "dead");
+
+        // Simula o rdev falhando por falta de permissão após iniciar
+        let result: std::result::Result<(), &str> = Agent::supervise_listener(&agent.listener_alive, || {
+            assert_eq!(agent.health()["capture_listener"], "alive");
+            Err("permissão negada")
+        });
+
+        assert!(result.is_err());
+        assert!(!agent.is_listener_alive());
+        assert_eq!(agent.health()["capture_listener"], "dead");
+    }
+
+    #[derive(Default)]
+    struct CaptureErrorRecorder(std::sync::Mutex<Vec<CaptureError>>);
+
+    impl LiveEventEmitter for CaptureErrorRecorder {
+        fn emit(&self, _event: &KeyEvent) {}
+
+        fn emit_capture_error(&self, error: &CaptureError) {
+            self.0.lock().unwrap().push(error.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listener_error_surfaced_to_ui() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let mut agent = Agent::new(masker, database).await.unwrap();
+        let emitter = Arc::new(CaptureErrorRecorder::default());
+        agent.set_live_emitter(emitter.clone());
+        assert!(agent.last_capture_error().is_none());
+
+        // Listener simulado que falha como o rdev sem permissão de acessibilidade
+        let status_tx = agent.start_status_forwarder();
+        let result = Agent::run_listener(&agent.listener_alive, &status_tx, || Err::<(), _>("permissão negada"));
+        assert!(result.is_err());
+
+        let mut error = None;
+        for _ in 0..50 {
+            error = agent.last_capture_error();
+            if error.is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        let error = error.expect("erro de captura não repassado");
+        assert!(error.reason.contains("permissão negada"));
+        assert_eq!(emitter.0.lock().unwrap().as_slice(), std::slice::from_ref(&error));
+        assert!(!agent.is_listener_alive());
+        assert_eq!(agent.health()["capture_listener"], "dead");
+        assert_eq!(agent.health()["capture_error"], error.reason);
+    }
+
+    #[tokio::test]
+    async fn test_listener_success_sends_no_error() {
+        let masker = Masker::new();
+        let database = create_test_database().await.unwrap();
+        let agent = Agent::new(masker, database).await.unwrap();
+
+        let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+        let result = Agent::run_listener(&agent.listener_alive, &status_tx, || Ok::<(), &str>(()));
+        assert!(result.is_ok());
+        drop(status_tx);
+        assert!(status_rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_retention_cutoff() {
+        assert_eq!(retention_cutoff(1_000_000_000, 0), None);
+        assert_eq!(retention_cutoff(1_000_000_000, 1), Some(1_000_000_000 - 86_400_000));
+        assert_eq!(retention_cutoff(1_000_000, 30), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_backup_is_restorable_and_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = create_test_database().await.unwrap();
+        database.store_events(&two_events()).await.unwrap();
+
+        let hour = 60 * 60 * 1000;
+        let start = 1_792_000_000_000;
+        let config = AgentConfig {
+            backup_dir: Some(dir.path().to_path_buf()),
+            backup_interval_hours: 1,
+            backups_kept: 2,
+            ..Default::default()
+        };
+
+        // Sem pasta configurada não há backup
+        assert!(run_scheduled_backup(&database, &AgentConfig::default(), start).await.unwrap().is_none());
+
+        let first = run_scheduled_backup(&database, &config, start).await.unwrap().expect("primeiro backup");
+        assert!(run_scheduled_backup(&database, &config, start + hour / 2).await.unwrap().is_none());
+        for i in 1..=3 {
+            assert!(run_scheduled_backup(&database, &config, start + i * hour).await.unwrap().is_some());
+        }
+
+        let backups = crate::db::list_backups(dir.path()).unwrap();
+        let kept: Vec<u64> = backups.iter().map(|backup| backup.created_at).collect();
+        assert_eq!(kept, vec![start + 3 * hour, start + 2 * hour]);
+        assert!(!std::path::Path::new(&first.path).exists());
+
+        database.clear_all_data().await.unwrap();
+        database.restore_from(&backups[0].path).await.unwrap();
+        assert_eq!(database.get_stats().await.unwrap().total_events, 2);
+    }
+
+    fn two_events() -> Vec<KeyEvent> {
+        ["a", "b"].iter().map(|key| press(key)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_flush_retries_transient_store_failure() {
+        let metrics = AgentMetrics::new();
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let mut buffer = two_events();
+
+        Agent::flush_with_retry(
+            |_events| {
+                let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    if attempt == 0 {
+                        Err(anyhow!("database is locked"))
+                    } else {
+                        Ok(())
+                    }
+                }
+            },
+            &mut buffer,
+            &metrics,
+            Duration::ZERO,
+        )
+        .await;
+
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+        assert!(buffer.is_empty());
+        let summary = metrics.get_summary();
+        assert_eq!(summary["events_stored"], 2);
+        assert_eq!(summary["events_discarded"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_never_exceeds_cap_with_failing_store() {
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            buffer_size: 100,
+            max_buffer_size: 10,
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(Masker::new(), database.clone(), config).await.unwrap();
+        agent.set_sink(Arc::new(FailingSink));
+        agent.start().await.unwrap();
+
+        // O processador não roda enquanto o teste não cede: só cabem 10 na fila
+        let accepted = (0..95)
+            .filter(|i| agent.submit_event(press(&format!("k{}", i))).is_ok())
+            .count();
+        assert_eq!(accepted, 10);
+
+        agent.stop().await.unwrap();
+        let summary = agent.get_metrics();
+        assert_eq!(summary["events_dropped_overflow"], 85);
+        assert_eq!(summary["events_processed"], 10);
+        assert_eq!(summary["events_discarded"], 10);
+        assert_eq!(database.get_stats().await.unwrap().total_events, 0);
+    }
+
+    /// Banco lento: cada gravação leva 100ms e registra o tamanho do lote
+    #[derive(Default)]
+    struct SlowSink {
+        batches: std::sync::Mutex<Vec<usize>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EventSink for SlowSink {
+        async fn store(&self, events: &[KeyEvent]) -> Result<()> {
+            self.batches.lock().unwrap().push(events.len());
+            sleep(Duration::from_millis(100)).await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_buffer_never_exceeds_cap_with_slow_store() {
+        let database = create_test_database().await.unwrap();
+        let config = AgentConfig {
+            enable_window_detection: false,
+            buffer_size: 5,
+            max_buffer_size: 20,
+            ..Default::default()
+        };
+        let mut agent = Agent::with_config(Masker::new(), database, config).await.unwrap();
+        let sink = Arc::new(SlowSink::default());
+        agent.set_sink(sink.clone());
+        agent.start().await.unwrap();
+
+        for i in 0..100 {
+            // A full queue means the processor is in a forced flush; wait for it
+            while agent.submit_event(press(&format!("k{}", i))).is_err() {
+                sleep(Duration::from_millis(5)).await;
+            }
+            sleep(Duration::from_millis(1)).await;
+        }
+        agent.stop().await.unwrap();
+
+        let batches = sink.batches.lock().unwrap().clone();
+        assert!(batches.iter().all(|&len| len <= 20), "{:?}", batches);
+        assert_eq!(batches.iter().sum::<usize>(), 100);
+        let summary = agent.get_metrics();
+        assert!(summary["forced_flushes"] > 0);
+        assert_eq!(summary["events_stored"], 100);
+        assert_eq!(summary["events_discarded"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_gives_up_after_max_attempts() {
+        let metrics = AgentMetrics::new();
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let mut buffer = two_events();
+
+        Agent::flush_with_retry(
+            |_events| {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                async { Err(anyhow!("disk I/O error")) }
+            },
+            &mut buffer,
+            &metrics,
+            Duration::ZERO,
+        )
+        .await;
+
+        assert_eq!(attempts.load(Ordering::Relaxed), FLUSH_MAX_ATTEMPTS);
+        assert!(buffer.is_empty());
+        let summary = metrics.get_summary();
+        assert_eq!(summary["events_stored"], 0);
+        assert_eq!(summary["events_discarded"], 2);
+    }
+}
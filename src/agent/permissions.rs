@@ -0,0 +1,123 @@
+//! Resultado da verificação de permissões de captura.
+//!
+//! As sondagens dependem da plataforma (acessibilidade no macOS, display X11
+//! no Linux, elevação do processo no Windows); aqui fica apenas a montagem do
+//! status, para que a interface possa orientar o usuário.
+
+use serde::{Serialize, Deserialize};
+
+/// Erro retornado ao iniciar a captura sem as permissões necessárias
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermissionDenied;
+
+impl std::fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Permissões insuficientes para captura de teclas")
+    }
+}
+
+impl std::error::Error for PermissionDenied {}
+
+/// O que o agente consegue fazer com as permissões atuais
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionStatus {
+    /// O listener global de teclas pode ser iniciado. Só no macOS `start`
+    /// recusa iniciar sem ela; nas demais plataformas é apenas informativo
+    pub accessibility: bool,
+    /// A janela ativa pode ser identificada de forma confiável
+    pub window_detection: bool,
+    /// Sistema operacional (`std::env::consts::OS`)
+    pub platform: String,
+}
+
+impl PermissionStatus {
+    /// macOS: a captura exige permissão de Acessibilidade. A janela ativa traz
+    /// aplicação e PID, mas o título depende da permissão de Gravação de Tela,
+    /// então os filtros por título não são confiáveis
+    pub fn macos(accessibility_granted: bool) -> Self {
+        Self {
+            accessibility: accessibility_granted,
+            window_detection: false,
+            platform: "macos".to_string(),
+        }
+    }
+
+    /// Linux: o rdev escuta via X11 (ou XWayland); a janela ativa depende de
+    /// X11 ou de um compositor Wayland suportado. Sem display o agente ainda
+    /// inicia, e uma falha do listener chega como erro de captura
+    pub fn linux(has_x11_display: bool, window_backend_available: bool) -> Self {
+        Self {
+            accessibility: has_x11_display,
+            window_detection: window_backend_available,
+            platform: "linux".to_string(),
+        }
+    }
+
+    /// Windows: o hook de teclado não requer permissão, mas sem elevação o
+    /// título/processo de aplicações elevadas não pode ser lido
+    pub fn windows(process_elevated: bool) -> Self {
+        Self {
+            accessibility: true,
+            window_detection: process_elevated,
+            platform: "windows".to_string(),
+        }
+    }
+
+    /// Plataformas sem suporte específico
+    pub fn other(platform: &str) -> Self {
+        Self {
+            accessibility: true,
+            window_detection: false,
+            platform: platform.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_macos_status() {
+        let granted = PermissionStatus::macos(true);
+        assert!(granted.accessibility);
+        assert!(!granted.window_detection);
+        assert_eq!(granted.platform, "macos");
+
+        assert!(!PermissionStatus::macos(false).accessibility);
+    }
+
+    #[test]
+    fn test_linux_status() {
+        let x11 = PermissionStatus::linux(true, true);
+        assert!(x11.accessibility && x11.window_detection);
+
+        // Wayland puro: sem XWayland o rdev não recebe teclas
+        let wayland_only = PermissionStatus::linux(false, true);
+        assert!(!wayland_only.accessibility);
+        assert!(wayland_only.window_detection);
+
+        let headless = PermissionStatus::linux(false, false);
+        assert!(!headless.accessibility && !headless.window_detection);
+        assert_eq!(headless.platform, "linux");
+    }
+
+    #[test]
+    fn test_windows_status() {
+        let elevated = PermissionStatus::windows(true);
+        assert!(elevated.accessibility && elevated.window_detection);
+
+        let standard = PermissionStatus::windows(false);
+        assert!(standard.accessibility);
+        assert!(!standard.window_detection);
+        assert_eq!(standard.platform, "windows");
+    }
+
+    #[test]
+    fn test_other_platform_status() {
+        let status = PermissionStatus::other("freebsd");
+        assert!(status.accessibility);
+        assert!(!status.window_detection);
+        assert_eq!(status.platform, "freebsd");
+    }
+}
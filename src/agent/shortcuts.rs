@@ -0,0 +1,139 @@
+//! Modificadores ativos no momento de cada tecla.
+//!
+//! O rdev entrega cada tecla isoladamente, então o conjunto de modificadores
+//! segurados é reconstruído a partir dos eventos de press/release. Os nomes
+//! são normalizados (lado esquerdo/direito unificados) para que `Ctrl+S`
+//! seja pesquisável independentemente de qual Ctrl foi usado.
+
+use std::collections::HashSet;
+
+use super::KeyEvent;
+
+/// Ordem canônica dos modificadores em uma combinação
+const MODIFIER_ORDER: [&str; 4] = ["Ctrl", "Alt", "Shift", "Meta"];
+
+/// Separador usado ao armazenar a combinação em texto
+pub const MODIFIER_SEPARATOR: &str = "+";
+
+/// Converte o nome de uma tecla (ou um alias digitado pelo usuário) no
+/// modificador canônico
+pub fn canonical_modifier(name: &str) -> Option<&'static str> {
+    match name.trim().to_lowercase().as_str() {
+        "ctrl" | "ctrlleft" | "ctrlright" | "control" | "controlleft" | "controlright" => Some("Ctrl"),
+        "alt" | "altgr" | "option" => Some("Alt"),
+        "shift" | "shiftleft" | "shiftright" => Some("Shift"),
+        "meta" | "metaleft" | "metaright" | "cmd" | "command" | "super" | "win" => Some("Meta"),
+        _ => None,
+    }
+}
+
+/// Normaliza uma lista de modificadores: remove duplicatas e nomes
+/// desconhecidos e aplica a ordem canônica
+pub fn normalize_modifiers<S: AsRef<str>>(names: &[S]) -> Vec<String> {
+    let present: HashSet<&'static str> = names
+        .iter()
+        .filter_map(|name| canonical_modifier(name.as_ref()))
+        .collect();
+
+    MODIFIER_ORDER
+        .iter()
+        .filter(|modifier| present.contains(*modifier))
+        .map(|modifier| modifier.to_string())
+        .collect()
+}
+
+/// Representação armazenada no banco (`None` quando não há modificadores)
+pub fn encode_modifiers(modifiers: &[String]) -> Option<String> {
+    let normalized = normalize_modifiers(modifiers);
+    if normalized.is_empty() {
+        None
+    } else {
+        Some(normalized.join(MODIFIER_SEPARATOR))
+    }
+}
+
+/// Inverso de `encode_modifiers`
+pub fn decode_modifiers(encoded: Option<&str>) -> Vec<String> {
+    encoded
+        .map(|value| normalize_modifiers(&value.split(MODIFIER_SEPARATOR).collect::<Vec<_>>()))
+        .unwrap_or_default()
+}
+
+/// Acompanha os modificadores segurados ao longo do fluxo de eventos
+#[derive(Debug, Default)]
+pub struct ModifierTracker {
+    held: HashSet<String>,
+}
+
+impl ModifierTracker {
+    /// Atualiza o estado com o evento e retorna os modificadores ativos para
+    /// ele (vazio para os próprios modificadores)
+    pub fn observe(&mut self, event: &KeyEvent) -> Vec<String> {
+        if canonical_modifier(&event.key).is_some() && event.is_modifier {
+            match event.event_type.as_str() {
+                "press" => {
+                    self.held.insert(event.key.clone());
+                }
+                "release" => {
+                    self.held.remove(&event.key);
+                }
+                _ => {}
+            }
+            return Vec::new();
+        }
+
+        normalize_modifiers(&self.held.iter().collect::<Vec<_>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str, event_type: &str, is_modifier: bool) -> KeyEvent {
+        KeyEvent {
+            timestamp: 1,
+            key: key.to_string(),
+            event_type: event_type.to_string(),
+            window_info: None,
+            is_modifier,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_modifiers() {
+        assert_eq!(normalize_modifiers(&["shift", "CtrlRight", "ctrl"]), vec!["Ctrl", "Shift"]);
+        assert_eq!(normalize_modifiers(&["Cmd", "unknown"]), vec!["Meta"]);
+        assert!(normalize_modifiers::<&str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let encoded = encode_modifiers(&["ShiftLeft".to_string(), "CtrlLeft".to_string()]);
+        assert_eq!(encoded.as_deref(), Some("Ctrl+Shift"));
+        assert_eq!(decode_modifiers(encoded.as_deref()), vec!["Ctrl", "Shift"]);
+
+        assert_eq!(encode_modifiers(&[]), None);
+        assert!(decode_modifiers(None).is_empty());
+    }
+
+    #[test]
+    fn test_tracker_follows_press_and_release() {
+        let mut tracker = ModifierTracker::default();
+
+        assert!(tracker.observe(&key("s", "press", false)).is_empty());
+
+        assert!(tracker.observe(&key("CtrlLeft", "press", true)).is_empty());
+        assert_eq!(tracker.observe(&key("s", "press", false)), vec!["Ctrl"]);
+
+        tracker.observe(&key("ShiftRight", "press", true));
+        assert_eq!(tracker.observe(&key("s", "press", false)), vec!["Ctrl", "Shift"]);
+
+        tracker.observe(&key("CtrlLeft", "release", true));
+        tracker.observe(&key("ShiftRight", "release", true));
+        assert!(tracker.observe(&key("s", "press", false)).is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+//! Destinos dos eventos capturados.
+//!
+//! O processador grava cada lote em um `EventSink`. O padrão é o banco local
+//! (`SqliteSink`); `CompositeSink` replica o lote para vários destinos, por
+//! exemplo o banco e um arquivo, sem que a falha de um impeça os demais.
+
+use std::sync::Arc;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use tracing::warn;
+
+use super::KeyEvent;
+use crate::db::Database;
+
+/// Destino de um lote de eventos já mascarados
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    async fn store(&self, events: &[KeyEvent]) -> Result<()>;
+}
+
+/// Grava no banco SQLite do perfil
+pub struct SqliteSink {
+    database: Arc<Database>,
+}
+
+impl SqliteSink {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait]
+impl EventSink for SqliteSink {
+    async fn store(&self, events: &[KeyEvent]) -> Result<()> {
+        self.database.store_events(events).await
+    }
+}
+
+/// Replica cada lote para todos os destinos, em ordem.
+///
+/// A falha de um destino é registrada e não interrompe os seguintes; o lote só
+/// é considerado perdido (e repetido pelo agente) se todos falharem
+pub struct CompositeSink {
+    sinks: Vec<Arc<dyn EventSink>>,
+}
+
+impl CompositeSink {
+    pub fn new(sinks: Vec<Arc<dyn EventSink>>) -> Self {
+        Self { sinks }
+    }
+}
+
+#[async_trait]
+impl EventSink for CompositeSink {
+    async fn store(&self, events: &[KeyEvent]) -> Result<()> {
+        let mut failures = 0;
+        for (index, sink) in self.sinks.iter().enumerate() {
+            if let Err(e) = sink.store(events).await {
+                warn!("⚠️ Destino {} de eventos falhou: {}", index, e);
+                failures += 1;
+            }
+        }
+
+        if !self.sinks.is_empty() && failures == self.sinks.len() {
+            return Err(anyhow!("Todos os {} destinos de eventos falharam", failures));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::test_support::press;
+
+    #[derive(Default)]
+    struct MockSink {
+        stored: Mutex<Vec<String>>,
+        fail: bool,
+    }
+
+    impl MockSink {
+        fn failing() -> Self {
+            Self { fail: true, ..Default::default() }
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.stored.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl EventSink for MockSink {
+        async fn store(&self, events: &[KeyEvent]) -> Result<()> {
+            if self.fail {
+                return Err(anyhow!("destino indisponível"));
+            }
+            self.stored.lock().unwrap().extend(events.iter().map(|event| event.key.clone()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_composite_fans_out_to_every_sink() {
+        let first = Arc::new(MockSink::default());
+        let second = Arc::new(MockSink::default());
+        let composite = CompositeSink::new(vec![first.clone(), second.clone()]);
+
+        composite.store(&[press("a"), press("b")]).await.unwrap();
+
+        assert_eq!(first.keys(), vec!["a", "b"]);
+        assert_eq!(second.keys(), vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_failing_sink_does_not_block_others() {
+        let healthy = Arc::new(MockSink::default());
+        let composite = CompositeSink::new(vec![Arc::new(MockSink::failing()), healthy.clone()]);
+
+        assert!(composite.store(&[press("a")]).await.is_ok());
+        assert_eq!(healthy.keys(), vec!["a"]);
+
+        let all_failing = CompositeSink::new(vec![
+            Arc::new(MockSink::failing()) as Arc<dyn EventSink>,
+            Arc::new(MockSink::failing()),
+        ]);
+        assert!(all_failing.store(&[press("a")]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_sink_stores_in_database() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let database = Arc::new(Database::new(temp_file.path()).await.unwrap());
+        let sink = SqliteSink::new(database.clone());
+
+        sink.store(&[press("a")]).await.unwrap();
+        assert_eq!(database.get_stats().await.unwrap().total_events, 1);
+    }
+}
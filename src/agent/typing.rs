@@ -0,0 +1,128 @@
+//! Ritmo de digitação: teclas por minuto e tempo ativo digitando.
+//!
+//! As teclas do último minuto ficam num anel de contadores por segundo, então
+//! registrar custa O(1) e a consulta percorre um tamanho fixo. O tempo ativo
+//! conta janelas de `ACTIVE_BUCKET_SECS` com pelo menos uma tecla.
+
+/// Janela usada no cálculo de teclas por minuto
+const RATE_WINDOW_SECS: usize = 60;
+
+/// Tamanho de cada janela de atividade, em segundos
+pub const ACTIVE_BUCKET_SECS: u64 = 5;
+
+#[derive(Debug)]
+pub struct TypingActivity {
+    /// `(segundo, teclas)` indexado por `segundo % RATE_WINDOW_SECS`
+    recent: [(u64, u32); RATE_WINDOW_SECS],
+    last_active_bucket: Option<u64>,
+    active_buckets: u64,
+}
+
+impl Default for TypingActivity {
+    fn default() -> Self {
+        Self {
+            recent: [(0, 0); RATE_WINDOW_SECS],
+            last_active_bucket: None,
+            active_buckets: 0,
+        }
+    }
+}
+
+impl TypingActivity {
+    /// Registra uma tecla pressionada no instante `timestamp` (segundos)
+    pub fn record(&mut self, timestamp: u64) {
+        let slot = &mut self.recent[(timestamp % RATE_WINDOW_SECS as u64) as usize];
+        if slot.0 == timestamp {
+            slot.1 += 1;
+        } else if slot.0 < timestamp {
+            // A posição guardava um segundo que já saiu da janela
+            *slot = (timestamp, 1);
+        }
+
+        let bucket = timestamp / ACTIVE_BUCKET_SECS;
+        if self.last_active_bucket.map_or(true, |last| bucket > last) {
+            self.last_active_bucket = Some(bucket);
+            self.active_buckets += 1;
+        }
+    }
+
+    /// Teclas pressionadas nos 60 segundos que terminam em `now`
+    pub fn keys_per_minute(&self, now: u64) -> u64 {
+        let oldest = now.saturating_sub(RATE_WINDOW_SECS as u64 - 1);
+        self.recent
+            .iter()
+            .filter(|(second, count)| *count > 0 && (oldest..=now).contains(second))
+            .map(|(_, count)| u64::from(*count))
+            .sum()
+    }
+
+    /// Segundos em janelas com ao menos uma tecla
+    pub fn active_seconds(&self) -> u64 {
+        self.active_buckets * ACTIVE_BUCKET_SECS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_steady_typing_rate() {
+        let mut activity = TypingActivity::default();
+
+        // 4 teclas por segundo durante 2 minutos
+        for second in 0..120 {
+            for _ in 0..4 {
+                activity.record(START + second);
+            }
+        }
+
+        assert_eq!(activity.keys_per_minute(START + 119), 240);
+        // 120s contínuos ocupam 24 janelas de 5s (START é múltiplo de 5)
+        assert_eq!(activity.active_seconds(), 120);
+    }
+
+    #[test]
+    fn test_rate_decays_after_pause() {
+        let mut activity = TypingActivity::default();
+        for second in 0..30 {
+            activity.record(START + second);
+        }
+
+        assert_eq!(activity.keys_per_minute(START + 29), 30);
+        assert_eq!(activity.keys_per_minute(START + 74), 15);
+        assert_eq!(activity.keys_per_minute(START + 200), 0);
+
+        // Depois da pausa, o anel é reaproveitado sem somar segundos antigos
+        activity.record(START + 200);
+        assert_eq!(activity.keys_per_minute(START + 200), 1);
+    }
+
+    #[test]
+    fn test_active_time_ignores_idle_gaps() {
+        let mut activity = TypingActivity::default();
+
+        // Rajadas curtas separadas por minutos de inatividade
+        for burst in 0..3 {
+            let at = START + burst * 300;
+            for offset in 0..3 {
+                activity.record(at + offset);
+            }
+        }
+
+        let active = activity.active_seconds();
+        assert!((3 * 3..=3 * 2 * ACTIVE_BUCKET_SECS).contains(&active), "{}", active);
+    }
+
+    #[test]
+    fn test_late_events_do_not_double_count() {
+        let mut activity = TypingActivity::default();
+        activity.record(START + 10);
+        activity.record(START + 2);
+
+        assert_eq!(activity.active_seconds(), ACTIVE_BUCKET_SECS);
+        assert_eq!(activity.keys_per_minute(START + 10), 2);
+    }
+}
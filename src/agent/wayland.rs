@@ -0,0 +1,311 @@
+//! Detecção de janela ativa em sessões Wayland.
+//!
+//! O protocolo Wayland não expõe a janela em foco para clientes comuns, então
+//! cada compositor é consultado pela sua própria interface: IPC do sway
+//! (wlroots), `hyprctl` no Hyprland e D-Bus no GNOME Shell. Quando nenhuma
+//! delas está disponível, o agente recorre ao X11 (XWayland).
+
+use std::process::Command;
+use serde_json::Value;
+use tracing::debug;
+
+use super::WindowInfo;
+
+/// Backend utilizado para detectar a janela ativa no Linux
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinuxWindowBackend {
+    X11,
+    Sway,
+    Hyprland,
+    GnomeShell,
+    Unsupported,
+}
+
+/// Seleciona o backend a partir das variáveis de ambiente da sessão atual
+pub fn detect_backend() -> LinuxWindowBackend {
+    select_backend(|name| std::env::var(name).ok())
+}
+
+/// Indica se há um display X11 (ou XWayland) disponível
+pub fn has_x11_display() -> bool {
+    is_set(std::env::var("DISPLAY").ok())
+}
+
+/// Lógica de seleção do backend, independente do ambiente real
+pub fn select_backend<F>(env: F) -> LinuxWindowBackend
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let has_x11 = is_set(env("DISPLAY"));
+
+    if !is_set(env("WAYLAND_DISPLAY")) {
+        return if has_x11 {
+            LinuxWindowBackend::X11
+        } else {
+            LinuxWindowBackend::Unsupported
+        };
+    }
+
+    if is_set(env("SWAYSOCK")) {
+        return LinuxWindowBackend::Sway;
+    }
+
+    if is_set(env("HYPRLAND_INSTANCE_SIGNATURE")) {
+        return LinuxWindowBackend::Hyprland;
+    }
+
+    let desktop = env("XDG_CURRENT_DESKTOP").unwrap_or_default().to_lowercase();
+    if desktop.split(':').any(|d| d == "gnome") {
+        return LinuxWindowBackend::GnomeShell;
+    }
+
+    if has_x11 {
+        LinuxWindowBackend::X11
+    } else {
+        LinuxWindowBackend::Unsupported
+    }
+}
+
+fn is_set(value: Option<String>) -> bool {
+    value.map(|v| !v.trim().is_empty()).unwrap_or(false)
+}
+
+/// Consulta o compositor Wayland pela janela em foco
+pub fn get_active_window(backend: LinuxWindowBackend, timestamp: u64) -> Option<WindowInfo> {
+    let window = match backend {
+        LinuxWindowBackend::Sway => {
+            run_command("swaymsg", &["-t", "get_tree", "-r"]).and_then(|out| parse_sway_tree(&out))
+        }
+        LinuxWindowBackend::Hyprland => {
+            run_command("hyprctl", &["activewindow", "-j"]).and_then(|out| parse_hyprland_window(&out))
+        }
+        LinuxWindowBackend::GnomeShell => run_command(
+            "gdbus",
+            &[
+                "call",
+                "--session",
+                "--dest",
+                "org.gnome.Shell",
+                "--object-path",
+                "/org/gnome/Shell",
+                "--method",
+                "org.gnome.Shell.Eval",
+                GNOME_FOCUS_SCRIPT,
+            ],
+        )
+        .and_then(|out| parse_gnome_eval(&out)),
+        LinuxWindowBackend::X11 | LinuxWindowBackend::Unsupported => None,
+    };
+
+    window.map(|(title, application, process_id)| WindowInfo {
+        title,
+        application,
+        process_id,
+        timestamp,
+    })
+}
+
+/// Script avaliado pelo GNOME Shell (requer `Eval` habilitado)
+const GNOME_FOCUS_SCRIPT: &str = "(() => { const w = global.display.focus_window; \
+    return w ? JSON.stringify({ title: w.get_title(), app_id: w.get_wm_class(), pid: w.get_pid() }) : ''; })()";
+
+fn run_command(program: &str, args: &[&str]) -> Option<String> {
+    match Command::new(program).args(args).output() {
+        Ok(output) if output.status.success() => String::from_utf8(output.stdout).ok(),
+        Ok(output) => {
+            debug!("🪟 {} retornou status {:?}", program, output.status.code());
+            None
+        }
+        Err(e) => {
+            debug!("🪟 Não foi possível executar {}: {}", program, e);
+            None
+        }
+    }
+}
+
+type ParsedWindow = (String, String, Option<u32>);
+
+/// Extrai a janela em foco da árvore retornada por `swaymsg -t get_tree`
+pub fn parse_sway_tree(json: &str) -> Option<ParsedWindow> {
+    let tree: Value = serde_json::from_str(json).ok()?;
+    let focused = find_focused_node(&tree)?;
+
+    let title = focused.get("name").and_then(Value::as_str).unwrap_or("Unknown");
+    let application = focused
+        .get("app_id")
+        .and_then(Value::as_str)
+        .or_else(|| {
+            focused
+                .get("window_properties")
+                .and_then(|props| props.get("class"))
+                .and_then(Value::as_str)
+        })
+        .unwrap_or("Unknown");
+    let pid = focused.get("pid").and_then(Value::as_u64).map(|pid| pid as u32);
+
+    Some((title.to_string(), application.to_string(), pid))
+}
+
+fn find_focused_node(node: &Value) -> Option<&Value> {
+    if node.get("focused").and_then(Value::as_bool).unwrap_or(false) {
+        return Some(node);
+    }
+
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(*key).and_then(Value::as_array))
+        .flatten()
+        .find_map(find_focused_node)
+}
+
+/// Interpreta a saída de `hyprctl activewindow -j`
+pub fn parse_hyprland_window(json: &str) -> Option<ParsedWindow> {
+    let window: Value = serde_json::from_str(json).ok()?;
+
+    let title = window.get("title").and_then(Value::as_str)?;
+    let application = window
+        .get("class")
+        .and_then(Value::as_str)
+        .filter(|class| !class.is_empty())
+        .unwrap_or("Unknown");
+    let pid = window
+        .get("pid")
+        .and_then(Value::as_i64)
+        .filter(|pid| *pid > 0)
+        .map(|pid| pid as u32);
+
+    Some((title.to_string(), application.to_string(), pid))
+}
+
+/// Interpreta a resposta do `org.gnome.Shell.Eval`, no formato
+/// `(true, '{"title": ..., "app_id": ..., "pid": ...}')`
+pub fn parse_gnome_eval(output: &str) -> Option<ParsedWindow> {
+    let output = output.trim();
+    if !output.starts_with("(true") {
+        debug!("🪟 GNOME Shell recusou Eval - detecção de janela indisponível");
+        return None;
+    }
+
+    let start = output.find('\'')? + 1;
+    let end = output.rfind('\'')?;
+    if end <= start {
+        return None;
+    }
+
+    let payload = output[start..end].replace("\\'", "'");
+    let window: Value = serde_json::from_str(&payload).ok()?;
+
+    let title = window.get("title").and_then(Value::as_str).unwrap_or("Unknown");
+    let application = window.get("app_id").and_then(Value::as_str).unwrap_or("Unknown");
+    let pid = window.get("pid").and_then(Value::as_u64).map(|pid| pid as u32);
+
+    Some((title.to_string(), application.to_string(), pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env_from(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_select_backend_x11_only() {
+        let env = env_from(&[("DISPLAY", ":0")]);
+        assert_eq!(select_backend(env), LinuxWindowBackend::X11);
+    }
+
+    #[test]
+    fn test_select_backend_without_display() {
+        let env = env_from(&[]);
+        assert_eq!(select_backend(env), LinuxWindowBackend::Unsupported);
+    }
+
+    #[test]
+    fn test_select_backend_wayland_compositors() {
+        let env = env_from(&[("WAYLAND_DISPLAY", "wayland-1"), ("SWAYSOCK", "/run/user/1000/sway.sock")]);
+        assert_eq!(select_backend(env), LinuxWindowBackend::Sway);
+
+        let env = env_from(&[("WAYLAND_DISPLAY", "wayland-1"), ("HYPRLAND_INSTANCE_SIGNATURE", "abc")]);
+        assert_eq!(select_backend(env), LinuxWindowBackend::Hyprland);
+
+        let env = env_from(&[("WAYLAND_DISPLAY", "wayland-0"), ("XDG_CURRENT_DESKTOP", "ubuntu:GNOME")]);
+        assert_eq!(select_backend(env), LinuxWindowBackend::GnomeShell);
+    }
+
+    #[test]
+    fn test_select_backend_wayland_falls_back_to_xwayland() {
+        let env = env_from(&[("WAYLAND_DISPLAY", "wayland-0"), ("XDG_CURRENT_DESKTOP", "KDE"), ("DISPLAY", ":1")]);
+        assert_eq!(select_backend(env), LinuxWindowBackend::X11);
+
+        let env = env_from(&[("WAYLAND_DISPLAY", "wayland-0"), ("XDG_CURRENT_DESKTOP", "KDE")]);
+        assert_eq!(select_backend(env), LinuxWindowBackend::Unsupported);
+    }
+
+    #[test]
+    fn test_select_backend_ignores_empty_wayland_display() {
+        let env = env_from(&[("WAYLAND_DISPLAY", ""), ("SWAYSOCK", "/tmp/sway.sock"), ("DISPLAY", ":0")]);
+        assert_eq!(select_backend(env), LinuxWindowBackend::X11);
+    }
+
+    #[test]
+    fn test_parse_sway_tree() {
+        let json = r#"{
+            "name": "root", "focused": false,
+            "nodes": [{
+                "name": "eDP-1", "focused": false,
+                "nodes": [
+                    { "name": "notes.md - Code", "app_id": "code", "pid": 4242, "focused": true, "nodes": [] }
+                ]
+            }]
+        }"#;
+
+        let (title, application, pid) = parse_sway_tree(json).unwrap();
+        assert_eq!(title, "notes.md - Code");
+        assert_eq!(application, "code");
+        assert_eq!(pid, Some(4242));
+    }
+
+    #[test]
+    fn test_parse_sway_tree_xwayland_class() {
+        let json = r#"{
+            "focused": false,
+            "floating_nodes": [
+                { "name": "Steam", "app_id": null, "window_properties": { "class": "steam" }, "focused": true }
+            ]
+        }"#;
+
+        let (_, application, pid) = parse_sway_tree(json).unwrap();
+        assert_eq!(application, "steam");
+        assert_eq!(pid, None);
+    }
+
+    #[test]
+    fn test_parse_hyprland_window() {
+        let json = r#"{ "title": "Terminal", "class": "kitty", "pid": 1337 }"#;
+        let (title, application, pid) = parse_hyprland_window(json).unwrap();
+        assert_eq!(title, "Terminal");
+        assert_eq!(application, "kitty");
+        assert_eq!(pid, Some(1337));
+
+        // Sem janela em foco o hyprctl retorna um objeto vazio
+        assert!(parse_hyprland_window("{}").is_none());
+    }
+
+    #[test]
+    fn test_parse_gnome_eval() {
+        let output = r#"(true, '{"title":"Inbox - Mail","app_id":"org.gnome.Evolution","pid":900}')"#;
+        let (title, application, pid) = parse_gnome_eval(output).unwrap();
+        assert_eq!(title, "Inbox - Mail");
+        assert_eq!(application, "org.gnome.Evolution");
+        assert_eq!(pid, Some(900));
+
+        assert!(parse_gnome_eval("(false, '')").is_none());
+    }
+}
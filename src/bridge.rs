@@ -0,0 +1,322 @@
+//! Ponte REST local (feature `rest-bridge`).
+//!
+//! Expõe `search_text` e `stats` do perfil ativo em `127.0.0.1`, com os mesmos
+//! formatos JSON do search-service (`ApiResponse`, `SearchRequest`,
+//! `SearchResponse`), para que ferramentas externas consultem o banco local.
+//! Toda requisição precisa do cabeçalho `Authorization: Bearer <token>`; o
+//! token é gerado a cada inicialização e gravado em `rest-bridge.token`.
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::Result;
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use tracing::{info, error, debug};
+
+use crate::db::{DatabaseStats, SearchResult};
+use crate::local_http::LocalServer;
+use crate::profiles::ActiveProfile;
+use crate::search::SearchOptions;
+
+/// Porta da ponte; sem a variável a ponte não é iniciada
+pub const BRIDGE_PORT_ENV: &str = "KEYAI_BRIDGE_PORT";
+
+/// Arquivo onde o token da sessão atual é gravado
+pub const TOKEN_FILE_NAME: &str = "rest-bridge.token";
+
+const MAX_LIMIT: usize = 200;
+
+/// Envelope das respostas, como no search-service
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiResponse<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub error: Option<String>,
+}
+
+impl<T> ApiResponse<T> {
+    fn ok(data: T) -> Self {
+        Self { success: true, data: Some(data), error: None }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self { success: false, data: None, error: Some(message.into()) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchRequest {
+    pub query: String,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+    /// Total de ocorrências da consulta, não só as desta página
+    pub total_results: usize,
+    pub query: String,
+    pub took_ms: u64,
+    pub has_more: bool,
+    /// `offset` da próxima página, quando `has_more`
+    pub next_offset: Option<usize>,
+}
+
+#[derive(Clone)]
+struct BridgeState {
+    profile: Arc<ActiveProfile>,
+    token: Arc<str>,
+}
+
+/// Ponte em execução com o token desta sessão; para de aceitar requisições
+/// quando o handle é descartado
+pub struct RestBridge {
+    server: LocalServer,
+    token: String,
+}
+
+impl RestBridge {
+    /// Gera um token novo e inicia a ponte em `127.0.0.1:port` (0 escolhe uma
+    /// porta livre)
+    pub async fn start(profile: Arc<ActiveProfile>, port: u16) -> Result<Self> {
+        let token = generate_token();
+        let app = router(BridgeState {
+            profile,
+            token: Arc::from(token.as_str()),
+        });
+        let server = LocalServer::start(app, port, "rest-bridge").await?;
+
+        info!("🌉 Ponte REST ouvindo em http://{}", server.addr());
+        Ok(Self { server, token })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.server.addr()
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Grava o token em `dir/rest-bridge.token`, legível só pelo usuário no Unix
+    pub fn write_token(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(TOKEN_FILE_NAME);
+        std::fs::write(&path, &self.token)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        }
+        debug!("🔑 Token da ponte REST gravado em {:?}", path);
+        Ok(())
+    }
+}
+
+fn router(state: BridgeState) -> Router {
+    Router::new()
+        .route("/search/text", post(search_text))
+        .route("/stats", get(stats))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+async fn require_token(State(state): State<BridgeState>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| tokens_match(token, &state.token));
+
+    if !authorized {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(ApiResponse::<()>::error("Token ausente ou inválido")),
+        ).into_response();
+    }
+    next.run(request).await
+}
+
+async fn search_text(State(state): State<BridgeState>, Json(request): Json<SearchRequest>) -> Response {
+    debug!("🌉 search_text via ponte REST: limit={}, offset={}", request.limit, request.offset);
+
+    let start_time = std::time::Instant::now();
+    let options = SearchOptions {
+        limit: request.limit.clamp(1, MAX_LIMIT),
+        offset: request.offset,
+        ..Default::default()
+    };
+
+    let database = state.profile.database();
+    let search = async {
+        let results = database.search_text_with_options(&request.query, &options).await?;
+        let total_results = database.count_text_matches(&request.query, &options).await?;
+        anyhow::Ok((results, total_results))
+    };
+
+    match search.await {
+        Ok((results, total_results)) => {
+            let end = options.offset + results.len();
+            let has_more = !results.is_empty() && end < total_results;
+            Json(ApiResponse::ok(SearchResponse {
+                results,
+                total_results,
+                query: request.query,
+                took_ms: start_time.elapsed().as_millis() as u64,
+                has_more,
+                next_offset: has_more.then_some(end),
+            })).into_response()
+        },
+        Err(e) => {
+            error!("❌ Erro na busca via ponte REST: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<SearchResponse>::error(format!("Erro na busca: {}", e))),
+            ).into_response()
+        }
+    }
+}
+
+async fn stats(State(state): State<BridgeState>) -> Response {
+    match state.profile.database().get_stats().await {
+        Ok(stats) => Json(ApiResponse::ok(stats)).into_response(),
+        Err(e) => {
+            error!("❌ Erro ao obter estatísticas via ponte REST: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<DatabaseStats>::error(format!("Erro ao obter estatísticas: {}", e))),
+            ).into_response()
+        }
+    }
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Comparação sem saída antecipada, para não vazar o token pelo tempo de resposta
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::{Profile, DEFAULT_PROFILE};
+    use crate::test_support::{http_request, press_at};
+
+    async fn send(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: &str,
+    ) -> (u16, serde_json::Value) {
+        let auth = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        let headers = format!("Content-Type: application/json\r\n{}", auth);
+        let (status, body) = http_request(addr, method, path, &headers, body).await;
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    async fn start_bridge(dir: &Path) -> RestBridge {
+        let profile = Profile::open(dir, DEFAULT_PROFILE, None).await.unwrap();
+        profile.database.store_events(&[press_at("k", 1), press_at("z", 2)]).await.unwrap();
+        RestBridge::start(Arc::new(ActiveProfile::new(dir, profile)), 0).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_valid_token_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let bridge = start_bridge(dir.path()).await;
+        assert!(bridge.addr().ip().is_loopback());
+
+        let (status, body) = send(bridge.addr(), "GET", "/stats", None, "").await;
+        assert_eq!(status, 401);
+        assert_eq!(body["success"], false);
+
+        let (status, _) = send(bridge.addr(), "GET", "/stats", Some("outro-token"), "").await;
+        assert_eq!(status, 401);
+
+        let search = r#"{"query": "k"}"#;
+        let (status, body) = send(bridge.addr(), "POST", "/search/text", None, search).await;
+        assert_eq!(status, 401);
+        assert!(body["data"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_search_and_stats_with_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let bridge = start_bridge(dir.path()).await;
+        let token = bridge.token().to_string();
+
+        let (status, body) = send(bridge.addr(), "GET", "/stats", Some(&token), "").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["success"], true);
+        assert_eq!(body["data"]["total_events"], 2);
+
+        let search = r#"{"query": "k", "limit": 10}"#;
+        let (status, body) = send(bridge.addr(), "POST", "/search/text", Some(&token), search).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["total_results"], 1);
+        assert_eq!(body["data"]["query"], "k");
+        assert_eq!(body["data"]["results"][0]["content"], "k");
+
+        bridge.write_token(dir.path()).unwrap();
+        let saved = std::fs::read_to_string(dir.path().join(TOKEN_FILE_NAME)).unwrap();
+        assert_eq!(saved, token);
+    }
+
+    #[tokio::test]
+    async fn test_search_reports_total_beyond_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = Profile::open(dir.path(), DEFAULT_PROFILE, None).await.unwrap();
+        let events: Vec<_> = (1..=5).map(|timestamp| press_at("k", timestamp)).collect();
+        profile.database.store_events(&events).await.unwrap();
+        let bridge = RestBridge::start(Arc::new(ActiveProfile::new(dir.path(), profile)), 0).await.unwrap();
+        let token = bridge.token().to_string();
+
+        let search = r#"{"query": "k", "limit": 2}"#;
+        let (_, body) = send(bridge.addr(), "POST", "/search/text", Some(&token), search).await;
+        assert_eq!(body["data"]["results"].as_array().unwrap().len(), 2);
+        assert_eq!(body["data"]["total_results"], 5);
+        assert_eq!(body["data"]["has_more"], true);
+        assert_eq!(body["data"]["next_offset"], 2);
+
+        let last_page = r#"{"query": "k", "limit": 2, "offset": 4}"#;
+        let (_, body) = send(bridge.addr(), "POST", "/search/text", Some(&token), last_page).await;
+        assert_eq!(body["data"]["total_results"], 5);
+        assert_eq!(body["data"]["has_more"], false);
+        assert!(body["data"]["next_offset"].is_null());
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abd", "abc"));
+        assert!(!tokens_match("ab", "abc"));
+        assert_eq!(generate_token().len(), 64);
+    }
+}
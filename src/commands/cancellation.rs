@@ -0,0 +1,216 @@
+//! Cancelamento e tempo limite das buscas disparadas pela interface.
+//!
+//! Cada busca pode receber um `request_id`; enquanto ela roda, o token fica
+//! registrado e `cancel_search` o aciona. A busca executa em uma task
+//! separada, abortada quando o token é cancelado ou o tempo limite expira; a
+//! consulta SQLite em andamento é interrompida junto (`db::cancellable`).
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use super::CommandError;
+use crate::db::{cancellable, QueryInterrupts};
+
+/// Tempo limite de cada comando de busca, em segundos
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchTimeouts {
+    pub text_secs: u64,
+    pub semantic_secs: u64,
+    pub hybrid_secs: u64,
+}
+
+impl Default for SearchTimeouts {
+    fn default() -> Self {
+        Self {
+            text_secs: 10,
+            semantic_secs: 30,
+            hybrid_secs: 30,
+        }
+    }
+}
+
+/// Token registrado, com a geração para distinguir reutilizações do mesmo id
+type InFlight = HashMap<String, (u64, CancellationToken)>;
+
+/// Buscas concluídas com sucesso e o tempo somado delas
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchLatency {
+    pub completed: u64,
+    pub total_secs: f64,
+}
+
+/// Buscas em andamento que podem ser canceladas pelo `request_id`
+#[derive(Debug, Default)]
+pub struct SearchRegistry {
+    in_flight: Mutex<InFlight>,
+    next_generation: AtomicU64,
+    timeouts: SearchTimeouts,
+    completed: AtomicU64,
+    total_micros: AtomicU64,
+}
+
+impl SearchRegistry {
+    pub fn new(timeouts: SearchTimeouts) -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+            next_generation: AtomicU64::new(0),
+            timeouts,
+            completed: AtomicU64::new(0),
+            total_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn timeouts(&self) -> &SearchTimeouts {
+        &self.timeouts
+    }
+
+    /// Cancela a busca com o `request_id`; retorna `false` se ela não está em andamento
+    pub fn cancel(&self, request_id: &str) -> bool {
+        match self.lock().remove(request_id) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Latência acumulada das buscas que terminaram com sucesso
+    pub fn latency(&self) -> SearchLatency {
+        SearchLatency {
+            completed: self.completed.load(Ordering::Relaxed),
+            total_secs: self.total_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+        }
+    }
+
+    /// Quantidade de buscas canceláveis em andamento
+    pub fn in_flight(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Executa `task` em uma task própria, abortando-a no cancelamento ou no tempo limite
+    pub async fn run<T, F>(&self, request_id: Option<String>, timeout: Duration, task: F) -> Result<T, CommandError>
+    where
+        F: Future<Output = Result<T, CommandError>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let token = CancellationToken::new();
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        if let Some(id) = &request_id {
+            if let Some((_, previous)) = self.lock().insert(id.clone(), (generation, token.clone())) {
+                warn!("⚠️ request_id '{}' reutilizado; busca anterior cancelada", id);
+                previous.cancel();
+            }
+        }
+
+        let started = Instant::now();
+        // Aborting only lands at an await; a running rusqlite query is interrupted instead
+        let interrupts = Arc::new(QueryInterrupts::default());
+        let mut handle = tokio::spawn(cancellable(interrupts.clone(), task));
+        let result = tokio::select! {
+            joined = &mut handle => joined.unwrap_or_else(|e| {
+                Err(CommandError::Internal(format!("Tarefa de busca falhou: {}", e)))
+            }),
+            _ = token.cancelled() => {
+                interrupts.interrupt_all();
+                handle.abort();
+                debug!("🛑 Busca {:?} cancelada", request_id);
+                Err(CommandError::Cancelled("Busca cancelada".to_string()))
+            }
+            _ = tokio::time::sleep(timeout) => {
+                interrupts.interrupt_all();
+                handle.abort();
+                Err(CommandError::Timeout(format!("Busca excedeu o tempo limite de {}s", timeout.as_secs())))
+            }
+        };
+
+        if result.is_ok() {
+            self.completed.fetch_add(1, Ordering::Relaxed);
+            self.total_micros.fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+        }
+
+        if let Some(id) = &request_id {
+            let mut in_flight = self.lock();
+            // Não remove o token de uma busca mais nova com o mesmo id
+            if in_flight.get(id).map(|(current, _)| *current == generation).unwrap_or(false) {
+                in_flight.remove(id);
+            }
+        }
+
+        result
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, InFlight> {
+        self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    async fn slow_search() -> Result<Vec<String>, CommandError> {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        Ok(vec!["resultado".to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_cancel_in_flight_search() {
+        let registry = Arc::new(SearchRegistry::default());
+
+        let runner = registry.clone();
+        let search = tokio::spawn(async move {
+            runner.run(Some("busca-1".to_string()), Duration::from_secs(60), slow_search()).await
+        });
+
+        // Espera a busca ser registrada antes de cancelar
+        while registry.in_flight() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(registry.cancel("busca-1"));
+
+        let result = search.await.unwrap();
+        assert_eq!(result.unwrap_err().code(), "cancelled");
+        assert_eq!(registry.in_flight(), 0);
+        assert!(!registry.cancel("busca-1"));
+    }
+
+    #[tokio::test]
+    async fn test_search_times_out() {
+        let registry = SearchRegistry::default();
+
+        let result = registry.run(None, Duration::from_millis(20), slow_search()).await;
+        assert_eq!(result.unwrap_err().code(), "timeout");
+    }
+
+    #[tokio::test]
+    async fn test_completed_search_is_unregistered() {
+        let registry = SearchRegistry::default();
+
+        let result = registry
+            .run(Some("busca-2".to_string()), Duration::from_secs(5), async { Ok(42) })
+            .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(registry.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_latency_counts_only_successful_searches() {
+        let registry = SearchRegistry::default();
+
+        registry.run(None, Duration::from_secs(5), async { Ok(1) }).await.unwrap();
+        let _ = registry.run(None, Duration::from_millis(20), slow_search()).await;
+
+        let latency = registry.latency();
+        assert_eq!(latency.completed, 1);
+        assert!(latency.total_secs < 5.0);
+    }
+}
@@ -0,0 +1,104 @@
+//! Criptografia dos arquivos de exportação.
+//!
+//! O arquivo exportado sai do banco SQLCipher, então é cifrado com
+//! XChaCha20-Poly1305 usando uma chave derivada da senha via Argon2id.
+//! Formato: `MAGIC || salt (16) || nonce (24) || texto cifrado + tag`.
+
+use anyhow::{Result, anyhow};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+
+/// Assinatura no início dos arquivos cifrados
+const MAGIC: &[u8] = b"KEYAI-ENC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+/// Senha incorreta ou arquivo cifrado corrompido
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPassphrase;
+
+impl std::fmt::Display for InvalidPassphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Senha incorreta ou arquivo corrompido")
+    }
+}
+
+impl std::error::Error for InvalidPassphrase {}
+
+/// `true` se o conteúdo foi gerado por `encrypt`
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut rng = rand::thread_rng();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let cipher = cipher_for(passphrase, &salt)?;
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .map_err(|e| anyhow!("Falha ao cifrar exportação: {}", e))?;
+
+    let mut output = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    output.extend_from_slice(MAGIC);
+    output.extend_from_slice(&salt);
+    output.extend_from_slice(&nonce);
+    output.extend_from_slice(&ciphertext);
+    Ok(output)
+}
+
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    if !is_encrypted(data) || data.len() < HEADER_LEN {
+        return Err(InvalidPassphrase.into());
+    }
+
+    let salt = &data[MAGIC.len()..MAGIC.len() + SALT_LEN];
+    let nonce = &data[MAGIC.len() + SALT_LEN..HEADER_LEN];
+
+    // A tag do Poly1305 não confere tanto com senha errada quanto com dados adulterados
+    cipher_for(passphrase, salt)?
+        .decrypt(XNonce::from_slice(nonce), &data[HEADER_LEN..])
+        .map_err(|_| InvalidPassphrase.into())
+}
+
+fn cipher_for(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Falha ao derivar chave da senha: {}", e))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let encrypted = encrypt(b"[{\"key\":\"a\"}]", "senha forte").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.windows(3).any(|w| w == b"key"));
+
+        assert_eq!(decrypt(&encrypted, "senha forte").unwrap(), b"[{\"key\":\"a\"}]");
+    }
+
+    #[test]
+    fn test_wrong_passphrase_and_tampering() {
+        let mut encrypted = encrypt(b"dados", "correta").unwrap();
+
+        let error = decrypt(&encrypted, "errada").unwrap_err();
+        assert!(error.downcast_ref::<InvalidPassphrase>().is_some());
+
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 0xff;
+        assert!(decrypt(&encrypted, "correta").is_err());
+
+        assert!(decrypt(b"[]", "correta").is_err());
+    }
+}
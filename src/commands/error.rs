@@ -0,0 +1,179 @@
+//! Erro estruturado retornado pelos comandos Tauri.
+//!
+//! Serializa como `{ "code": "...", "message": "..." }`, permitindo que a
+//! interface traduza a mensagem e decida o que fazer a partir do `code`.
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::agent::PermissionDenied;
+use crate::search::SemanticUnavailable;
+use super::encryption::InvalidPassphrase;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// Falha ao ler ou gravar no banco de dados
+    Database(String),
+    /// Falha ao executar uma busca
+    Search(String),
+    /// Falha ao controlar o agente de captura
+    Agent(String),
+    /// Permissões do sistema insuficientes
+    Permission(String),
+    /// Argumento inválido ou confirmação ausente
+    Validation(String),
+    /// Recurso indisponível nesta instalação (ex.: busca semântica sem modelo)
+    Unavailable(String),
+    /// Busca cancelada pela interface (`cancel_search`)
+    Cancelled(String),
+    /// Busca interrompida por exceder o tempo limite
+    Timeout(String),
+    /// Erros sem categoria específica (E/S, tarefas internas)
+    Internal(String),
+}
+
+impl CommandError {
+    /// Código estável usado pelo frontend
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Database(_) => "database",
+            Self::Search(_) => "search",
+            Self::Agent(_) => "agent",
+            Self::Permission(_) => "permission",
+            Self::Validation(_) => "validation",
+            Self::Unavailable(_) => "unavailable",
+            Self::Cancelled(_) => "cancelled",
+            Self::Timeout(_) => "timeout",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Database(message)
+            | Self::Search(message)
+            | Self::Agent(message)
+            | Self::Permission(message)
+            | Self::Validation(message)
+            | Self::Unavailable(message)
+            | Self::Cancelled(message)
+            | Self::Timeout(message)
+            | Self::Internal(message) => message,
+        }
+    }
+
+    /// Erro do agente, distinguindo falta de permissão das demais falhas
+    pub fn agent(context: &str, error: anyhow::Error) -> Self {
+        let message = format!("{}: {}", context, error);
+        if error.downcast_ref::<PermissionDenied>().is_some() {
+            Self::Permission(message)
+        } else {
+            Self::Agent(message)
+        }
+    }
+
+    /// Erro de busca, distinguindo a busca semântica indisponível das demais falhas
+    pub fn search(context: &str, error: anyhow::Error) -> Self {
+        let message = format!("{}: {}", context, error);
+        if error.downcast_ref::<SemanticUnavailable>().is_some() {
+            Self::Unavailable(message)
+        } else {
+            Self::Search(message)
+        }
+    }
+
+    /// Classifica um erro genérico pela causa e prefixa a mensagem com o contexto
+    pub fn classify(context: &str, error: anyhow::Error) -> Self {
+        Self::category_of(&error)(format!("{}: {}", context, error))
+    }
+
+    fn category_of(error: &anyhow::Error) -> fn(String) -> Self {
+        if error.downcast_ref::<PermissionDenied>().is_some() {
+            Self::Permission
+        } else if error.downcast_ref::<rusqlite::Error>().is_some()
+            || error.downcast_ref::<r2d2::Error>().is_some()
+        {
+            Self::Database
+        } else if error.downcast_ref::<serde_json::Error>().is_some()
+            || error.downcast_ref::<InvalidPassphrase>().is_some()
+        {
+            Self::Validation
+        } else {
+            Self::Internal
+        }
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CommandError", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", self.message())?;
+        state.end()
+    }
+}
+
+impl From<anyhow::Error> for CommandError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::category_of(&error)(error.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for CommandError {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::Database(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_code_and_message() {
+        let error = CommandError::Validation("Intervalo inválido".to_string());
+        let json = serde_json::to_value(&error).unwrap();
+
+        assert_eq!(json, serde_json::json!({ "code": "validation", "message": "Intervalo inválido" }));
+    }
+
+    #[test]
+    fn test_conversions_from_sources() {
+        let sqlite = CommandError::from(rusqlite::Error::QueryReturnedNoRows);
+        assert_eq!(sqlite.code(), "database");
+
+        let wrapped = CommandError::from(anyhow::Error::from(rusqlite::Error::InvalidQuery));
+        assert_eq!(wrapped.code(), "database");
+
+        let json = serde_json::from_str::<Vec<u8>>("{").unwrap_err();
+        assert_eq!(CommandError::classify("Erro ao importar dados", json.into()).code(), "validation");
+
+        assert_eq!(CommandError::from(anyhow::anyhow!("falha")).code(), "internal");
+    }
+
+    #[test]
+    fn test_search_semantic_unavailable() {
+        let error = CommandError::search("Erro na busca semântica", SemanticUnavailable.into());
+        assert_eq!(error.code(), "unavailable");
+
+        let error = CommandError::search("Erro na busca", anyhow::anyhow!("falha"));
+        assert_eq!(error.code(), "search");
+    }
+
+    #[test]
+    fn test_agent_permission_denied() {
+        let error = CommandError::agent("Erro ao iniciar agente", PermissionDenied.into());
+        assert_eq!(error.code(), "permission");
+        assert!(error.message().starts_with("Erro ao iniciar agente: Permissões insuficientes"));
+
+        let error = CommandError::agent("Erro ao parar agente", anyhow::anyhow!("canal fechado"));
+        assert_eq!(error.code(), "agent");
+    }
+}
@@ -1,19 +1,54 @@
 use tauri::State;
+use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
 use tracing::{info, error, debug, warn};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::AppState;
-use crate::search::{SearchOptions, HybridSearchResult};
-use crate::db::{SearchResult, DatabaseStats, Database, StoredEvent};
-use crate::agent::{AgentConfig, WindowInfo};
+use crate::search::{compile_search_regex, SearchOptions, HybridSearchResult, DEFAULT_MIN_QUERY_LEN, DEFAULT_POPULAR_WINDOW_SECS};
+use crate::db::{normalize_millis, BackupInfo, CompactionReport, SearchResult, DatabaseStats, Database, EmbeddingInfo, EventSummary, RegexMatch, StoredEvent, SessionSummary, ShortcutMatch};
+use crate::agent::{Agent, AgentConfig, PermissionStatus, WindowInfo};
+use crate::masker::{validate_pattern, Masker, MaskingResult, PatternValidation};
+use crate::profiles::{validate_profile_name, ProfileInfo};
+
+mod error;
+pub use error::CommandError;
+
+mod cancellation;
+pub use cancellation::{SearchLatency, SearchRegistry, SearchTimeouts};
+
+mod encryption;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResponse {
     pub results: Vec<SearchResult>,
+    /// Total de ocorrências da consulta, não só as desta página
     pub total_count: usize,
     pub search_time_ms: u64,
+    pub has_more: bool,
+    /// `offset` da próxima página, quando `has_more`
+    pub next_offset: Option<usize>,
+}
+
+impl SearchResponse {
+    fn empty() -> Self {
+        Self::page(Vec::new(), 0, 0, 0)
+    }
+
+    /// Página que começa em `offset` dentro de `total_count` ocorrências
+    fn page(results: Vec<SearchResult>, total_count: usize, offset: usize, search_time_ms: u64) -> Self {
+        let end = offset + results.len();
+        let has_more = !results.is_empty() && end < total_count;
+        Self {
+            results,
+            total_count,
+            search_time_ms,
+            has_more,
+            next_offset: has_more.then_some(end),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,48 +56,105 @@ pub struct HybridSearchResponse {
     pub results: Vec<HybridSearchResult>,
     pub total_count: usize,
     pub search_time_ms: u64,
+    /// `false` quando a parte semântica foi pulada por falta de modelo
+    pub semantic_available: bool,
+}
+
+impl HybridSearchResponse {
+    fn empty(semantic_available: bool) -> Self {
+        Self { results: Vec::new(), total_count: 0, search_time_ms: 0, semantic_available }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AgentStatus {
     pub is_running: bool,
+    pub is_paused: bool,
     pub uptime_seconds: u64,
     pub events_captured: u64,
 }
 
+impl AgentStatus {
+    /// Monta o status a partir do resumo de métricas do agente
+    pub fn from_metrics(is_running: bool, is_paused: bool, metrics: &HashMap<String, u64>) -> Self {
+        Self {
+            is_running,
+            is_paused,
+            uptime_seconds: metrics.get("uptime_seconds").copied().unwrap_or(0),
+            events_captured: metrics.get("events_captured").copied().unwrap_or(0),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppStats {
     pub database: DatabaseStats,
     pub agent: AgentStatus,
 }
 
+/// Resultado de `clear_data`/`delete_events`: o que foi (ou, em `preview`, seria) removido
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletionReport {
+    pub preview: bool,
+    #[serde(flatten)]
+    pub summary: EventSummary,
+}
+
 /// Busca textual simples
+// Each argument is a named field of the frontend's invoke() payload
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn search_text(
     state: State<'_, AppState>,
     query: String,
     limit: Option<usize>,
     offset: Option<usize>,
-) -> Result<SearchResponse, String> {
-    debug!("🔍 Comando search_text chamado: query='{}', limit={:?}, offset={:?}", 
-           query, limit, offset);
+    advanced: Option<bool>,
+    window_title_contains: Option<String>,
+    event_types: Option<Vec<String>>,
+    context_size: Option<usize>,
+    request_id: Option<String>,
+) -> Result<SearchResponse, CommandError> {
+    debug!("🔍 Comando search_text chamado: query='{}', limit={:?}, offset={:?}, advanced={:?}, window_title={:?}, event_types={:?}", 
+           query, limit, offset, advanced, window_title_contains, event_types);
     
     let start_time = std::time::Instant::now();
+    let options = SearchOptions {
+        limit: limit.unwrap_or(50),
+        offset: offset.unwrap_or(0),
+        advanced: advanced.unwrap_or(false),
+        window_title_contains: window_title_contains.filter(|title| !title.trim().is_empty()),
+        event_types: event_types.filter(|types| !types.is_empty()),
+        context_size: context_size.unwrap_or(0),
+        ..Default::default()
+    };
+    if !check_query(&query, DEFAULT_MIN_QUERY_LEN)? {
+        return Ok(SearchResponse::empty());
+    }
     
-    match state.database.search_text(&query, limit.unwrap_or(50)).await {
-        Ok(results) => {
+    let database = state.profile.database();
+    record_search(&database, &state.masker, &query).await;
+
+    let offset = options.offset;
+    let timeout = Duration::from_secs(state.searches.timeouts().text_secs);
+    let search = state.searches.run(request_id, timeout, async move {
+        let results = database.search_text_with_options(&query, &options).await
+            .map_err(|e| CommandError::Search(format!("Erro na busca: {}", e)))?;
+        let total_count = database.count_text_matches(&query, &options).await
+            .map_err(|e| CommandError::Search(format!("Erro ao contar resultados: {}", e)))?;
+        Ok((results, total_count))
+    });
+
+    match search.await {
+        Ok((results, total_count)) => {
             let search_time = start_time.elapsed().as_millis() as u64;
-            info!("✅ Busca textual concluída: {} resultados em {}ms", results.len(), search_time);
+            info!("✅ Busca textual concluída: {} de {} resultados em {}ms", results.len(), total_count, search_time);
             
-            Ok(SearchResponse {
-                total_count: results.len(),
-                results,
-                search_time_ms: search_time,
-            })
+            Ok(SearchResponse::page(results, total_count, offset, search_time))
         },
         Err(e) => {
             error!("❌ Erro na busca textual: {}", e);
-            Err(format!("Erro na busca: {}", e))
+            Err(e)
         }
     }
 }
@@ -74,7 +166,8 @@ pub async fn search_semantic(
     query: String,
     limit: Option<usize>,
     threshold: Option<f32>,
-) -> Result<HybridSearchResponse, String> {
+    request_id: Option<String>,
+) -> Result<HybridSearchResponse, CommandError> {
     debug!("🧠 Comando search_semantic chamado: query='{}', limit={:?}, threshold={:?}", 
            query, limit, threshold);
     
@@ -85,8 +178,20 @@ pub async fn search_semantic(
         min_score_threshold: threshold.unwrap_or(0.7) as f64,
         ..Default::default()
     };
+    if !check_query(&query, DEFAULT_MIN_QUERY_LEN)? {
+        return Ok(HybridSearchResponse::empty(state.profile.search_engine().semantic_available()));
+    }
     
-    match state.search_engine.search_semantic(&query, &options).await {
+    record_search(&state.profile.database(), &state.masker, &query).await;
+
+    let engine = state.profile.search_engine();
+    let timeout = Duration::from_secs(state.searches.timeouts().semantic_secs);
+    let search = state.searches.run(request_id, timeout, async move {
+        engine.search_semantic(&query, &options).await
+            .map_err(|e| CommandError::search("Erro na busca semântica", e))
+    });
+
+    match search.await {
         Ok(results) => {
             let search_time = start_time.elapsed().as_millis() as u64;
             info!("✅ Busca semântica concluída: {} resultados em {}ms", results.len(), search_time);
@@ -95,11 +200,12 @@ pub async fn search_semantic(
                 total_count: results.len(),
                 results,
                 search_time_ms: search_time,
+                semantic_available: true,
             })
         },
         Err(e) => {
             error!("❌ Erro na busca semântica: {}", e);
-            Err(format!("Erro na busca semântica: {}", e))
+            Err(e)
         }
     }
 }
@@ -110,12 +216,26 @@ pub async fn search_hybrid(
     state: State<'_, AppState>,
     query: String,
     options: SearchOptions,
-) -> Result<HybridSearchResponse, String> {
+    request_id: Option<String>,
+) -> Result<HybridSearchResponse, CommandError> {
     debug!("🔀 Comando search_hybrid chamado: query='{}', options={:?}", query, options);
     
     let start_time = std::time::Instant::now();
+    let engine = state.profile.search_engine();
+    let semantic_available = engine.semantic_available();
+    if !check_query(&query, DEFAULT_MIN_QUERY_LEN)? {
+        return Ok(HybridSearchResponse::empty(semantic_available));
+    }
     
-    match state.search_engine.search_hybrid(&query, &options).await {
+    record_search(&state.profile.database(), &state.masker, &query).await;
+
+    let timeout = Duration::from_secs(state.searches.timeouts().hybrid_secs);
+    let search = state.searches.run(request_id, timeout, async move {
+        engine.search_hybrid(&query, &options).await
+            .map_err(|e| CommandError::Search(format!("Erro na busca híbrida: {}", e)))
+    });
+
+    match search.await {
         Ok(results) => {
             let search_time = start_time.elapsed().as_millis() as u64;
             info!("✅ Busca híbrida concluída: {} resultados em {}ms", results.len(), search_time);
@@ -124,71 +244,269 @@ pub async fn search_hybrid(
                 total_count: results.len(),
                 results,
                 search_time_ms: search_time,
+                semantic_available,
             })
         },
         Err(e) => {
             error!("❌ Erro na busca híbrida: {}", e);
-            Err(format!("Erro na busca híbrida: {}", e))
+            Err(e)
+        }
+    }
+}
+
+/// Consulta vazia retorna `Ok(false)`: o comando responde sem resultados e
+/// sem tocar no banco. Consultas curtas demais são recusadas
+fn check_query(query: &str, min_len: usize) -> Result<bool, CommandError> {
+    let len = query.trim().chars().count();
+    if len == 0 {
+        debug!("🔍 Consulta vazia - nenhuma busca executada");
+        return Ok(false);
+    }
+    if len < min_len {
+        return Err(CommandError::Validation(format!(
+            "Consulta muito curta: informe ao menos {} caracteres", min_len
+        )));
+    }
+    Ok(true)
+}
+
+/// Busca por expressão regular no texto dos eventos. Como a varredura é
+/// linear, exige um intervalo de tempo ou uma aplicação
+#[tauri::command]
+pub async fn search_regex(
+    state: State<'_, AppState>,
+    pattern: String,
+    start_timestamp: Option<u64>,
+    end_timestamp: Option<u64>,
+    application: Option<String>,
+    limit: Option<usize>,
+    request_id: Option<String>,
+) -> Result<Vec<RegexMatch>, CommandError> {
+    debug!("🔣 Comando search_regex chamado: pattern='{}', {:?}..={:?}, application={:?}",
+           pattern, start_timestamp, end_timestamp, application);
+
+    let (start_timestamp, end_timestamp) = regex_search_range(start_timestamp, end_timestamp, application.as_deref())?;
+    let regex = compile_search_regex(&pattern).map_err(|e| CommandError::Validation(e.to_string()))?;
+    let limit = limit.unwrap_or(100);
+
+    let database = state.profile.database();
+    let timeout = Duration::from_secs(state.searches.timeouts().text_secs);
+    // O prazo interno encerra a varredura síncrona, que o abort da task não interrompe
+    let deadline = std::time::Instant::now() + timeout;
+    let search = state.searches.run(request_id, timeout, async move {
+        database.search_regex(&regex, start_timestamp, end_timestamp, application.as_deref(), limit, deadline).await
+            .map_err(|e| CommandError::Search(format!("Erro na busca por expressão regular: {}", e)))
+    });
+
+    match search.await {
+        Ok(results) => {
+            info!("✅ Busca por expressão regular concluída: {} resultados", results.len());
+            Ok(results)
+        },
+        Err(e) => {
+            error!("❌ Erro na busca por expressão regular: {}", e);
+            Err(e)
         }
     }
 }
 
+/// Cancela uma busca em andamento pelo `request_id` informado na chamada
+#[tauri::command]
+pub async fn cancel_search(
+    state: State<'_, AppState>,
+    request_id: String,
+) -> Result<bool, CommandError> {
+    debug!("🛑 Comando cancel_search chamado: request_id='{}'", request_id);
+
+    let cancelled = state.searches.cancel(&request_id);
+    if !cancelled {
+        debug!("🛑 Nenhuma busca em andamento com request_id '{}'", request_id);
+    }
+    Ok(cancelled)
+}
+
 /// Obtém estatísticas do banco de dados
 #[tauri::command]
 pub async fn get_database_stats(
     state: State<'_, AppState>
-) -> Result<DatabaseStats, String> {
+) -> Result<DatabaseStats, CommandError> {
     debug!("📊 Comando get_database_stats chamado");
     
-    match state.database.get_stats().await {
+    match state.profile.database().get_stats().await {
         Ok(stats) => {
             info!("✅ Estatísticas obtidas: {} eventos", stats.total_events);
             Ok(stats)
         },
         Err(e) => {
             error!("❌ Erro ao obter estatísticas: {}", e);
-            Err(format!("Erro ao obter estatísticas: {}", e))
+            Err(CommandError::Database(format!("Erro ao obter estatísticas: {}", e)))
         }
     }
 }
 
+/// Modelo de embeddings ativo, dimensão e quantos eventos já estão indexados
+#[tauri::command]
+pub async fn get_embedding_info(
+    state: State<'_, AppState>
+) -> Result<EmbeddingInfo, CommandError> {
+    debug!("🧠 Comando get_embedding_info chamado");
+
+    state.profile.search_engine().embedding_info().await.map_err(|e| {
+        error!("❌ Erro ao obter informações de embeddings: {}", e);
+        CommandError::Database(format!("Erro ao obter informações de embeddings: {}", e))
+    })
+}
+
 /// Obtém sugestões de busca
 #[tauri::command]
 pub async fn get_search_suggestions(
     state: State<'_, AppState>,
     partial_query: String,
     limit: Option<usize>,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<String>, CommandError> {
     debug!("💡 Comando get_search_suggestions chamado: query='{}', limit={:?}", 
            partial_query, limit);
+
+    if partial_query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
     
-    match state.search_engine.get_search_suggestions(&partial_query, limit.unwrap_or(10)).await {
+    match state.profile.search_engine().get_search_suggestions(&partial_query, limit.unwrap_or(10)).await {
         Ok(suggestions) => {
             info!("✅ {} sugestões geradas", suggestions.len());
             Ok(suggestions)
         },
         Err(e) => {
             error!("❌ Erro ao gerar sugestões: {}", e);
-            Err(format!("Erro ao gerar sugestões: {}", e))
+            Err(CommandError::Search(format!("Erro ao gerar sugestões: {}", e)))
         }
     }
 }
 
-/// Otimiza os índices de busca
+/// Otimiza os índices de busca; `vacuum` também compacta o arquivo do banco
 #[tauri::command]
 pub async fn optimize_search_index(
-    state: State<'_, AppState>
-) -> Result<String, String> {
-    debug!("🔧 Comando optimize_search_index chamado");
+    state: State<'_, AppState>,
+    vacuum: Option<bool>
+) -> Result<String, CommandError> {
+    debug!("🔧 Comando optimize_search_index chamado (vacuum: {:?})", vacuum);
     
-    match state.search_engine.optimize_search_index().await {
+    match state.profile.search_engine().optimize_search_index(vacuum.unwrap_or(false)).await {
         Ok(_) => {
             info!("✅ Índices de busca otimizados");
             Ok("Índices de busca otimizados com sucesso".to_string())
         },
         Err(e) => {
             error!("❌ Erro ao otimizar índices: {}", e);
-            Err(format!("Erro ao otimizar índices: {}", e))
+            Err(CommandError::Search(format!("Erro ao otimizar índices: {}", e)))
+        }
+    }
+}
+
+/// Reconstrói o índice de busca textual a partir dos eventos gravados
+#[tauri::command]
+pub async fn rebuild_search_index(
+    state: State<'_, AppState>
+) -> Result<String, CommandError> {
+    debug!("🔧 Comando rebuild_search_index chamado");
+
+    match state.profile.database().rebuild_fts_index().await {
+        Ok(_) => {
+            info!("✅ Índice de busca reconstruído");
+            Ok("Índice de busca reconstruído com sucesso".to_string())
+        },
+        Err(e) => {
+            error!("❌ Erro ao reconstruir índice: {}", e);
+            Err(CommandError::Database(format!("Erro ao reconstruir índice: {}", e)))
+        }
+    }
+}
+
+/// Cria um backup consistente (e criptografado, se houver chave) do banco de dados
+#[tauri::command]
+pub async fn backup_database(
+    path: String,
+    state: State<'_, AppState>
+) -> Result<String, CommandError> {
+    debug!("💾 Comando backup_database chamado: path='{}'", path);
+
+    match state.profile.database().backup_to(&path).await {
+        Ok(_) => {
+            info!("✅ Backup criado em {}", path);
+            Ok(format!("Backup criado em {}", path))
+        },
+        Err(e) => {
+            error!("❌ Erro ao criar backup: {}", e);
+            Err(CommandError::Database(format!("Erro ao criar backup: {}", e)))
+        }
+    }
+}
+
+/// Lista os backups agendados da pasta `backup_dir`, do mais recente ao mais antigo
+#[tauri::command]
+pub async fn list_backups(
+    state: State<'_, AppState>
+) -> Result<Vec<BackupInfo>, CommandError> {
+    debug!("💾 Comando list_backups chamado");
+
+    let Some(dir) = state.agent.lock().await.get_config().await.backup_dir else {
+        return Ok(Vec::new());
+    };
+
+    crate::db::list_backups(&dir).map_err(|e| {
+        error!("❌ Erro ao listar backups: {}", e);
+        CommandError::Internal(format!("Erro ao listar backups: {}", e))
+    })
+}
+
+/// Restaura um dos backups de `backup_dir`, substituindo todos os dados atuais.
+/// O agente é parado durante a restauração e religado em seguida se estava ativo
+#[tauri::command]
+pub async fn restore_backup(
+    path: String,
+    confirm: bool,
+    state: State<'_, AppState>
+) -> Result<String, CommandError> {
+    debug!("♻️ Comando restore_backup chamado: path='{}', confirm={}", path, confirm);
+
+    require_confirmation(confirm, "Confirmação necessária para restaurar backup")?;
+
+    // Holding the lock keeps the agent from being restarted mid-restore
+    let mut agent = state.agent.lock().await;
+    let backups = match agent.get_config().await.backup_dir {
+        Some(dir) => crate::db::list_backups(&dir)
+            .map_err(|e| CommandError::Internal(format!("Erro ao listar backups: {}", e)))?,
+        None => Vec::new(),
+    };
+    if !backups.iter().any(|backup| backup.path == path) {
+        return Err(CommandError::Validation(format!("Backup não encontrado: {}", path)));
+    }
+
+    let was_running = agent.is_running();
+    if was_running {
+        if let Err(e) = agent.stop().await {
+            error!("❌ Erro ao parar agente antes de restaurar backup: {}", e);
+            return Err(CommandError::agent("Erro ao parar agente", e));
+        }
+    }
+
+    let restored = state.profile.database().restore_from(&path).await;
+
+    if was_running {
+        if let Err(e) = agent.start().await {
+            error!("❌ Erro ao religar agente após restaurar backup: {}", e);
+            return Err(CommandError::agent("Erro ao iniciar agente", e));
+        }
+    }
+
+    match restored {
+        Ok(()) => {
+            info!("✅ Backup restaurado de {}", path);
+            Ok(format!("Backup restaurado de {}", path))
+        },
+        Err(e) => {
+            error!("❌ Erro ao restaurar backup: {}", e);
+            Err(CommandError::classify("Erro ao restaurar backup", e))
         }
     }
 }
@@ -198,7 +516,7 @@ pub async fn optimize_search_index(
 pub async fn toggle_agent(
     enable: bool,
     state: State<'_, AppState>
-) -> Result<AgentStatus, String> {
+) -> Result<AgentStatus, CommandError> {
     debug!("🎛️ Comando toggle_agent chamado: enable={}", enable);
     
     let mut agent = state.agent.lock().await;
@@ -211,7 +529,7 @@ pub async fn toggle_agent(
                 },
                 Err(e) => {
                     error!("❌ Erro ao iniciar agente: {}", e);
-                    return Err(format!("Erro ao iniciar agente: {}", e));
+                    return Err(CommandError::agent("Erro ao iniciar agente", e));
                 }
             }
         }
@@ -223,39 +541,60 @@ pub async fn toggle_agent(
                 },
                 Err(e) => {
                     error!("❌ Erro ao parar agente: {}", e);
-                    return Err(format!("Erro ao parar agente: {}", e));
+                    return Err(CommandError::agent("Erro ao parar agente", e));
                 }
             }
         }
     }
 
     // Return updated status
-    let metrics = agent.get_metrics();
-    let current_window = agent.get_current_window().await;
-    let config = agent.get_config().await;
-
-    Ok(AgentStatus {
-        is_running: agent.is_running(),
-        uptime_seconds: 0,
-        events_captured: 0,
-    })
+    Ok(AgentStatus::from_metrics(agent.is_running(), agent.is_paused(), &agent.get_metrics()))
 }
 
 /// Obtém o status atual do agente
 #[tauri::command]
 pub async fn get_agent_status(
     state: State<'_, AppState>
-) -> Result<AgentStatus, String> {
+) -> Result<AgentStatus, CommandError> {
     debug!("📊 Comando get_agent_status chamado");
     
     let agent = state.agent.lock().await;
-    let metrics = agent.get_metrics();
 
-    Ok(AgentStatus {
-        is_running: agent.is_running(),
-        uptime_seconds: metrics.get("uptime_seconds").copied().unwrap_or(0),
-        events_captured: metrics.get("events_captured").copied().unwrap_or(0),
-    })
+    Ok(AgentStatus::from_metrics(agent.is_running(), agent.is_paused(), &agent.get_metrics()))
+}
+
+/// Pausa a captura sem encerrar o listener de teclas
+#[tauri::command]
+pub async fn pause_capture(
+    state: State<'_, AppState>
+) -> Result<AgentStatus, CommandError> {
+    debug!("⏸️ Comando pause_capture chamado");
+
+    let agent = state.agent.lock().await;
+
+    if let Err(e) = agent.pause() {
+        error!("❌ Erro ao pausar captura: {}", e);
+        return Err(CommandError::agent("Erro ao pausar captura", e));
+    }
+
+    Ok(AgentStatus::from_metrics(agent.is_running(), agent.is_paused(), &agent.get_metrics()))
+}
+
+/// Retoma a captura pausada
+#[tauri::command]
+pub async fn resume_capture(
+    state: State<'_, AppState>
+) -> Result<AgentStatus, CommandError> {
+    debug!("▶️ Comando resume_capture chamado");
+
+    let agent = state.agent.lock().await;
+
+    if let Err(e) = agent.resume() {
+        error!("❌ Erro ao retomar captura: {}", e);
+        return Err(CommandError::agent("Erro ao retomar captura", e));
+    }
+
+    Ok(AgentStatus::from_metrics(agent.is_running(), agent.is_paused(), &agent.get_metrics()))
 }
 
 /// Atualiza a configuração do agente
@@ -263,7 +602,7 @@ pub async fn get_agent_status(
 pub async fn update_agent_config(
     config: AgentConfig,
     state: State<'_, AppState>
-) -> Result<AgentStatus, String> {
+) -> Result<AgentStatus, CommandError> {
     debug!("🔧 Comando update_agent_config chamado");
     
     let agent = state.agent.lock().await;
@@ -271,18 +610,12 @@ pub async fn update_agent_config(
     match agent.update_config(config).await {
         Ok(_) => {
             info!("✅ Configuração do agente atualizada");
-            
-            let metrics = agent.get_metrics();
 
-            Ok(AgentStatus {
-                is_running: agent.is_running(),
-                uptime_seconds: metrics.get("uptime_seconds").copied().unwrap_or(0),
-                events_captured: metrics.get("events_captured").copied().unwrap_or(0),
-            })
+            Ok(AgentStatus::from_metrics(agent.is_running(), agent.is_paused(), &agent.get_metrics()))
         },
         Err(e) => {
             error!("❌ Erro ao atualizar configuração: {}", e);
-            Err(format!("Erro ao atualizar configuração: {}", e))
+            Err(CommandError::Validation(format!("Erro ao atualizar configuração: {}", e)))
         }
     }
 }
@@ -291,7 +624,7 @@ pub async fn update_agent_config(
 #[tauri::command]
 pub async fn get_agent_config(
     state: State<'_, AppState>
-) -> Result<AgentConfig, String> {
+) -> Result<AgentConfig, CommandError> {
     debug!("⚙️ Comando get_agent_config chamado");
     
     let agent = state.agent.lock().await;
@@ -302,7 +635,7 @@ pub async fn get_agent_config(
 #[tauri::command]
 pub async fn get_current_window(
     state: State<'_, AppState>
-) -> Result<Option<WindowInfo>, String> {
+) -> Result<Option<WindowInfo>, CommandError> {
     debug!("🪟 Comando get_current_window chamado");
     
     let agent = state.agent.lock().await;
@@ -313,36 +646,243 @@ pub async fn get_current_window(
 #[tauri::command]
 pub async fn get_agent_metrics(
     state: State<'_, AppState>
-) -> Result<HashMap<String, u64>, String> {
+) -> Result<HashMap<String, u64>, CommandError> {
     debug!("📈 Comando get_agent_metrics chamado");
     
     let agent = state.agent.lock().await;
     Ok(agent.get_metrics())
 }
 
+/// Informa quais permissões de captura estão disponíveis nesta plataforma
+#[tauri::command]
+pub async fn get_permission_status() -> Result<PermissionStatus, CommandError> {
+    debug!("🔐 Comando get_permission_status chamado");
+
+    // No macOS a verificação sonda o rdev e bloqueia brevemente
+    tokio::task::spawn_blocking(Agent::check_permissions)
+        .await
+        .map_err(|e| {
+            error!("❌ Erro ao verificar permissões: {}", e);
+            CommandError::Internal(format!("Erro ao verificar permissões: {}", e))
+        })
+}
+
+/// Adiciona (ou substitui) um padrão customizado de mascaramento
+#[tauri::command]
+pub async fn add_masker_pattern(
+    name: String,
+    pattern: String,
+    state: State<'_, AppState>
+) -> Result<Vec<String>, CommandError> {
+    debug!("🔒 Comando add_masker_pattern chamado: name='{}'", name);
+
+    if name.trim().is_empty() {
+        return Err(CommandError::Validation("Nome do padrão não pode ser vazio".to_string()));
+    }
+    // Same blocking scan as validate_masker_pattern, kept off the async runtime
+    let (checked_name, checked_pattern) = (name.clone(), pattern.clone());
+    tokio::task::spawn_blocking(move || check_pattern(&checked_name, &checked_pattern))
+        .await
+        .map_err(|e| CommandError::Internal(format!("Erro ao validar padrão: {}", e)))??;
+
+    let mut masker = state.masker.write().await;
+    if let Err(e) = masker.add_custom_pattern(name.clone(), pattern.clone()) {
+        warn!("⚠️ Padrão de mascaramento inválido '{}': {}", name, e);
+        return Err(CommandError::Validation(format!("Regex inválida para o padrão '{}': {}", name, e)));
+    }
+
+    info!("✅ Padrão de mascaramento '{}' adicionado", name);
+    Ok(sorted_patterns(&masker))
+}
+
+/// Compila o padrão e estima o risco de ReDoS, sem adicioná-lo ao masker
+#[tauri::command]
+pub async fn validate_masker_pattern(pattern: String) -> Result<PatternValidation, CommandError> {
+    debug!("🔒 Comando validate_masker_pattern chamado");
+
+    // A varredura dos textos de teste bloqueia por até alguns centésimos de segundo
+    tokio::task::spawn_blocking(move || validate_pattern(&pattern))
+        .await
+        .map_err(|e| CommandError::Internal(format!("Erro ao validar padrão: {}", e)))
+}
+
+fn check_pattern(name: &str, pattern: &str) -> Result<(), CommandError> {
+    let validation = validate_pattern(pattern);
+    if validation.valid {
+        return Ok(());
+    }
+
+    warn!("⚠️ Padrão de mascaramento recusado '{}': {:?}", name, validation);
+    Err(CommandError::Validation(match validation.compile_error {
+        Some(e) => format!("Regex inválida para o padrão '{}': {}", name, e),
+        None => format!("Regex do padrão '{}' tem risco de deixar o mascaramento lento", name),
+    }))
+}
+
+/// Remove um padrão de mascaramento pelo nome
+#[tauri::command]
+pub async fn remove_masker_pattern(
+    name: String,
+    state: State<'_, AppState>
+) -> Result<Vec<String>, CommandError> {
+    debug!("🔒 Comando remove_masker_pattern chamado: name='{}'", name);
+
+    let mut masker = state.masker.write().await;
+    if !masker.remove_pattern(&name) {
+        return Err(CommandError::Validation(format!("Padrão de mascaramento '{}' não encontrado", name)));
+    }
+
+    info!("🗑️ Padrão de mascaramento '{}' removido", name);
+    Ok(sorted_patterns(&masker))
+}
+
+/// Lista os nomes dos padrões de mascaramento ativos
+#[tauri::command]
+pub async fn list_masker_patterns(
+    state: State<'_, AppState>
+) -> Result<Vec<String>, CommandError> {
+    debug!("🔒 Comando list_masker_patterns chamado");
+
+    let masker = state.masker.read().await;
+    Ok(sorted_patterns(&masker))
+}
+
+/// Mostra o efeito do mascaramento num texto de exemplo, sem gravar nada.
+/// Com `pattern_name`, aplica só esse padrão
+#[tauri::command]
+pub async fn preview_masking(
+    text: String,
+    pattern_name: Option<String>,
+    state: State<'_, AppState>
+) -> Result<MaskingResult, CommandError> {
+    debug!("🔒 Comando preview_masking chamado: pattern={:?}", pattern_name);
+
+    let masker = state.masker.read().await;
+    preview_masking_with(&masker, &text, pattern_name.as_deref())
+}
+
+fn preview_masking_with(masker: &Masker, text: &str, pattern_name: Option<&str>) -> Result<MaskingResult, CommandError> {
+    match pattern_name {
+        Some(name) => masker.mask_text_with_pattern(text, name).ok_or_else(|| {
+            CommandError::Validation(format!("Padrão de mascaramento '{}' não encontrado", name))
+        }),
+        None => Ok(masker.mask_text_detailed(text)),
+    }
+}
+
+/// Mascara um texto qualquer (ex.: antes de exibi-lo) com os mesmos padrões do agente
+#[tauri::command]
+pub async fn mask_text(
+    text: String,
+    state: State<'_, AppState>
+) -> Result<MaskingResult, CommandError> {
+    debug!("🔒 Comando mask_text chamado ({} caracteres)", text.chars().count());
+
+    Ok(mask_text_with(&state.masker, &text).await)
+}
+
+async fn mask_text_with(masker: &RwLock<Masker>, text: &str) -> MaskingResult {
+    masker.read().await.mask_text_detailed(text)
+}
+
+/// Lista as palavras-chave de janela que ativam o mascaramento reforçado
+#[tauri::command]
+pub async fn get_sensitive_contexts(
+    state: State<'_, AppState>
+) -> Result<Vec<String>, CommandError> {
+    debug!("🔒 Comando get_sensitive_contexts chamado");
+
+    let masker = state.masker.read().await;
+    Ok(masker.sensitive_contexts().to_vec())
+}
+
+/// Substitui as palavras-chave de janela que ativam o mascaramento reforçado
+#[tauri::command]
+pub async fn set_sensitive_contexts(
+    keywords: Vec<String>,
+    state: State<'_, AppState>
+) -> Result<Vec<String>, CommandError> {
+    debug!("🔒 Comando set_sensitive_contexts chamado: {} palavras-chave", keywords.len());
+
+    let mut masker = state.masker.write().await;
+    masker.set_sensitive_contexts(keywords);
+
+    info!("✅ Contextos sensíveis atualizados");
+    Ok(masker.sensitive_contexts().to_vec())
+}
+
+/// Operações destrutivas exigem confirmação explícita da interface
+fn require_confirmation(confirm: bool, message: &str) -> Result<(), CommandError> {
+    if confirm {
+        Ok(())
+    } else {
+        Err(CommandError::Validation(message.to_string()))
+    }
+}
+
+fn validate_time_range(start_timestamp: u64, end_timestamp: u64) -> Result<(), CommandError> {
+    if start_timestamp > end_timestamp {
+        return Err(CommandError::Validation("Intervalo inválido: início maior que o fim".to_string()));
+    }
+    Ok(())
+}
+
+/// Intervalo da busca por expressão regular: sem aplicação, início e fim são obrigatórios
+fn regex_search_range(
+    start_timestamp: Option<u64>,
+    end_timestamp: Option<u64>,
+    application: Option<&str>,
+) -> Result<(u64, u64), CommandError> {
+    let has_application = application.map(|app| !app.trim().is_empty()).unwrap_or(false);
+    match (start_timestamp, end_timestamp) {
+        (Some(start), Some(end)) => {
+            validate_time_range(start, end)?;
+            Ok((start, end))
+        }
+        (start, end) if has_application => Ok((start.unwrap_or(0), end.unwrap_or(u64::MAX))),
+        _ => Err(CommandError::Validation(
+            "Busca por expressão regular exige um intervalo de tempo ou uma aplicação".to_string()
+        )),
+    }
+}
+
+/// Senha da exportação; sem ela, só prossegue com `allow_plaintext`
+fn validate_export_passphrase(passphrase: Option<String>, allow_plaintext: bool) -> Result<Option<String>, CommandError> {
+    match passphrase {
+        Some(passphrase) if passphrase.is_empty() => {
+            Err(CommandError::Validation("Senha de exportação vazia".to_string()))
+        }
+        Some(passphrase) => Ok(Some(passphrase)),
+        None if allow_plaintext => Ok(None),
+        None => Err(CommandError::Validation(
+            "Informe uma senha ou confirme a exportação sem criptografia (allow_plaintext)".to_string()
+        )),
+    }
+}
+
+fn sorted_patterns(masker: &Masker) -> Vec<String> {
+    let mut patterns = masker.list_patterns();
+    patterns.sort();
+    patterns
+}
+
 /// Obtém estatísticas gerais da aplicação
 #[tauri::command]
 pub async fn get_stats(
     state: State<'_, AppState>
-) -> Result<AppStats, String> {
+) -> Result<AppStats, CommandError> {
     debug!("📊 Comando get_stats chamado");
     
-    let db_stats = match state.database.get_stats().await {
+    let db_stats = match state.profile.database().get_stats().await {
         Ok(stats) => stats,
         Err(e) => {
             error!("❌ Erro ao obter estatísticas do banco: {}", e);
-            return Err(format!("Erro ao obter estatísticas: {}", e));
+            return Err(CommandError::Database(format!("Erro ao obter estatísticas: {}", e)));
         }
     };
 
     let agent = state.agent.lock().await;
-    let metrics = agent.get_metrics();
-
-    let agent_status = AgentStatus {
-        is_running: agent.is_running(),
-        uptime_seconds: metrics.get("uptime_seconds").copied().unwrap_or(0),
-        events_captured: metrics.get("events_captured").copied().unwrap_or(0),
-    };
+    let agent_status = AgentStatus::from_metrics(agent.is_running(), agent.is_paused(), &agent.get_metrics());
 
     Ok(AppStats {
         database: db_stats,
@@ -350,16 +890,27 @@ pub async fn get_stats(
     })
 }
 
-/// Limpa todos os dados armazenados
+/// Limpa todos os dados armazenados. Com `preview`, apenas informa o que seria removido
 #[tauri::command]
 pub async fn clear_data(
     confirm: bool,
+    preview: Option<bool>,
     state: State<'_, AppState>
-) -> Result<String, String> {
-    debug!("🗑️ Comando clear_data chamado: confirm={}", confirm);
+) -> Result<DeletionReport, CommandError> {
+    debug!("🗑️ Comando clear_data chamado: confirm={}, preview={:?}", confirm, preview);
     
-    if !confirm {
-        return Err("Confirmação necessária para limpar dados".to_string());
+    let preview = preview.unwrap_or(false);
+    if !preview {
+        require_confirmation(confirm, "Confirmação necessária para limpar dados")?;
+    }
+
+    let database = state.profile.database();
+    let summary = database.preview_delete_events(0, u64::MAX, None).await.map_err(|e| {
+        error!("❌ Erro ao resumir dados: {}", e);
+        CommandError::Database(format!("Erro ao resumir dados: {}", e))
+    })?;
+    if preview {
+        return Ok(DeletionReport { preview: true, summary });
     }
 
     // Stop agent if running
@@ -367,83 +918,224 @@ pub async fn clear_data(
     if agent.is_running() {
         if let Err(e) = agent.stop().await {
             error!("❌ Erro ao parar agente antes de limpar dados: {}", e);
-            return Err(format!("Erro ao parar agente: {}", e));
+            return Err(CommandError::agent("Erro ao parar agente", e));
         }
     }
     drop(agent);
 
-    match state.database.clear_all_data().await {
+    match database.clear_all_data().await {
         Ok(_) => {
             info!("✅ Todos os dados foram limpos");
-            Ok("Dados limpos com sucesso".to_string())
+            Ok(DeletionReport { preview: false, summary })
         },
         Err(e) => {
             error!("❌ Erro ao limpar dados: {}", e);
-            Err(format!("Erro ao limpar dados: {}", e))
+            Err(CommandError::Database(format!("Erro ao limpar dados: {}", e)))
         }
     }
 }
 
+/// Remove eventos de um intervalo de tempo, opcionalmente de uma única aplicação.
+/// Com `preview`, apenas informa o que seria removido
+#[tauri::command]
+pub async fn delete_events(
+    start_timestamp: u64,
+    end_timestamp: u64,
+    application: Option<String>,
+    confirm: bool,
+    preview: Option<bool>,
+    state: State<'_, AppState>
+) -> Result<DeletionReport, CommandError> {
+    debug!("🗑️ Comando delete_events chamado: {}..={}, application={:?}, confirm={}, preview={:?}",
+           start_timestamp, end_timestamp, application, confirm, preview);
+
+    validate_time_range(start_timestamp, end_timestamp)?;
+
+    let preview = preview.unwrap_or(false);
+    if !preview {
+        require_confirmation(confirm, "Confirmação necessária para remover eventos")?;
+    }
+
+    let database = state.profile.database();
+    let mut summary = database
+        .preview_delete_events(start_timestamp, end_timestamp, application.as_deref())
+        .await
+        .map_err(|e| {
+            error!("❌ Erro ao resumir eventos: {}", e);
+            CommandError::Database(format!("Erro ao resumir eventos: {}", e))
+        })?;
+    if preview {
+        return Ok(DeletionReport { preview: true, summary });
+    }
+
+    match database.delete_events(start_timestamp, end_timestamp, application.as_deref()).await {
+        Ok(deleted) => {
+            info!("✅ {} eventos removidos", deleted);
+            summary.event_count = deleted as i64;
+            Ok(DeletionReport { preview: false, summary })
+        },
+        Err(e) => {
+            error!("❌ Erro ao remover eventos: {}", e);
+            Err(CommandError::Database(format!("Erro ao remover eventos: {}", e)))
+        }
+    }
+}
+
+/// Junta as teclas gravadas uma a uma em segmentos de texto pesquisáveis,
+/// mascarando o texto remontado. Com `preview`, apenas informa o que mudaria
+#[tauri::command]
+pub async fn compact_history(
+    confirm: bool,
+    preview: Option<bool>,
+    state: State<'_, AppState>
+) -> Result<CompactionReport, CommandError> {
+    debug!("🧱 Comando compact_history chamado: confirm={}, preview={:?}", confirm, preview);
+
+    let preview = preview.unwrap_or(false);
+    if !preview {
+        require_confirmation(confirm, "Confirmação necessária para compactar o histórico")?;
+    }
+
+    let masker = state.masker.read().await.clone();
+    let mask = |text: &str| masker.mask_text(text);
+    state.profile.database().compact_keystrokes_into_segments(!preview, &mask).await.map_err(|e| {
+        error!("❌ Erro ao compactar histórico: {}", e);
+        CommandError::Database(format!("Erro ao compactar histórico: {}", e))
+    })
+}
+
+/// Busca ocorrências de um atalho de teclado (ex.: Ctrl+S)
+#[tauri::command]
+pub async fn search_shortcuts(
+    state: State<'_, AppState>,
+    modifiers: Vec<String>,
+    key: String,
+    limit: Option<usize>,
+) -> Result<Vec<ShortcutMatch>, CommandError> {
+    debug!("⌨️ Comando search_shortcuts chamado: {:?}+{}", modifiers, key);
+
+    if key.trim().is_empty() {
+        return Err(CommandError::Validation("Tecla do atalho não informada".to_string()));
+    }
+
+    state.profile.database().search_shortcuts(&modifiers, &key, limit.unwrap_or(50)).await.map_err(|e| {
+        error!("❌ Erro na busca por atalho: {}", e);
+        CommandError::Database(format!("Erro na busca por atalho: {}", e))
+    })
+}
+
+/// Eventos gravados após `last_id` (o maior id já recebido), para o feed
+/// incremental do frontend. Sem `last_id`, começa do primeiro evento
+#[tauri::command]
+pub async fn poll_new_events(
+    state: State<'_, AppState>,
+    last_id: Option<i64>,
+    limit: Option<usize>,
+) -> Result<Vec<StoredEvent>, CommandError> {
+    let last_id = last_id.unwrap_or(0);
+    debug!("🆕 Comando poll_new_events chamado: last_id={}, limit={:?}", last_id, limit);
+
+    let limit = limit.unwrap_or(500).clamp(1, 5000);
+    state.profile.database().events_since(last_id, limit).await.map_err(|e| {
+        error!("❌ Erro ao buscar eventos novos: {}", e);
+        CommandError::Database(format!("Erro ao buscar eventos novos: {}", e))
+    })
+}
+
+/// Lista as sessões de digitação registradas
+#[tauri::command]
+pub async fn list_sessions(
+    state: State<'_, AppState>
+) -> Result<Vec<SessionSummary>, CommandError> {
+    debug!("🗂️ Comando list_sessions chamado");
+
+    state.profile.database().list_sessions().await.map_err(|e| {
+        error!("❌ Erro ao listar sessões: {}", e);
+        CommandError::Database(format!("Erro ao listar sessões: {}", e))
+    })
+}
+
 /// Obtém as buscas mais populares
 #[tauri::command]
 pub async fn get_popular_searches(
     limit: Option<usize>,
+    window_secs: Option<u64>,
     state: State<'_, AppState>
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<String>, CommandError> {
     debug!("🔥 Comando get_popular_searches chamado");
     
-    match state.search_engine.get_popular_searches(limit.unwrap_or(10)).await {
+    let window_secs = window_secs.unwrap_or(DEFAULT_POPULAR_WINDOW_SECS);
+    match state.profile.search_engine().get_popular_searches(limit.unwrap_or(10), window_secs).await {
         Ok(searches) => {
             debug!("✅ {} buscas populares encontradas", searches.len());
             Ok(searches)
         },
         Err(e) => {
             error!("❌ Erro ao obter buscas populares: {}", e);
-            Err(format!("Erro ao obter buscas populares: {}", e))
+            Err(CommandError::Search(format!("Erro ao obter buscas populares: {}", e)))
         }
     }
 }
 
-/// Exporta dados para arquivo JSON
+/// Limpa o histórico de buscas usado nas buscas populares
+#[tauri::command]
+pub async fn clear_search_log(
+    state: State<'_, AppState>
+) -> Result<usize, CommandError> {
+    debug!("🗑️ Comando clear_search_log chamado");
+
+    state.profile.database().clear_search_log().await.map_err(|e| {
+        error!("❌ Erro ao limpar log de buscas: {}", e);
+        CommandError::Database(format!("Erro ao limpar log de buscas: {}", e))
+    })
+}
+
+/// Exporta dados para arquivo JSON, cifrado com `passphrase`. Exportar em
+/// texto puro exige `allow_plaintext`
 #[tauri::command]
 pub async fn export_data(
     file_path: String,
     date_from: Option<String>,
     date_to: Option<String>,
+    passphrase: Option<String>,
+    allow_plaintext: Option<bool>,
     state: State<'_, AppState>
-) -> Result<String, String> {
-    debug!("📤 Comando export_data chamado: path='{}'", file_path);
+) -> Result<String, CommandError> {
+    debug!("📤 Comando export_data chamado: path='{}', cifrado={}", file_path, passphrase.is_some());
+
+    let passphrase = validate_export_passphrase(passphrase, allow_plaintext.unwrap_or(false))?;
     
     // Implementação básica de exportação
-    match export_data_to_file(&state.database, &file_path, date_from, date_to).await {
+    match export_data_to_file(&state.profile.database(), &file_path, date_from, date_to, passphrase.as_deref()).await {
         Ok(count) => {
             info!("✅ {} eventos exportados para {}", count, file_path);
             Ok(format!("{} eventos exportados com sucesso", count))
         },
         Err(e) => {
             error!("❌ Erro ao exportar dados: {}", e);
-            Err(format!("Erro ao exportar dados: {}", e))
+            Err(CommandError::classify("Erro ao exportar dados", e))
         }
     }
 }
 
-/// Importa dados de arquivo JSON
+/// Importa dados de arquivo JSON; arquivos cifrados exigem a `passphrase` da exportação
 #[tauri::command]
 pub async fn import_data(
     file_path: String,
+    passphrase: Option<String>,
     state: State<'_, AppState>
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
     debug!("📥 Comando import_data chamado: path='{}'", file_path);
     
     // Implementação básica de importação
-    match import_data_from_file(&state.database, &file_path).await {
+    match import_data_from_file(&state.profile.database(), &file_path, passphrase.as_deref()).await {
         Ok(count) => {
             info!("✅ {} eventos importados de {}", count, file_path);
             Ok(format!("{} eventos importados com sucesso", count))
         },
         Err(e) => {
             error!("❌ Erro ao importar dados: {}", e);
-            Err(format!("Erro ao importar dados: {}", e))
+            Err(CommandError::classify("Erro ao importar dados", e))
         }
     }
 }
@@ -452,13 +1144,13 @@ pub async fn import_data(
 #[tauri::command]
 pub async fn health_check(
     state: State<'_, AppState>
-) -> Result<HashMap<String, String>, String> {
+) -> Result<HashMap<String, String>, CommandError> {
     debug!("🏥 Comando health_check chamado");
     
     let mut status = HashMap::new();
     
     // Test database
-    match state.database.get_stats().await {
+    match state.profile.database().get_stats().await {
         Ok(_) => status.insert("database".to_string(), "ok".to_string()),
         Err(e) => status.insert("database".to_string(), format!("error: {}", e)),
     };
@@ -466,15 +1158,70 @@ pub async fn health_check(
     // Test search engine - basic check
     status.insert("search_engine".to_string(), "ok".to_string());
     
-    // Test agent
+    // Test agent and whether the capture thread is still listening
     let agent = state.agent.lock().await;
-    let agent_status = if agent.is_running() { "running" } else { "stopped" };
-    status.insert("agent".to_string(), agent_status.to_string());
+    status.extend(agent.health());
     
     info!("✅ Health check concluído: {:?}", status);
     Ok(status)
 }
 
+/// Diretório dos arquivos de log, para o usuário anexar ao reportar problemas
+#[tauri::command]
+pub async fn get_log_path() -> Result<String, CommandError> {
+    debug!("📝 Comando get_log_path chamado");
+    Ok(crate::logging::log_dir().to_string_lossy().to_string())
+}
+
+/// Lista os perfis de captura, indicando o ativo
+#[tauri::command]
+pub async fn list_profiles(
+    state: State<'_, AppState>
+) -> Result<Vec<ProfileInfo>, CommandError> {
+    debug!("👤 Comando list_profiles chamado");
+
+    state.profile.list().map_err(|e| {
+        error!("❌ Erro ao listar perfis: {}", e);
+        CommandError::Internal(format!("Erro ao listar perfis: {}", e))
+    })
+}
+
+/// Troca o perfil ativo, abrindo `keyai-<nome>.db` com a chave informada.
+/// O agente é parado durante a troca e religado se estava ativo
+#[tauri::command]
+pub async fn switch_profile(
+    name: String,
+    key: Option<String>,
+    state: State<'_, AppState>
+) -> Result<Vec<ProfileInfo>, CommandError> {
+    debug!("👤 Comando switch_profile chamado: name='{}'", name);
+
+    validate_profile_name(&name).map_err(|e| CommandError::Validation(e.to_string()))?;
+
+    let mut agent = state.agent.lock().await;
+    if let Err(e) = state.profile.switch(&mut agent, &name, key.as_deref()).await {
+        error!("❌ Erro ao trocar para o perfil '{}': {}", name, e);
+        return Err(CommandError::classify("Erro ao trocar de perfil", e));
+    }
+    drop(agent);
+
+    info!("✅ Perfil '{}' ativado", name);
+    list_profiles(state).await
+}
+
+/// Registra a consulta no log de buscas, mascarando PII antes de persistir
+async fn record_search(database: &Database, masker: &RwLock<Masker>, query: &str) {
+    let masked_query = masker.read().await.mask_text(query);
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    if let Err(e) = database.log_search(&masked_query, timestamp).await {
+        warn!("⚠️ Falha ao registrar busca no histórico: {}", e);
+    }
+}
+
 // Helper functions for export/import
 
 async fn export_data_to_file(
@@ -482,6 +1229,7 @@ async fn export_data_to_file(
     file_path: &str,
     _date_from: Option<String>,
     _date_to: Option<String>,
+    passphrase: Option<&str>,
 ) -> Result<usize, anyhow::Error> {
     use std::fs::File;
     use std::io::Write;
@@ -491,10 +1239,14 @@ async fn export_data_to_file(
     
     // Convert to JSON
     let json_data = serde_json::to_string_pretty(&events)?;
+    let output = match passphrase {
+        Some(passphrase) => encryption::encrypt(json_data.as_bytes(), passphrase)?,
+        None => json_data.into_bytes(),
+    };
     
     // Write to file
     let mut file = File::create(file_path)?;
-    file.write_all(json_data.as_bytes())?;
+    file.write_all(&output)?;
     
     Ok(events.len())
 }
@@ -502,6 +1254,7 @@ async fn export_data_to_file(
 async fn import_data_from_file(
     database: &Arc<Database>,
     file_path: &str,
+    passphrase: Option<&str>,
 ) -> Result<usize, anyhow::Error> {
     use std::fs::File;
     use std::io::Read;
@@ -509,17 +1262,28 @@ async fn import_data_from_file(
     
     // Read and parse JSON file
     let mut file = File::open(file_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+
+    if encryption::is_encrypted(&contents) {
+        use anyhow::Context;
+        let passphrase = passphrase
+            .ok_or(encryption::InvalidPassphrase)
+            .context("Arquivo cifrado: informe a senha usada na exportação")?;
+        contents = encryption::decrypt(&contents, passphrase)?;
+    }
     
     // Parse JSON as StoredEvent array and convert to KeyEvent
-    let stored_events: Vec<StoredEvent> = serde_json::from_str(&contents)?;
+    let stored_events: Vec<StoredEvent> = serde_json::from_slice(&contents)?;
     
     let mut imported_count = 0;
     for stored_event in stored_events {
+        // Exports made before the switch to milliseconds carry seconds
+        let timestamp = normalize_millis(stored_event.timestamp);
+
         // Convert StoredEvent back to KeyEvent for storage
         let key_event = KeyEvent {
-            timestamp: stored_event.timestamp,
+            timestamp,
             key: stored_event.key,
             event_type: stored_event.event_type,
             window_info: if stored_event.window_title.is_some() || stored_event.application.is_some() {
@@ -527,13 +1291,15 @@ async fn import_data_from_file(
                     title: stored_event.window_title.unwrap_or_default(),
                     application: stored_event.application.unwrap_or_default(),
                     process_id: None,
-                    timestamp: stored_event.timestamp,
+                    timestamp,
                 })
             } else {
                 None
             },
             is_modifier: false, // This info is lost in export, could be enhanced
             is_function_key: false, // This info is lost in export, could be enhanced
+            session_id: stored_event.session_id,
+            modifiers: crate::agent::shortcuts::decode_modifiers(stored_event.modifiers.as_deref()),
         };
         
         // Store individual event (batch processing could be more efficient)
@@ -543,4 +1309,263 @@ async fn import_data_from_file(
     
     info!("✅ {} eventos importados de {}", imported_count, file_path);
     Ok(imported_count)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_status_from_metrics() {
+        let mut metrics = HashMap::new();
+        metrics.insert("uptime_seconds".to_string(), 42);
+        metrics.insert("events_captured".to_string(), 7);
+
+        let status = AgentStatus::from_metrics(true, false, &metrics);
+        assert!(status.is_running);
+        assert!(!status.is_paused);
+        assert_eq!(status.uptime_seconds, 42);
+        assert_eq!(status.events_captured, 7);
+
+        let status = AgentStatus::from_metrics(false, false, &HashMap::new());
+        assert!(!status.is_running);
+        assert_eq!(status.uptime_seconds, 0);
+        assert_eq!(status.events_captured, 0);
+    }
+
+    #[test]
+    fn test_search_page_metadata() {
+        let result = |id| SearchResult {
+            id,
+            content: String::new(),
+            timestamp: 0,
+            timestamp_iso: String::new(),
+            relevance_score: 1.0,
+            context: None,
+            highlight: None,
+            window_title: None,
+            context_before: None,
+            context_after: None,
+        };
+
+        let first = SearchResponse::page(vec![result(1), result(2)], 5, 0, 3);
+        assert_eq!(first.total_count, 5);
+        assert!(first.has_more);
+        assert_eq!(first.next_offset, Some(2));
+
+        let last = SearchResponse::page(vec![result(5)], 5, 4, 3);
+        assert!(!last.has_more);
+        assert_eq!(last.next_offset, None);
+
+        // Offset além do fim não aponta para outra página
+        let past_end = SearchResponse::page(Vec::new(), 5, 10, 3);
+        assert!(!past_end.has_more);
+
+        let empty = SearchResponse::empty();
+        assert_eq!((empty.total_count, empty.has_more, empty.next_offset), (0, false, None));
+    }
+
+    #[test]
+    fn test_sorted_patterns() {
+        let mut masker = Masker::new();
+        masker.add_custom_pattern("aaa_custom".to_string(), r"\bX\d+\b".to_string()).unwrap();
+
+        let patterns = sorted_patterns(&masker);
+        assert_eq!(patterns.first().map(String::as_str), Some("aaa_custom"));
+        assert!(patterns.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_preview_masking() {
+        let mut masker = Masker::new();
+        masker.add_custom_pattern("matricula".to_string(), r"\bMAT-\d{4}\b".to_string()).unwrap();
+        let text = "MAT-1234 de joao@exemplo.com";
+
+        let all = preview_masking_with(&masker, text, None).unwrap();
+        assert_eq!(all.pattern_counts.get("matricula"), Some(&1));
+        assert_eq!(all.pattern_counts.get("email"), Some(&1));
+        assert!(!all.masked_text.contains("MAT-1234"));
+
+        let single = preview_masking_with(&masker, text, Some("matricula")).unwrap();
+        assert_eq!(single.detected_patterns, vec!["matricula".to_string()]);
+        assert!(single.masked_text.contains("joao@exemplo.com"));
+
+        let error = preview_masking_with(&masker, text, Some("ausente")).unwrap_err();
+        assert_eq!(error.code(), "validation");
+    }
+
+    #[test]
+    fn test_check_query_length() {
+        // Vazia: sem erro e sem busca
+        assert!(!check_query("", 2).unwrap());
+        assert!(!check_query("   ", 2).unwrap());
+
+        let error = check_query("a", 2).unwrap_err();
+        assert!(matches!(error, CommandError::Validation(_)));
+        assert!(check_query(" é ", 2).is_err());
+
+        assert!(check_query("oi", 2).unwrap());
+        assert!(check_query("ção", 3).unwrap());
+        assert!(check_query("a", 1).unwrap());
+    }
+
+    #[test]
+    fn test_check_pattern_rejects_risky_regex() {
+        assert!(check_pattern("matricula", r"\bMAT-\d{4}\b").is_ok());
+
+        let error = check_pattern("frase", r"(\w+\s?)+$").unwrap_err();
+        assert!(matches!(error, CommandError::Validation(ref message) if message.contains("lento")));
+
+        let error = check_pattern("quebrado", "(unclosed").unwrap_err();
+        assert!(matches!(error, CommandError::Validation(ref message) if message.contains("inválida")));
+    }
+
+    #[tokio::test]
+    async fn test_mask_text_shares_agent_masker() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let database = Arc::new(Database::new(temp_file.path()).await.unwrap());
+        let agent = Agent::new(Masker::new(), database).await.unwrap();
+        let masker = agent.shared_masker();
+
+        let result = mask_text_with(&masker, "CPF 123.456.789-01, email joao@exemplo.com").await;
+        assert_eq!(result.masked_text, "CPF ***.***.***-01, email j***@exemplo.com");
+        assert_eq!(result.pattern_counts.len(), 2);
+
+        // Padrão adicionado em tempo de execução vale para o comando e para o agente
+        masker.write().await.add_custom_pattern("matricula".to_string(), r"\bMAT-\d{4}\b".to_string()).unwrap();
+        let result = mask_text_with(&masker, "MAT-1234").await;
+        assert_eq!(result.detected_patterns, vec!["matricula".to_string()]);
+        assert!(Arc::ptr_eq(&masker, &agent.shared_masker()));
+        assert!(agent.shared_masker().read().await.list_patterns().contains(&"matricula".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_record_search_masks_and_ranks() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let database = Database::new(temp_file.path()).await.unwrap();
+        let masker = RwLock::new(Masker::new());
+
+        for query in ["relatório", "joao@exemplo.com", "relatório", "relatório", "joao@exemplo.com"] {
+            record_search(&database, &masker, query).await;
+        }
+
+        let popular = database.popular_searches(0, 10).await.unwrap();
+        assert_eq!(popular[0], "relatório");
+        assert_eq!(popular[1], "j***@exemplo.com");
+        assert!(!popular.iter().any(|q| q.contains("joao@")));
+    }
+
+    #[test]
+    fn test_bad_arguments_are_validation_errors() {
+        let error = validate_time_range(10, 5).unwrap_err();
+        assert_eq!(error.code(), "validation");
+        assert!(validate_time_range(5, 5).is_ok());
+
+        let error = require_confirmation(false, "Confirmação necessária para limpar dados").unwrap_err();
+        assert_eq!(serde_json::to_value(&error).unwrap()["code"], "validation");
+        assert!(require_confirmation(true, "").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_export_roundtrip() {
+        let source_file = tempfile::NamedTempFile::new().unwrap();
+        let source = Arc::new(Database::new(source_file.path()).await.unwrap());
+        let events: Vec<crate::agent::KeyEvent> = (1..=3)
+            .map(|ts| crate::agent::KeyEvent {
+                timestamp: ts,
+                key: format!("tecla{}", ts),
+                event_type: "press".to_string(),
+                window_info: None,
+                is_modifier: false,
+                is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
+            })
+            .collect();
+        source.store_events(&events).await.unwrap();
+
+        let export_dir = tempfile::tempdir().unwrap();
+        let export_path = export_dir.path().join("export.json");
+        let export_path = export_path.to_str().unwrap();
+        assert_eq!(export_data_to_file(&source, export_path, None, None, Some("senha")).await.unwrap(), 3);
+
+        let raw = std::fs::read(export_path).unwrap();
+        assert!(!raw.windows(6).any(|w| w == b"tecla1"));
+
+        let target_file = tempfile::NamedTempFile::new().unwrap();
+        let target = Arc::new(Database::new(target_file.path()).await.unwrap());
+
+        let error = import_data_from_file(&target, export_path, Some("errada")).await.unwrap_err();
+        assert_eq!(CommandError::classify("Erro ao importar dados", error).code(), "validation");
+        let error = import_data_from_file(&target, export_path, None).await.unwrap_err();
+        assert_eq!(CommandError::classify("Erro ao importar dados", error).code(), "validation");
+        assert_eq!(target.get_stats().await.unwrap().total_events, 0);
+
+        assert_eq!(import_data_from_file(&target, export_path, Some("senha")).await.unwrap(), 3);
+        let keys: Vec<_> = target
+            .search_by_timerange(0, u64::MAX, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.key)
+            .collect();
+        assert_eq!(keys, vec!["tecla3", "tecla2", "tecla1"]);
+    }
+
+    #[tokio::test]
+    async fn test_import_converts_second_timestamps() {
+        let export_dir = tempfile::tempdir().unwrap();
+        let export_path = export_dir.path().join("antigo.json");
+        // Exportação anterior aos milissegundos, ainda sem `timestamp_iso`
+        std::fs::write(
+            &export_path,
+            r#"[{"id": 1, "timestamp": 1700000000, "key": "a", "event_type": "press",
+                 "window_title": null, "application": null, "text_content": "a",
+                 "created_at": "2023-11-14 22:13:20"}]"#,
+        ).unwrap();
+
+        let target_file = tempfile::NamedTempFile::new().unwrap();
+        let target = Arc::new(Database::new(target_file.path()).await.unwrap());
+        assert_eq!(import_data_from_file(&target, export_path.to_str().unwrap(), None).await.unwrap(), 1);
+
+        let events = target.search_by_timerange(0, u64::MAX, 10).await.unwrap();
+        assert_eq!(events[0].timestamp, 1_700_000_000_000);
+        assert_eq!(events[0].timestamp_iso, "2023-11-14T22:13:20.000Z");
+        assert_eq!(events[0].timestamp_datetime().timestamp(), 1_700_000_000);
+    }
+
+    #[test]
+    fn test_regex_search_requires_filter() {
+        assert_eq!(regex_search_range(None, None, None).unwrap_err().code(), "validation");
+        assert_eq!(regex_search_range(Some(10), None, None).unwrap_err().code(), "validation");
+        assert_eq!(regex_search_range(None, None, Some(" ")).unwrap_err().code(), "validation");
+        assert_eq!(regex_search_range(Some(10), Some(5), Some("editor")).unwrap_err().code(), "validation");
+
+        assert_eq!(regex_search_range(Some(5), Some(10), None).unwrap(), (5, 10));
+        assert_eq!(regex_search_range(Some(5), None, Some("editor")).unwrap(), (5, u64::MAX));
+    }
+
+    #[test]
+    fn test_plaintext_export_requires_opt_in() {
+        assert_eq!(validate_export_passphrase(None, false).unwrap_err().code(), "validation");
+        assert_eq!(validate_export_passphrase(Some(String::new()), true).unwrap_err().code(), "validation");
+        assert_eq!(validate_export_passphrase(None, true).unwrap(), None);
+        assert_eq!(validate_export_passphrase(Some("s".to_string()), false).unwrap().as_deref(), Some("s"));
+    }
+
+    #[tokio::test]
+    async fn test_database_failure_is_database_error() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let database = Database::new(temp_file.path()).await.unwrap();
+
+        // Remove a tabela por fora para provocar uma falha real do SQLite
+        rusqlite::Connection::open(temp_file.path())
+            .unwrap()
+            .execute("DROP TABLE search_log", [])
+            .unwrap();
+
+        let error = CommandError::from(database.popular_searches(0, 10).await.unwrap_err());
+        assert_eq!(error.code(), "database");
+        assert_eq!(serde_json::to_value(&error).unwrap()["code"], "database");
+    }
+}
@@ -0,0 +1,117 @@
+//! Backups periódicos em uma pasta escolhida pelo usuário.
+//!
+//! Cada backup é um arquivo `keyai-backup-<data UTC>.db`; o nome tem largura
+//! fixa, então a ordem alfabética é a cronológica e a data é lida de volta do
+//! próprio nome ao listar.
+
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use serde::{Serialize, Deserialize};
+use tracing::{debug, warn};
+
+const BACKUP_FILE_PREFIX: &str = "keyai-backup-";
+const BACKUP_FILE_SUFFIX: &str = ".db";
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+/// Backup encontrado na pasta de backups
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub path: String,
+    /// Momento do backup, em milissegundos desde a época
+    pub created_at: u64,
+    pub size_bytes: u64,
+}
+
+/// Nome do arquivo de um backup feito em `created_at` (ms)
+pub fn backup_file_name(created_at: u64) -> String {
+    let time = Utc
+        .timestamp_millis_opt(created_at as i64)
+        .single()
+        .unwrap_or_default();
+    format!("{}{}{}", BACKUP_FILE_PREFIX, time.format(BACKUP_TIME_FORMAT), BACKUP_FILE_SUFFIX)
+}
+
+fn parse_backup_file_name(name: &str) -> Option<u64> {
+    let time = name.strip_prefix(BACKUP_FILE_PREFIX)?.strip_suffix(BACKUP_FILE_SUFFIX)?;
+    let time = NaiveDateTime::parse_from_str(time, BACKUP_TIME_FORMAT).ok()?;
+    u64::try_from(time.and_utc().timestamp_millis()).ok()
+}
+
+/// Backups em `dir`, do mais recente ao mais antigo. Pasta inexistente não tem backups
+pub fn list_backups(dir: &Path) -> Result<Vec<BackupInfo>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        // Outros arquivos da pasta (ex.: de sincronização) são ignorados
+        let Some(created_at) = name.to_str().and_then(parse_backup_file_name) else {
+            continue;
+        };
+
+        backups.push(BackupInfo {
+            path: entry.path().to_string_lossy().to_string(),
+            created_at,
+            size_bytes: entry.metadata()?.len(),
+        });
+    }
+
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+    Ok(backups)
+}
+
+/// Remove os backups além dos `keep` mais recentes, retornando os removidos
+pub fn prune_backups(dir: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for backup in list_backups(dir)?.into_iter().skip(keep) {
+        let path = PathBuf::from(&backup.path);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                debug!("🗑️ Backup antigo removido: {:?}", path);
+                removed.push(path);
+            }
+            Err(e) => warn!("⚠️ Não foi possível remover o backup {:?}: {}", path, e),
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_roundtrip() {
+        let created_at = 1_792_000_000_123;
+        let name = backup_file_name(created_at);
+
+        assert!(name.starts_with(BACKUP_FILE_PREFIX) && name.ends_with(BACKUP_FILE_SUFFIX));
+        assert_eq!(parse_backup_file_name(&name), Some(created_at));
+        assert_eq!(parse_backup_file_name("notas.db"), None);
+    }
+
+    #[test]
+    fn test_list_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(list_backups(&dir.path().join("ausente")).unwrap().is_empty());
+
+        for created_at in [3_000, 1_000, 2_000] {
+            std::fs::write(dir.path().join(backup_file_name(created_at)), b"db").unwrap();
+        }
+        std::fs::write(dir.path().join("desktop.ini"), b"").unwrap();
+
+        let backups = list_backups(dir.path()).unwrap();
+        let order: Vec<u64> = backups.iter().map(|b| b.created_at).collect();
+        assert_eq!(order, vec![3_000, 2_000, 1_000]);
+        assert_eq!(backups[0].size_bytes, 2);
+
+        let removed = prune_backups(dir.path(), 2).unwrap();
+        assert_eq!(removed, vec![PathBuf::from(&backups[2].path)]);
+        assert_eq!(list_backups(dir.path()).unwrap().len(), 2);
+        assert!(dir.path().join("desktop.ini").exists());
+    }
+}
@@ -0,0 +1,367 @@
+//! Compactação do histórico de teclas em segmentos de texto.
+//!
+//! Bancos antigos guardam uma linha por caractere, e o FTS5 indexa letras
+//! soltas que nenhuma busca encontra. A compactação percorre as teclas
+//! pressionadas em ordem, agrupa as contínuas (mesma sessão, aplicação e
+//! janela, sem pausas longas) e troca cada grupo por uma única linha
+//! `segment` com o texto remontado. Roda em lotes, liberando o escritor entre
+//! eles para não bloquear a captura.
+
+use anyhow::Result;
+use rusqlite::{Connection, params};
+use serde::{Serialize, Deserialize};
+use tracing::{info, debug};
+
+use super::Database;
+use super::tokens::TOKEN_INDEX_KEY;
+
+/// Tipo das linhas criadas pela compactação
+pub const SEGMENT_EVENT_TYPE: &str = "segment";
+
+/// Pausa máxima entre teclas de um mesmo segmento, em milissegundos
+const SEGMENT_GAP_MS: i64 = 5_000;
+
+/// Teclas lidas por lote
+const COMPACTION_BATCH_SIZE: usize = 5_000;
+
+/// Resultado de `compact_keystrokes_into_segments`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactionReport {
+    /// `true` quando nada foi gravado (sem confirmação)
+    pub preview: bool,
+    pub segments: usize,
+    /// Linhas de teclas substituídas pelos segmentos
+    pub events_merged: usize,
+}
+
+struct Keystroke {
+    id: i64,
+    timestamp: i64,
+    key: String,
+    text_content: Option<String>,
+    session_id: Option<String>,
+    application: Option<String>,
+    window_title: Option<String>,
+}
+
+struct Segment {
+    ids: Vec<i64>,
+    text: String,
+    last_timestamp: i64,
+    session_id: Option<String>,
+    application: Option<String>,
+    window_title: Option<String>,
+}
+
+impl Segment {
+    fn start(keystroke: &Keystroke) -> Self {
+        Self {
+            ids: Vec::new(),
+            text: String::new(),
+            last_timestamp: keystroke.timestamp,
+            session_id: keystroke.session_id.clone(),
+            application: keystroke.application.clone(),
+            window_title: keystroke.window_title.clone(),
+        }
+    }
+
+    fn accepts(&self, keystroke: &Keystroke) -> bool {
+        keystroke.session_id == self.session_id
+            && keystroke.application == self.application
+            && keystroke.window_title == self.window_title
+            && keystroke.timestamp - self.last_timestamp <= SEGMENT_GAP_MS
+    }
+
+    fn push(&mut self, keystroke: &Keystroke) {
+        match (&keystroke.text_content, keystroke.key.as_str()) {
+            (Some(text), _) => self.text.push_str(text),
+            (None, "Space") => self.text.push(' '),
+            (None, "Return") => self.text.push('\n'),
+            (None, "Tab") => self.text.push('\t'),
+            (None, "Backspace") => {
+                self.text.pop();
+            }
+            _ => {}
+        }
+        self.ids.push(keystroke.id);
+        self.last_timestamp = keystroke.timestamp;
+    }
+
+    /// Segmentos de uma tecla só, ou que não deixam texto, ficam como estão
+    fn is_worth_merging(&self) -> bool {
+        self.ids.len() > 1 && !self.text.trim().is_empty()
+    }
+}
+
+/// Agrupa as teclas na ordem em que chegam; o segmento aberto continua no lote seguinte
+#[derive(Default)]
+struct SegmentBuilder {
+    current: Option<Segment>,
+    closed: Vec<Segment>,
+}
+
+impl SegmentBuilder {
+    fn push(&mut self, keystroke: &Keystroke) {
+        if !self.current.as_ref().is_some_and(|segment| segment.accepts(keystroke)) {
+            self.close();
+            self.current = Some(Segment::start(keystroke));
+        }
+        if let Some(segment) = self.current.as_mut() {
+            segment.push(keystroke);
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(segment) = self.current.take() {
+            self.closed.push(segment);
+        }
+    }
+
+    fn take_closed(&mut self) -> Vec<Segment> {
+        std::mem::take(&mut self.closed)
+            .into_iter()
+            .filter(Segment::is_worth_merging)
+            .collect()
+    }
+}
+
+/// Teclas pressionadas que produzem texto, depois de `after_id`
+fn load_keystrokes(conn: &Connection, after_id: i64, limit: usize) -> rusqlite::Result<Vec<Keystroke>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, timestamp, key, text_content, session_id, application, window_title
+         FROM events
+         WHERE id > ?1
+           AND event_type = 'press'
+           AND (length(text_content) = 1 OR (text_content IS NULL AND key IN ('Space', 'Return', 'Tab', 'Backspace')))
+         ORDER BY id
+         LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![after_id, limit], |row| {
+        Ok(Keystroke {
+            id: row.get(0)?,
+            timestamp: row.get(1)?,
+            key: row.get(2)?,
+            text_content: row.get(3)?,
+            session_id: row.get(4)?,
+            application: row.get(5)?,
+            window_title: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Troca cada segmento pela sua primeira linha, agora com o texto completo.
+/// Retorna quantos segmentos foram gravados e quantas teclas eles juntaram;
+/// segmentos duplicados são pulados e não entram em nenhuma das contagens
+fn rewrite_segments(
+    conn: &Connection,
+    segments: &[Segment],
+    mask: &(dyn Fn(&str) -> String + Sync),
+) -> Result<(usize, usize)> {
+    let tx = conn.unchecked_transaction()?;
+    let mut written = 0;
+    let mut merged = 0;
+    {
+        let mut update = tx.prepare_cached(
+            "UPDATE OR IGNORE events SET key = ?2, event_type = ?3, text_content = ?2 WHERE id = ?1",
+        )?;
+        let mut delete = tx.prepare_cached(
+            "DELETE FROM events WHERE id IN (SELECT value FROM json_each(?1))",
+        )?;
+
+        for segment in segments {
+            let text = mask(&segment.text);
+            // Another row with the same timestamp and text already exists
+            if update.execute(params![segment.ids[0], text, SEGMENT_EVENT_TYPE])? == 0 {
+                debug!("🧱 Segmento iniciado no evento {} ignorado (duplicado)", segment.ids[0]);
+                continue;
+            }
+            delete.execute(params![serde_json::to_string(&segment.ids[1..])?])?;
+            written += 1;
+            merged += segment.ids.len();
+        }
+    }
+    tx.commit()?;
+    Ok((written, merged))
+}
+
+impl Database {
+    /// Junta as teclas de texto gravadas uma a uma em segmentos e reconstrói
+    /// os índices. Sem `confirm`, apenas conta o que seria compactado.
+    /// `mask` é aplicado a cada texto remontado antes de gravá-lo, já que um
+    /// dado sensível digitado tecla a tecla só aparece depois de juntar
+    pub async fn compact_keystrokes_into_segments(
+        &self,
+        confirm: bool,
+        mask: &(dyn Fn(&str) -> String + Sync),
+    ) -> Result<CompactionReport> {
+        self.compact_keystrokes_in_batches(confirm, mask, COMPACTION_BATCH_SIZE).await
+    }
+
+    async fn compact_keystrokes_in_batches(
+        &self,
+        confirm: bool,
+        mask: &(dyn Fn(&str) -> String + Sync),
+        batch_size: usize,
+    ) -> Result<CompactionReport> {
+        let mut report = CompactionReport { preview: !confirm, ..Default::default() };
+        let mut builder = SegmentBuilder::default();
+        let mut cursor = 0;
+
+        loop {
+            let keystrokes = {
+                let conn = self.reader()?;
+                load_keystrokes(&conn, cursor, batch_size)?
+            };
+            let last_batch = keystrokes.len() < batch_size;
+            for keystroke in &keystrokes {
+                builder.push(keystroke);
+                cursor = keystroke.id;
+            }
+            if last_batch {
+                builder.close();
+            }
+
+            let segments = builder.take_closed();
+            if confirm && !segments.is_empty() {
+                let conn = self.writer.lock().await;
+                let (written, merged) = rewrite_segments(&conn, &segments, mask)?;
+                report.segments += written;
+                report.events_merged += merged;
+            } else {
+                report.segments += segments.len();
+                report.events_merged += segments.iter().map(|segment| segment.ids.len()).sum::<usize>();
+            }
+
+            if last_batch {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+
+        if confirm && report.segments > 0 {
+            self.rebuild_fts_index().await?;
+
+            // Word positions changed; the phrase index is rebuilt on the next search
+            let conn = self.writer.lock().await;
+            conn.execute("DELETE FROM tokens", [])?;
+            conn.execute("DELETE FROM config WHERE key = ?1", params![TOKEN_INDEX_KEY])?;
+        }
+
+        info!(
+            "🧱 Compactação do histórico: {} segmentos a partir de {} teclas{}",
+            report.segments,
+            report.events_merged,
+            if report.preview { " (prévia)" } else { "" }
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    async fn seed_typing(db: &Database, start: u64, text: &str, application: &str) {
+        let conn = db.writer.lock().await;
+        for (i, c) in text.chars().enumerate() {
+            let (key, content) = match c {
+                ' ' => ("Space".to_string(), None),
+                '\u{8}' => ("Backspace".to_string(), None),
+                c => (c.to_string(), Some(c.to_string())),
+            };
+            conn.execute(
+                "INSERT INTO events (timestamp, key, event_type, application, text_content)
+                 VALUES (?1, ?2, 'press', ?3, ?4)",
+                params![start + i as u64, key, application, content],
+            ).unwrap();
+            // Liberações e modificadores não entram nos segmentos
+            conn.execute(
+                "INSERT INTO events (timestamp, key, event_type, application) VALUES (?1, ?2, 'release', ?3)",
+                params![start + i as u64, key, application],
+            ).unwrap();
+        }
+    }
+
+    fn no_mask(text: &str) -> String {
+        text.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_compaction_makes_words_searchable() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        seed_typing(&db, 1_000, "relatorio anuaal\u{8}\u{8}l", "editor").await;
+        // Outra aplicação e uma pausa longa iniciam novos segmentos
+        seed_typing(&db, 1_100, "oi mundo", "chat").await;
+        seed_typing(&db, 60_000, "fim", "chat").await;
+        assert!(db.search_text("relatorio", 10).await.unwrap().is_empty());
+
+        let preview = db.compact_keystrokes_into_segments(false, &no_mask).await.unwrap();
+        assert!(preview.preview);
+        assert_eq!(preview.segments, 3);
+        assert!(db.search_text("relatorio", 10).await.unwrap().is_empty());
+
+        let report = db.compact_keystrokes_in_batches(true, &no_mask, 4).await.unwrap();
+        assert!(!report.preview);
+        assert_eq!(report.segments, 3);
+        assert_eq!(report.events_merged, 19 + 8 + 3);
+
+        let results = db.search_text("relatorio", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "relatorio anual");
+        assert_eq!(db.search_text("mundo", 10).await.unwrap()[0].content, "oi mundo");
+        assert_eq!(db.search_text("fim", 10).await.unwrap()[0].timestamp, 60_000);
+
+        let phrases = db.search_phrase("relatorio anual", 0, &no_mask).await.unwrap();
+        assert_eq!(phrases.len(), 1);
+
+        let remaining = db.search_by_timerange(0, u64::MAX, 100).await.unwrap();
+        let segments = remaining.iter().filter(|e| e.event_type == SEGMENT_EVENT_TYPE).count();
+        let presses = remaining.iter().filter(|e| e.event_type == "press").count();
+        assert_eq!((segments, presses), (3, 0));
+        assert_eq!(remaining.iter().filter(|e| e.event_type == "release").count(), 30);
+
+        // Uma segunda execução não encontra mais nada para juntar
+        let again = db.compact_keystrokes_into_segments(true, &no_mask).await.unwrap();
+        assert_eq!(again.segments, 0);
+    }
+
+    #[tokio::test]
+    async fn test_skipped_duplicate_segment_not_counted() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        seed_typing(&db, 1_000, "oi", "editor").await;
+        seed_typing(&db, 1_100, "tchau", "chat").await;
+        {
+            // O primeiro segmento já existe, de uma compactação anterior
+            let conn = db.writer.lock().await;
+            conn.execute(
+                "INSERT INTO events (timestamp, key, event_type) VALUES (1000, 'oi', ?1)",
+                params![SEGMENT_EVENT_TYPE],
+            ).unwrap();
+        }
+
+        let report = db.compact_keystrokes_into_segments(true, &no_mask).await.unwrap();
+        assert_eq!(report.segments, 1);
+        assert_eq!(report.events_merged, 5);
+    }
+
+    #[tokio::test]
+    async fn test_compaction_masks_reconstructed_text() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        seed_typing(&db, 1_000, "cpf 12345678901", "editor").await;
+
+        let masker = crate::masker::Masker::new();
+        let mask = |text: &str| masker.mask_text(text);
+        db.compact_keystrokes_into_segments(true, &mask).await.unwrap();
+
+        let events = db.search_by_timerange(0, u64::MAX, 100).await.unwrap();
+        let segment = events.iter().find(|e| e.event_type == SEGMENT_EVENT_TYPE).unwrap();
+        assert_ne!(segment.text_content.as_deref(), Some("cpf 12345678901"));
+        assert!(!segment.key.contains("12345678901"));
+    }
+}
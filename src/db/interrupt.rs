@@ -0,0 +1,100 @@
+//! Interrupção de consultas de leitura canceladas.
+//!
+//! As consultas rodam de forma síncrona dentro das tasks de busca, então
+//! abortar a task só teria efeito no próximo `await`. Dentro de
+//! [`cancellable`], cada conexão de leitura obtida é registrada em
+//! [`QueryInterrupts`]; `interrupt_all` chama `sqlite3_interrupt` nelas e a
+//! consulta em andamento falha com `SQLITE_INTERRUPT`, liberando o worker.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard};
+use r2d2::PooledConnection;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, InterruptHandle};
+
+tokio::task_local! {
+    static QUERY_INTERRUPTS: Arc<QueryInterrupts>;
+}
+
+/// Conexões de leitura em uso por uma busca
+#[derive(Default)]
+pub struct QueryInterrupts {
+    state: Mutex<InterruptState>,
+}
+
+#[derive(Default)]
+struct InterruptState {
+    next_id: u64,
+    handles: HashMap<u64, InterruptHandle>,
+    interrupted: bool,
+}
+
+impl QueryInterrupts {
+    /// Interrompe as consultas em andamento e as que a busca ainda iniciar
+    pub fn interrupt_all(&self) {
+        let mut state = self.lock();
+        state.interrupted = true;
+        for handle in state.handles.values() {
+            handle.interrupt();
+        }
+    }
+
+    fn register(&self, handle: InterruptHandle) -> u64 {
+        let mut state = self.lock();
+        if state.interrupted {
+            handle.interrupt();
+        }
+        state.next_id += 1;
+        let id = state.next_id;
+        state.handles.insert(id, handle);
+        id
+    }
+
+    fn unregister(&self, id: u64) {
+        self.lock().handles.remove(&id);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, InterruptState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Executa `future` com as leituras registradas em `interrupts`
+pub async fn cancellable<F: Future>(interrupts: Arc<QueryInterrupts>, future: F) -> F::Output {
+    QUERY_INTERRUPTS.scope(interrupts, future).await
+}
+
+/// Conexão do pool de leitura, registrada na busca em andamento (se houver)
+pub(super) struct ReadConnection {
+    conn: PooledConnection<SqliteConnectionManager>,
+    registration: Option<(Arc<QueryInterrupts>, u64)>,
+}
+
+impl ReadConnection {
+    pub(super) fn new(conn: PooledConnection<SqliteConnectionManager>) -> Self {
+        let registration = QUERY_INTERRUPTS.try_with(Arc::clone).ok().map(|interrupts| {
+            let id = interrupts.register(conn.get_interrupt_handle());
+            (interrupts, id)
+        });
+        Self { conn, registration }
+    }
+}
+
+impl Deref for ReadConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl Drop for ReadConnection {
+    fn drop(&mut self) {
+        // Unregister before `conn` returns to the pool, so a late interrupt cannot hit the next query
+        if let Some((interrupts, id)) = &self.registration {
+            interrupts.unregister(*id);
+        }
+    }
+}
@@ -0,0 +1,587 @@
+//! Migrações versionadas do schema SQLite.
+//!
+//! A versão aplicada fica em `PRAGMA user_version`. Cada passo roda numa
+//! transação própria e só então a versão é incrementada; para alterar o
+//! schema, adicione um novo passo ao final de [`MIGRATIONS`] em vez de editar
+//! os existentes.
+
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+use tracing::info;
+
+use super::timestamps::LEGACY_SECONDS_THRESHOLD;
+
+/// Um passo de migração do schema
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub up: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+/// Migrações em ordem crescente de versão
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "schema inicial (events, FTS5, embeddings, search_log)",
+        up: v1_initial_schema,
+    },
+    Migration {
+        version: 2,
+        description: "sessões de digitação (events.session_id)",
+        up: v2_session_id,
+    },
+    Migration {
+        version: 3,
+        description: "modificadores por evento (events.modifiers)",
+        up: v3_modifiers,
+    },
+    Migration {
+        version: 4,
+        description: "modelo e dimensão dos embeddings (embeddings.model_name, embeddings.dim)",
+        up: v4_embedding_model,
+    },
+    Migration {
+        version: 5,
+        description: "configurações persistidas (config)",
+        up: v5_config,
+    },
+    Migration {
+        version: 6,
+        description: "índice de palavras para busca por frase (tokens)",
+        up: v6_tokens,
+    },
+    Migration {
+        version: 7,
+        description: "FTS5 com unicode61 remove_diacritics 2",
+        up: v7_fts_remove_diacritics,
+    },
+    Migration {
+        version: 8,
+        description: "índice por tipo de evento (events.event_type)",
+        up: v8_event_type_index,
+    },
+    Migration {
+        version: 9,
+        description: "timestamps de eventos em milissegundos",
+        up: v9_timestamps_in_millis,
+    },
+];
+
+/// Versão mais recente conhecida por este binário
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> Result<u32> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+/// Aplica as migrações pendentes e retorna a versão resultante
+pub fn run(conn: &Connection) -> Result<u32> {
+    let start = current_version(conn)?;
+    let latest = latest_version();
+
+    if start > latest {
+        return Err(anyhow!(
+            "Banco de dados na versão {} é mais novo que a versão suportada ({})",
+            start,
+            latest
+        ));
+    }
+
+    let mut version = start;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > start) {
+        info!("🔧 Aplicando migração v{}: {}", migration.version, migration.description);
+
+        let tx = conn.unchecked_transaction()?;
+        (migration.up)(&tx)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+
+        version = migration.version;
+    }
+
+    Ok(version)
+}
+
+/// v1 - schema existente antes do versionamento. Usa `IF NOT EXISTS` para que
+/// bancos criados sem `user_version` sejam adotados sem perda de dados.
+fn v1_initial_schema(conn: &Connection) -> rusqlite::Result<()> {
+    // Create events table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            key TEXT NOT NULL,
+            event_type TEXT NOT NULL,
+            window_title TEXT,
+            application TEXT,
+            text_content TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE(timestamp, key, event_type)
+        )",
+        [],
+    )?;
+
+    // FTS5 reads external content by column name (needed by snippet()),
+    // so expose text_content under the indexed column name
+    conn.execute(
+        "CREATE VIEW IF NOT EXISTS events_fts_source AS
+         SELECT id, text_content AS content, timestamp, application, window_title
+         FROM events",
+        [],
+    )?;
+
+    // Older databases point the index straight at events, which breaks snippet()
+    let legacy_fts: bool = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master
+         WHERE name = 'text_search' AND sql LIKE '%content=''events''%'",
+        [],
+        |row| row.get::<_, i64>(0),
+    )? > 0;
+    if legacy_fts {
+        info!("🔧 Recriando índice FTS5 sobre events_fts_source");
+        conn.execute("DROP TABLE text_search", [])?;
+    }
+
+    // Create text_content table for full-text search
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS text_search USING fts5(
+            content,
+            timestamp,
+            application,
+            window_title,
+            content='events_fts_source',
+            content_rowid='id'
+        )",
+        [],
+    )?;
+
+    if legacy_fts {
+        conn.execute("INSERT INTO text_search(text_search) VALUES ('rebuild')", [])?;
+    }
+
+    // Vocabulary view over the index, used for term suggestions
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS text_search_vocab USING fts5vocab(text_search, 'col')",
+        [],
+    )?;
+
+    // Create embeddings table for semantic search
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS embeddings (
+            id INTEGER PRIMARY KEY,
+            event_id INTEGER NOT NULL,
+            embedding BLOB NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (event_id) REFERENCES events (id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Create indexes for performance
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events (timestamp)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_events_application ON events (application)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_embeddings_event_id ON embeddings (event_id)",
+        [],
+    )?;
+
+    // Query log backing popular searches (queries are masked before insert)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS search_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            query TEXT NOT NULL,
+            timestamp INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_search_log_timestamp ON search_log (timestamp)",
+        [],
+    )?;
+
+    // Create triggers to keep FTS5 table in sync
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS events_ai AFTER INSERT ON events BEGIN
+            INSERT INTO text_search(rowid, content, timestamp, application, window_title)
+            VALUES (new.id, new.text_content, new.timestamp, new.application, new.window_title);
+        END",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS events_ad AFTER DELETE ON events BEGIN
+            INSERT INTO text_search(text_search, rowid, content, timestamp, application, window_title)
+            VALUES ('delete', old.id, old.text_content, old.timestamp, old.application, old.window_title);
+        END",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS events_au AFTER UPDATE ON events BEGIN
+            INSERT INTO text_search(text_search, rowid, content, timestamp, application, window_title)
+            VALUES ('delete', old.id, old.text_content, old.timestamp, old.application, old.window_title);
+            INSERT INTO text_search(rowid, content, timestamp, application, window_title)
+            VALUES (new.id, new.text_content, new.timestamp, new.application, new.window_title);
+        END",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// v2 - agrupa eventos em sessões separadas por inatividade
+fn v2_session_id(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("ALTER TABLE events ADD COLUMN session_id TEXT", [])?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_events_session_id ON events (session_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// v3 - modificadores segurados em cada tecla, para busca por atalhos
+fn v3_modifiers(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("ALTER TABLE events ADD COLUMN modifiers TEXT", [])?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_events_shortcut ON events (key, modifiers)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// v4 - registra o modelo e a dimensão de cada embedding e passa a manter um
+/// único embedding por evento (o `INSERT OR REPLACE` antigo acumulava cópias)
+fn v4_embedding_model(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("ALTER TABLE embeddings ADD COLUMN model_name TEXT", [])?;
+    conn.execute("ALTER TABLE embeddings ADD COLUMN dim INTEGER", [])?;
+    conn.execute("UPDATE embeddings SET dim = length(embedding) / 4", [])?;
+
+    conn.execute(
+        "DELETE FROM embeddings
+         WHERE id NOT IN (SELECT MAX(id) FROM embeddings GROUP BY event_id)",
+        [],
+    )?;
+    conn.execute("DROP INDEX IF EXISTS idx_embeddings_event_id", [])?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_embeddings_event_id ON embeddings (event_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// v5 - configurações da aplicação (JSON por chave), mantidas entre execuções
+fn v5_config(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS config (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// v6 - tabela `tokens`, com as palavras remontadas das teclas para
+/// `search_phrase`. `(segment, position)` é único para que a palavra
+/// provisória seja substituída quando a digitação continua
+fn v6_tokens(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tokens (
+            id INTEGER PRIMARY KEY,
+            word TEXT NOT NULL,
+            segment INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            event_id INTEGER NOT NULL,
+            timestamp INTEGER NOT NULL,
+            FOREIGN KEY (event_id) REFERENCES events (id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tokens_word ON tokens (word)", [])?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_tokens_position ON tokens (segment, position)",
+        [],
+    )?;
+    // Lets the cascade from deleted events find their tokens
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tokens_event_id ON tokens (event_id)", [])?;
+    Ok(())
+}
+
+/// v7 - recria o índice com `remove_diacritics 2`, para que "joão" e "joao"
+/// (inclusive com acentos combinados) gerem o mesmo termo
+fn v7_fts_remove_diacritics(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("DROP TABLE IF EXISTS text_search", [])?;
+    conn.execute(
+        "CREATE VIRTUAL TABLE text_search USING fts5(
+            content,
+            timestamp,
+            application,
+            window_title,
+            content='events_fts_source',
+            content_rowid='id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        )",
+        [],
+    )?;
+    conn.execute("INSERT INTO text_search(text_search) VALUES ('rebuild')", [])?;
+    Ok(())
+}
+
+/// v8 - filtros por `event_type` (ex.: só Backspace pressionado) sem varrer a tabela
+fn v8_event_type_index(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_events_event_type ON events (event_type, timestamp)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// v9 - o agente gravava segundos; converte esses eventos (e seus tokens)
+/// para milissegundos. O gatilho `events_au` atualiza o índice FTS5
+fn v9_timestamps_in_millis(conn: &Connection) -> rusqlite::Result<()> {
+    let threshold = LEGACY_SECONDS_THRESHOLD as i64;
+    let converted = conn.execute(
+        "UPDATE events SET timestamp = timestamp * 1000 WHERE timestamp < ?1",
+        [threshold],
+    )?;
+    conn.execute(
+        "UPDATE tokens SET timestamp = timestamp * 1000 WHERE timestamp < ?1",
+        [threshold],
+    )?;
+    info!("🕒 {} eventos convertidos de segundos para milissegundos", converted);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::params;
+    use tempfile::NamedTempFile;
+
+    /// Layout gravado pelas versões sem versionamento (user_version = 0)
+    fn create_unversioned_fixture(conn: &Connection) {
+        conn.execute_batch(
+            "CREATE TABLE events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                key TEXT NOT NULL,
+                event_type TEXT NOT NULL,
+                window_title TEXT,
+                application TEXT,
+                text_content TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE(timestamp, key, event_type)
+            );
+            CREATE VIRTUAL TABLE text_search USING fts5(
+                content, timestamp, application, window_title,
+                content='events', content_rowid='id'
+            );
+            CREATE TABLE embeddings (
+                id INTEGER PRIMARY KEY,
+                event_id INTEGER NOT NULL,
+                embedding BLOB NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (event_id) REFERENCES events (id) ON DELETE CASCADE
+            );
+            CREATE TRIGGER events_ai AFTER INSERT ON events BEGIN
+                INSERT INTO text_search(rowid, content, timestamp, application, window_title)
+                VALUES (new.id, new.text_content, new.timestamp, new.application, new.window_title);
+            END;"
+        ).unwrap();
+
+        for (ts, text) in [(1, "antigo registro"), (2, "outro registro")] {
+            conn.execute(
+                "INSERT INTO events (timestamp, key, event_type, text_content, application)
+                 VALUES (?1, ?2, 'press', ?2, 'legacy')",
+                params![ts, text],
+            ).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_fresh_database_reaches_latest_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(current_version(&conn).unwrap(), 0);
+
+        assert_eq!(run(&conn).unwrap(), latest_version());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+
+        // Rodar de novo não reaplica nada
+        assert_eq!(run(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn test_unversioned_database_upgrades_without_data_loss() {
+        let temp_file = NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(temp_file.path()).unwrap();
+            create_unversioned_fixture(&conn);
+        }
+
+        let conn = Connection::open(temp_file.path()).unwrap();
+        assert_eq!(run(&conn).unwrap(), latest_version());
+
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM events", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+
+        // O índice foi reconstruído a partir dos dados existentes
+        let hits: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM text_search WHERE text_search MATCH 'registro'",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(hits, 2);
+
+        let has_search_log: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = 'search_log'",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(has_search_log, 1);
+    }
+
+    #[test]
+    fn test_newer_database_is_rejected() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+
+        assert!(run(&conn).is_err());
+    }
+
+    #[test]
+    fn test_v1_database_gains_session_column() {
+        let conn = Connection::open_in_memory().unwrap();
+        v1_initial_schema(&conn).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        conn.execute(
+            "INSERT INTO events (timestamp, key, event_type) VALUES (1, 'a', 'press')",
+            [],
+        ).unwrap();
+
+        assert_eq!(run(&conn).unwrap(), latest_version());
+
+        let session: Option<String> = conn
+            .query_row("SELECT session_id FROM events", [], |row| row.get(0))
+            .unwrap();
+        assert!(session.is_none());
+    }
+
+    #[test]
+    fn test_v3_embeddings_gain_dimension_and_are_deduplicated() {
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= 3) {
+            (migration.up)(&conn).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 3).unwrap();
+        conn.execute(
+            "INSERT INTO events (timestamp, key, event_type) VALUES (1, 'a', 'press')",
+            [],
+        ).unwrap();
+        for value in [1.0_f32, 2.0] {
+            let bytes: Vec<u8> = [value; 3].iter().flat_map(|x| x.to_le_bytes()).collect();
+            conn.execute(
+                "INSERT INTO embeddings (event_id, embedding) VALUES (1, ?1)",
+                params![bytes],
+            ).unwrap();
+        }
+
+        assert_eq!(run(&conn).unwrap(), latest_version());
+
+        let (count, dim, model): (i64, i64, Option<String>) = conn
+            .query_row("SELECT COUNT(*), MAX(dim), MAX(model_name) FROM embeddings", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!((count, dim, model), (1, 3, None));
+    }
+
+    #[test]
+    fn test_v7_rebuilds_index_with_diacritic_folding() {
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= 6) {
+            (migration.up)(&conn).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 6).unwrap();
+        conn.execute(
+            "INSERT INTO events (timestamp, key, event_type, text_content) VALUES (1, 'a', 'paste', 'São Paulo')",
+            [],
+        ).unwrap();
+
+        assert_eq!(run(&conn).unwrap(), latest_version());
+
+        let sql: String = conn
+            .query_row("SELECT sql FROM sqlite_master WHERE name = 'text_search'", [], |row| row.get(0))
+            .unwrap();
+        assert!(sql.contains("remove_diacritics 2"));
+
+        let hits: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM text_search WHERE text_search MATCH 'sao paulo'",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(hits, 1);
+    }
+
+    #[test]
+    fn test_v8_adds_event_type_index() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(run(&conn).unwrap(), latest_version());
+
+        let plan: String = conn
+            .query_row(
+                "EXPLAIN QUERY PLAN SELECT id FROM events WHERE event_type = 'release' AND timestamp > 0",
+                [],
+                |row| row.get(3),
+            )
+            .unwrap();
+        assert!(plan.contains("idx_events_event_type"), "{}", plan);
+    }
+
+    #[test]
+    fn test_v9_converts_second_timestamps_to_millis() {
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= 8) {
+            (migration.up)(&conn).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 8).unwrap();
+        for (ts, text) in [(1_700_000_000_i64, "gravado em segundos"), (1_700_000_000_500, "gravado em milissegundos")] {
+            conn.execute(
+                "INSERT INTO events (timestamp, key, event_type, text_content) VALUES (?1, ?2, 'press', ?2)",
+                params![ts, text],
+            ).unwrap();
+        }
+        conn.execute(
+            "INSERT INTO tokens (word, segment, position, event_id, timestamp) VALUES ('gravado', 1, 0, 1, 1700000000)",
+            [],
+        ).unwrap();
+
+        assert_eq!(run(&conn).unwrap(), latest_version());
+
+        let mut stmt = conn.prepare("SELECT timestamp FROM events ORDER BY id").unwrap();
+        let timestamps: Vec<i64> = stmt.query_map([], |row| row.get(0)).unwrap().map(|t| t.unwrap()).collect();
+        assert_eq!(timestamps, vec![1_700_000_000_000, 1_700_000_000_500]);
+
+        let token_ts: i64 = conn.query_row("SELECT timestamp FROM tokens", [], |row| row.get(0)).unwrap();
+        assert_eq!(token_ts, 1_700_000_000_000);
+
+        // O índice continua consistente depois do UPDATE
+        let hits: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM text_search WHERE text_search MATCH 'gravado'",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(hits, 2);
+    }
+}
@@ -1,32 +1,147 @@
 use std::path::Path;
 use std::sync::Arc;
-use rusqlite::{Connection, params};
+use std::time::Instant;
+use regex::Regex;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use rusqlite::backup::Backup;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
 use tokio::sync::Mutex;
-use anyhow::Result;
-use tracing::{info, debug};
+use anyhow::{Result, anyhow};
+use tracing::{info, debug, instrument};
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
 
-use crate::agent::KeyEvent;
+use crate::agent::{AgentConfig, KeyEvent, PASTE_EVENT_TYPE};
+use crate::agent::shortcuts::{decode_modifiers, encode_modifiers};
+use crate::search::SearchOptions;
+
+mod migrations;
+
+/// Limite de eventos de cada lado em `get_event_context`
+const MAX_CONTEXT_SIZE: usize = 50;
+
+/// Tipos de evento considerados pelas buscas quando nenhum é informado
+pub const DEFAULT_SEARCH_EVENT_TYPES: &[&str] = &["press", PASTE_EVENT_TYPE, SEGMENT_EVENT_TYPE];
+
+pub fn default_search_event_types() -> Vec<String> {
+    DEFAULT_SEARCH_EVENT_TYPES.iter().map(|t| t.to_string()).collect()
+}
+
+mod backups;
+pub use backups::{BackupInfo, list_backups, prune_backups};
+
+mod tokens;
+pub use tokens::PhraseMatch;
+
+mod compaction;
+pub use compaction::{CompactionReport, SEGMENT_EVENT_TYPE};
+
+mod interrupt;
+pub use interrupt::{cancellable, QueryInterrupts};
+use interrupt::ReadConnection;
+
+mod timestamps;
+pub use timestamps::{millis_to_datetime, millis_to_iso, normalize_millis};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredEvent {
     pub id: i64,
+    /// Milissegundos desde a época
     pub timestamp: u64,
+    /// `timestamp` em ISO-8601, para exibição no frontend
+    #[serde(default)]
+    pub timestamp_iso: String,
     pub key: String,
     pub event_type: String,
     pub window_title: Option<String>,
     pub application: Option<String>,
     pub text_content: Option<String>,
     pub created_at: String,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Combinação armazenada, ex.: `"Ctrl+Shift"`
+    #[serde(default)]
+    pub modifiers: Option<String>,
+}
+
+impl StoredEvent {
+    pub fn timestamp_datetime(&self) -> DateTime<Utc> {
+        millis_to_datetime(self.timestamp)
+    }
+}
+
+/// Ocorrência de um atalho de teclado
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShortcutMatch {
+    pub id: i64,
+    pub timestamp: u64,
+    pub key: String,
+    pub modifiers: Vec<String>,
+    pub window_title: Option<String>,
+    pub application: Option<String>,
+}
+
+/// Evento cujo texto casou com uma busca por expressão regular
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegexMatch {
+    pub id: i64,
+    pub timestamp: u64,
+    pub content: String,
+    pub application: Option<String>,
+    pub window_title: Option<String>,
+    /// Trechos encontrados em `content`
+    pub spans: Vec<MatchSpan>,
+}
+
+/// Posição de um trecho encontrado, em bytes UTF-8 (`start..end`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Limites e volume de uma sessão de digitação
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub started_at: u64,
+    pub ended_at: u64,
+    pub event_count: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     pub id: i64,
     pub content: String,
+    /// Milissegundos desde a época
     pub timestamp: u64,
+    /// `timestamp` em ISO-8601, para exibição no frontend
+    #[serde(default)]
+    pub timestamp_iso: String,
     pub relevance_score: f64,
     pub context: Option<String>,
+    /// Trecho do conteúdo em torno do termo encontrado, com delimitadores de destaque
+    pub highlight: Option<String>,
+    pub window_title: Option<String>,
+    /// Eventos da mesma sessão antes do resultado, com `context_size` > 0
+    #[serde(default)]
+    pub context_before: Option<Vec<StoredEvent>>,
+    #[serde(default)]
+    pub context_after: Option<Vec<StoredEvent>>,
+}
+
+impl SearchResult {
+    pub fn timestamp_datetime(&self) -> DateTime<Utc> {
+        millis_to_datetime(self.timestamp)
+    }
+}
+
+/// Eventos vizinhos de um evento na mesma sessão, em ordem cronológica
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventContext {
+    pub before: Vec<StoredEvent>,
+    pub after: Vec<StoredEvent>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,21 +150,133 @@ pub struct DatabaseStats {
     pub total_size_bytes: i64,
     pub oldest_event: Option<u64>,
     pub newest_event: Option<u64>,
+    /// Bytes ocupados pela tabela de eventos e seus índices
+    pub events_bytes: i64,
+    /// Bytes ocupados pelos embeddings e seus índices
+    pub embeddings_bytes: i64,
+    /// Bytes ocupados pelas tabelas internas do índice FTS5
+    pub fts_bytes: i64,
+    /// Quantidade de eventos por aplicação, da mais frequente para a menos
+    pub events_by_application: Vec<ApplicationCount>,
+}
+
+/// Eventos em um intervalo: quantos são, o período coberto e de quais aplicações
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSummary {
+    pub event_count: i64,
+    pub oldest_event: Option<u64>,
+    pub newest_event: Option<u64>,
+    pub events_by_application: Vec<ApplicationCount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplicationCount {
+    pub application: Option<String>,
+    pub event_count: i64,
+}
+
+/// Modelo que gera os embeddings armazenados
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingModelInfo {
+    pub name: String,
+    pub dimension: usize,
+}
+
+/// Estado do índice semântico em relação ao modelo ativo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingInfo {
+    pub model_name: Option<String>,
+    pub dimension: Option<usize>,
+    /// Eventos com embedding compatível com o modelo ativo
+    pub indexed_events: i64,
+    /// Embeddings de outro modelo ou dimensão, regerados sob demanda
+    pub stale_embeddings: i64,
 }
 
+/// Chave da configuração do agente na tabela `config`
+const AGENT_CONFIG_KEY: &str = "agent";
+
+/// Conexões de leitura mantidas no pool (WAL permite leituras concorrentes)
+const READ_POOL_SIZE: u32 = 4;
+
+/// Máximo de eventos gravados por transação em store_events
+const STORE_CHUNK_SIZE: usize = 5_000;
+
+/// Máximo de eventos removidos por transação na limpeza por retenção
+const RETENTION_BATCH_SIZE: usize = 5_000;
+
+/// Banco SQLite com um escritor serializado e um pool de conexões somente leitura
 pub struct Database {
-    connection: Arc<Mutex<Connection>>,
+    writer: Arc<Mutex<Connection>>,
+    readers: Pool<SqliteConnectionManager>,
+    key: Option<String>,
+    /// Modelo ativo; `store_embedding` recusa vetores de outra dimensão
+    embedding_model: std::sync::RwLock<Option<EmbeddingModelInfo>>,
+}
+
+/// Normaliza os scores BM25 para 0..1 relativo ao melhor resultado retornado
+fn normalize_relevance(results: &mut [SearchResult]) {
+    let max_score = results
+        .iter()
+        .map(|r| r.relevance_score)
+        .fold(0.0_f64, f64::max);
+
+    for result in results.iter_mut() {
+        result.relevance_score = if max_score > 0.0 {
+            (result.relevance_score / max_score).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Converte a consulta do usuário numa expressão MATCH segura para o FTS5.
+///
+/// No modo padrão cada termo vira uma frase entre aspas (com aspas internas
+/// duplicadas), de modo que `"`, `*`, `:`, `-`, `^` e `+` são tratados como
+/// texto. No modo avançado a consulta é repassada como está, permitindo
+/// operadores do FTS5 (`AND`, `OR`, `NEAR`, prefixos `*` etc.).
+/// Retorna `None` quando não há termos a buscar.
+pub fn build_fts_query(query: &str, advanced: bool) -> Option<String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return None;
+    }
+
+    if advanced {
+        return Some(query.to_string());
+    }
+
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+
+    Some(terms.join(" "))
 }
 
 impl Database {
     pub async fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        // SQLCipher key is temporarily disabled for debugging - use with_key when
+        // the key comes from secure storage
+        Self::open(db_path, None).await
+    }
+
+    /// Abre (ou cria) o banco criptografado com a chave SQLCipher informada
+    pub async fn with_key<P: AsRef<Path>>(db_path: P, key: &str) -> Result<Self> {
+        Self::open(db_path, Some(key.to_string())).await
+    }
+
+    async fn open<P: AsRef<Path>>(db_path: P, key: Option<String>) -> Result<Self> {
         info!("🗄️ Inicializando banco de dados: {:?}", db_path.as_ref());
         
-        let conn = Connection::open(db_path)?;
+        let path = db_path.as_ref().to_path_buf();
+        let conn = Connection::open(&path)?;
         
-        // Set SQLCipher password (in production, this should come from secure storage)
-        // Temporarily disabled for debugging - uncomment when SQLCipher is properly configured
-        // conn.execute("PRAGMA key = 'keyai-desktop-secret-key'", [])?;
+        // The key must be set before any other statement touches the file
+        if let Some(ref key) = key {
+            conn.pragma_update(None, "key", key)?;
+        }
         
         // Enable WAL mode for better concurrency
         let _: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
@@ -62,222 +289,460 @@ impl Database {
         conn.execute("PRAGMA cache_size = 10000", [])?;
         conn.execute("PRAGMA temp_store = MEMORY", [])?;
         
+        let writer = Arc::new(Mutex::new(conn));
+        Self::initialize_schema(&writer).await?;
+
+        // Readers open after the schema exists and WAL is enabled on the file
+        let reader_key = key.clone();
+        let manager = SqliteConnectionManager::file(&path).with_init(move |conn| {
+            if let Some(ref key) = reader_key {
+                conn.pragma_update(None, "key", key)?;
+            }
+            conn.execute_batch(
+                "PRAGMA query_only = ON;
+                 PRAGMA cache_size = 10000;
+                 PRAGMA temp_store = MEMORY;"
+            )
+        });
+        let readers = Pool::builder().max_size(READ_POOL_SIZE).build(manager)?;
+
         let database = Self {
-            connection: Arc::new(Mutex::new(conn)),
+            writer,
+            readers,
+            key,
+            embedding_model: std::sync::RwLock::new(None),
         };
         
-        database.initialize_schema().await?;
-        
         info!("✅ Banco de dados inicializado com sucesso");
         Ok(database)
     }
 
-    async fn initialize_schema(&self) -> Result<()> {
-        let conn = self.connection.lock().await;
+    async fn initialize_schema(writer: &Mutex<Connection>) -> Result<()> {
+        let conn = writer.lock().await;
         
-        // Create events table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS events (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                timestamp INTEGER NOT NULL,
-                key TEXT NOT NULL,
-                event_type TEXT NOT NULL,
-                window_title TEXT,
-                application TEXT,
-                text_content TEXT,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                UNIQUE(timestamp, key, event_type)
-            )",
-            [],
-        )?;
+        let version = migrations::run(&conn)?;
 
-        // Create text_content table for full-text search
-        conn.execute(
-            "CREATE VIRTUAL TABLE IF NOT EXISTS text_search USING fts5(
-                content,
-                timestamp,
-                application,
-                window_title,
-                content='events',
-                content_rowid='id'
-            )",
-            [],
-        )?;
+        debug!("✅ Schema do banco de dados inicializado (versão {})", version);
+        Ok(())
+    }
 
-        // Create embeddings table for semantic search
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS embeddings (
-                id INTEGER PRIMARY KEY,
-                event_id INTEGER NOT NULL,
-                embedding BLOB NOT NULL,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (event_id) REFERENCES events (id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
+    /// Obtém uma conexão somente leitura do pool, sem disputar o lock do escritor
+    fn reader(&self) -> Result<ReadConnection> {
+        Ok(ReadConnection::new(self.readers.get()?))
+    }
 
-        // Create indexes for performance
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events (timestamp)",
-            [],
+    #[instrument(skip_all, fields(events = events.len()))]
+    pub async fn store_events(&self, events: &[KeyEvent]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.writer.lock().await;
+
+        // Large imports are split so a single transaction doesn't grow unbounded
+        for chunk in events.chunks(STORE_CHUNK_SIZE) {
+            let tx = conn.unchecked_transaction()?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT OR IGNORE INTO events 
+                    (timestamp, key, event_type, window_title, application, text_content, session_id, modifiers)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+                )?;
+
+                for event in chunk {
+                    // Reconstruct text content from key events; pastes carry the whole text
+                    let text_content = if event.event_type == PASTE_EVENT_TYPE
+                        || (event.key.len() == 1 && event.event_type == "press")
+                    {
+                        Some(event.key.as_str())
+                    } else {
+                        None
+                    };
+
+                    let window_title = event.window_info.as_ref().map(|w| w.title.as_str());
+                    let application = event.window_info.as_ref().map(|w| w.application.as_str());
+
+                    stmt.execute(params![
+                        event.timestamp,
+                        event.key,
+                        event.event_type,
+                        window_title,
+                        application,
+                        text_content,
+                        event.session_id,
+                        encode_modifiers(&event.modifiers)
+                    ])?;
+                }
+            }
+            tx.commit()?;
+        }
+
+        debug!("✅ {} eventos armazenados no banco de dados", events.len());
+        Ok(())
+    }
+
+    pub async fn search_text(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        let options = SearchOptions {
+            limit,
+            ..Default::default()
+        };
+        self.search_text_with_options(query, &options).await
+    }
+
+    pub async fn search_text_with_options(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>> {
+        let Some(filter) = TextSearchFilter::new(query, options)? else {
+            debug!("🔍 Consulta vazia - nenhuma busca executada");
+            return Ok(Vec::new());
+        };
+
+        let conn = self.reader()?;
+        
+        // Column order: content, timestamp, application, window_title
+        let mut stmt = conn.prepare(&format!(
+            "SELECT e.id, e.text_content, e.timestamp, 
+                    bm25(text_search, ?7, 0.0, ?8, ?9) AS score, e.application, e.window_title,
+                    snippet(text_search, 0, ?10, ?11, '…', ?12)
+             FROM text_search ts
+             JOIN events e ON e.id = ts.rowid
+             WHERE {}
+             ORDER BY score, e.timestamp DESC, e.id
+             LIMIT ?5 OFFSET ?6",
+            TEXT_SEARCH_FILTER
+        ))?;
+
+        let weights = &options.column_weights;
+        // snippet() accepts at most 64 tokens
+        let snippet_tokens = options.snippet_tokens.clamp(1, 64) as i64;
+        let rows = stmt.query_map(
+            params![
+                filter.match_expr,
+                filter.title_pattern,
+                filter.exact_terms,
+                filter.event_types,
+                options.limit,
+                options.offset,
+                weights.content,
+                weights.application,
+                weights.window_title,
+                options.highlight_start,
+                options.highlight_end,
+                snippet_tokens
+            ],
+            |row| {
+                let timestamp: u64 = row.get(2)?;
+                Ok(SearchResult {
+                    id: row.get(0)?,
+                    content: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    timestamp,
+                    timestamp_iso: millis_to_iso(timestamp),
+                    // bm25() is negative, lower is better
+                    relevance_score: -row.get::<_, f64>(3)?,
+                    context: row.get::<_, Option<String>>(4)?,
+                    highlight: row.get::<_, Option<String>>(6)?.filter(|h| !h.is_empty()),
+                    window_title: row.get(5)?,
+                    context_before: None,
+                    context_after: None,
+                })
+            },
         )?;
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_events_application ON events (application)",
-            [],
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        normalize_relevance(&mut results);
+
+        if options.context_size > 0 {
+            let window = options.context_size.min(MAX_CONTEXT_SIZE);
+            for result in &mut results {
+                let context = event_context(&conn, result.id, window)?;
+                result.context_before = Some(context.before);
+                result.context_after = Some(context.after);
+            }
+        }
+
+        debug!("🔍 Busca textual retornou {} resultados", results.len());
+        Ok(results)
+    }
+
+    /// Total de eventos que `search_text_with_options` encontraria sem `limit`
+    /// e `offset`, para a paginação
+    pub async fn count_text_matches(&self, query: &str, options: &SearchOptions) -> Result<usize> {
+        let Some(filter) = TextSearchFilter::new(query, options)? else {
+            return Ok(0);
+        };
+
+        let conn = self.reader()?;
+        let count: i64 = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM text_search ts
+                 JOIN events e ON e.id = ts.rowid
+                 WHERE {}",
+                TEXT_SEARCH_FILTER
+            ),
+            params![filter.match_expr, filter.title_pattern, filter.exact_terms, filter.event_types],
+            |row| row.get(0),
         )?;
+        Ok(count as usize)
+    }
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_embeddings_event_id ON embeddings (event_id)",
-            [],
+    /// Sugere termos do vocabulário FTS5 que começam com o prefixo, ordenados por frequência
+    pub async fn suggest_terms(&self, prefix: &str, limit: usize) -> Result<Vec<String>> {
+        // unicode61 stores terms lowercased
+        let prefix = prefix.trim().to_lowercase();
+        if prefix.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT term FROM text_search_vocab
+             WHERE col = 'content' AND substr(term, 1, length(?1)) = ?1
+             ORDER BY cnt DESC, term
+             LIMIT ?2"
         )?;
 
-        // Create triggers to keep FTS5 table in sync
+        let terms = stmt
+            .query_map(params![prefix, limit], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        debug!("💡 {} termos sugeridos para o prefixo: {}", terms.len(), prefix);
+        Ok(terms)
+    }
+
+    /// Registra uma consulta executada no log de buscas
+    pub async fn log_search(&self, query: &str, timestamp: u64) -> Result<()> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.writer.lock().await;
         conn.execute(
-            "CREATE TRIGGER IF NOT EXISTS events_ai AFTER INSERT ON events BEGIN
-                INSERT INTO text_search(rowid, content, timestamp, application, window_title)
-                VALUES (new.id, new.text_content, new.timestamp, new.application, new.window_title);
-            END",
-            [],
+            "INSERT INTO search_log (query, timestamp) VALUES (?1, ?2)",
+            params![query, timestamp],
         )?;
+        Ok(())
+    }
 
-        conn.execute(
-            "CREATE TRIGGER IF NOT EXISTS events_ad AFTER DELETE ON events BEGIN
-                INSERT INTO text_search(text_search, rowid, content, timestamp, application, window_title)
-                VALUES ('delete', old.id, old.text_content, old.timestamp, old.application, old.window_title);
-            END",
-            [],
+    /// Consultas mais frequentes registradas a partir de `since` (timestamp em segundos)
+    pub async fn popular_searches(&self, since: u64, limit: usize) -> Result<Vec<String>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT query FROM search_log
+             WHERE timestamp >= ?1
+             GROUP BY query
+             ORDER BY COUNT(*) DESC, MAX(timestamp) DESC
+             LIMIT ?2"
         )?;
 
-        conn.execute(
-            "CREATE TRIGGER IF NOT EXISTS events_au AFTER UPDATE ON events BEGIN
-                INSERT INTO text_search(text_search, rowid, content, timestamp, application, window_title)
-                VALUES ('delete', old.id, old.text_content, old.timestamp, old.application, old.window_title);
-                INSERT INTO text_search(rowid, content, timestamp, application, window_title)
-                VALUES (new.id, new.text_content, new.timestamp, new.application, new.window_title);
-            END",
-            [],
+        let queries = stmt
+            .query_map(params![since, limit], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(queries)
+    }
+
+    /// Remove todo o histórico de buscas, retornando quantas entradas foram apagadas
+    pub async fn clear_search_log(&self) -> Result<usize> {
+        let conn = self.writer.lock().await;
+        let removed = conn.execute("DELETE FROM search_log", [])?;
+        info!("🗑️ {} entradas do log de buscas removidas", removed);
+        Ok(removed)
+    }
+
+    /// Lista as sessões de digitação, da mais recente para a mais antiga
+    pub async fn list_sessions(&self) -> Result<Vec<SessionSummary>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT session_id, MIN(timestamp), MAX(timestamp), COUNT(*)
+             FROM events
+             WHERE session_id IS NOT NULL
+             GROUP BY session_id
+             ORDER BY MIN(timestamp) DESC"
         )?;
 
-        debug!("✅ Schema do banco de dados inicializado");
-        Ok(())
+        let sessions = stmt
+            .query_map([], |row| {
+                Ok(SessionSummary {
+                    session_id: row.get(0)?,
+                    started_at: row.get(1)?,
+                    ended_at: row.get(2)?,
+                    event_count: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(sessions)
     }
 
-    pub async fn store_events(&self, events: &[KeyEvent]) -> Result<()> {
-        if events.is_empty() {
-            return Ok(());
+    /// Busca as vezes em que `key` foi pressionada com exatamente os modificadores informados
+    pub async fn search_shortcuts(&self, modifiers: &[String], key: &str, limit: usize) -> Result<Vec<ShortcutMatch>> {
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(anyhow!("Tecla do atalho não informada"));
         }
 
-        let conn = self.connection.lock().await;
-        let tx = conn.unchecked_transaction()?;
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, timestamp, key, modifiers, window_title, application
+             FROM events
+             WHERE key = ?1 COLLATE NOCASE
+               AND event_type = 'press'
+               AND modifiers IS ?2
+             ORDER BY timestamp DESC
+             LIMIT ?3"
+        )?;
 
-        for event in events {
-            // Reconstruct text content from key events
-            let text_content = if event.key.len() == 1 && event.event_type == "press" {
-                Some(event.key.clone())
-            } else {
-                None
-            };
+        let matches = stmt
+            .query_map(params![key, encode_modifiers(modifiers), limit], |row| {
+                let modifiers: Option<String> = row.get(3)?;
+                Ok(ShortcutMatch {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    key: row.get(2)?,
+                    modifiers: decode_modifiers(modifiers.as_deref()),
+                    window_title: row.get(4)?,
+                    application: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
 
-            let (window_title, application) = if let Some(ref window_info) = event.window_info {
-                (Some(window_info.title.clone()), Some(window_info.application.clone()))
-            } else {
-                (None, None)
+        debug!("⌨️ Busca por atalho retornou {} resultados", matches.len());
+        Ok(matches)
+    }
+
+    /// Varre o texto dos eventos do intervalo (opcionalmente de uma aplicação) com
+    /// `regex`, do mais recente para o mais antigo. A varredura é linear, por isso
+    /// é interrompida com erro ao passar de `deadline`
+    pub async fn search_regex(
+        &self,
+        regex: &Regex,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        application: Option<&str>,
+        limit: usize,
+        deadline: Instant,
+    ) -> Result<Vec<RegexMatch>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, timestamp, text_content, application, window_title
+             FROM events
+             WHERE timestamp BETWEEN ?1 AND ?2
+               AND (?3 IS NULL OR application = ?3)
+               AND text_content IS NOT NULL
+             ORDER BY timestamp DESC, id DESC"
+        )?;
+        let mut rows = stmt.query(params![sql_timestamp(start_timestamp), sql_timestamp(end_timestamp), application])?;
+
+        let mut matches = Vec::new();
+        while matches.len() < limit {
+            let Some(row) = rows.next()? else {
+                break;
             };
+            if Instant::now() >= deadline {
+                return Err(anyhow!("Busca por expressão regular excedeu o tempo limite"));
+            }
 
-            tx.execute(
-                "INSERT OR IGNORE INTO events 
-                (timestamp, key, event_type, window_title, application, text_content)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                params![
-                    event.timestamp,
-                    event.key,
-                    event.event_type,
-                    window_title,
-                    application,
-                    text_content
-                ],
-            )?;
-        }
-
-        tx.commit()?;
-        debug!("✅ {} eventos armazenados no banco de dados", events.len());
-        Ok(())
+            let content: String = row.get(2)?;
+            let spans: Vec<MatchSpan> = regex
+                .find_iter(&content)
+                .filter(|m| !m.is_empty())
+                .map(|m| MatchSpan { start: m.start(), end: m.end() })
+                .collect();
+            if spans.is_empty() {
+                continue;
+            }
+
+            matches.push(RegexMatch {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                content,
+                application: row.get(3)?,
+                window_title: row.get(4)?,
+                spans,
+            });
+        }
+
+        debug!("🔣 Busca por expressão regular retornou {} resultados", matches.len());
+        Ok(matches)
     }
 
-    pub async fn search_text(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
-        let conn = self.connection.lock().await;
+    /// Até `window` eventos antes e depois de `id`, na mesma sessão. Liberações
+    /// de tecla ficam de fora, como nas buscas
+    pub async fn get_event_context(&self, id: i64, window: usize) -> Result<EventContext> {
+        let conn = self.reader()?;
+        event_context(&conn, id, window.min(MAX_CONTEXT_SIZE))
+    }
+
+    /// Todos os eventos do período, de qualquer tipo (usado também na exportação)
+    pub async fn search_by_timerange(&self, start_timestamp: u64, end_timestamp: u64, limit: usize) -> Result<Vec<StoredEvent>> {
+        self.search_by_timerange_filtered(start_timestamp, end_timestamp, limit, None).await
+    }
+
+    /// Eventos do período restritos a `event_types` (ex.: `["release"]`);
+    /// `None` não filtra por tipo
+    pub async fn search_by_timerange_filtered(
+        &self,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        limit: usize,
+        event_types: Option<&[String]>,
+    ) -> Result<Vec<StoredEvent>> {
+        let event_types = event_types.map(serde_json::to_string).transpose()?;
+        let conn = self.reader()?;
         
         let mut stmt = conn.prepare(
-            "SELECT e.id, e.text_content, e.timestamp, 
-                    rank, e.application, e.window_title
-             FROM text_search ts
-             JOIN events e ON e.id = ts.rowid
-             WHERE text_search MATCH ?1
-             ORDER BY rank
-             LIMIT ?2"
+            "SELECT id, timestamp, key, event_type, window_title, application, text_content, created_at, session_id, modifiers
+             FROM events
+             WHERE timestamp BETWEEN ?1 AND ?2
+               AND (?4 IS NULL OR event_type IN (SELECT value FROM json_each(?4)))
+             ORDER BY timestamp DESC
+             LIMIT ?3"
         )?;
 
-        let rows = stmt.query_map(params![query, limit], |row| {
-            Ok(SearchResult {
-                id: row.get(0)?,
-                content: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
-                timestamp: row.get(2)?,
-                relevance_score: row.get::<_, f64>(3)?,
-                context: row.get::<_, Option<String>>(4)?,
-            })
-        })?;
+        let rows = stmt.query_map(
+            params![sql_timestamp(start_timestamp), sql_timestamp(end_timestamp), limit, event_types],
+            stored_event_from_row,
+        )?;
 
         let mut results = Vec::new();
         for row in rows {
             results.push(row?);
         }
 
-        debug!("🔍 Busca textual retornou {} resultados para: {}", results.len(), query);
+        debug!("📅 Busca por período retornou {} resultados", results.len());
         Ok(results)
     }
 
-    pub async fn search_by_timerange(&self, start_timestamp: u64, end_timestamp: u64, limit: usize) -> Result<Vec<StoredEvent>> {
-        let conn = self.connection.lock().await;
-        
+    /// Eventos com `id > last_id`, em ordem crescente de id. Serve de cursor
+    /// para o feed incremental: ao contrário do timestamp, o id é único, então
+    /// paginar pelo último id visto não repete nem pula eventos
+    pub async fn events_since(&self, last_id: i64, limit: usize) -> Result<Vec<StoredEvent>> {
+        let conn = self.reader()?;
+
         let mut stmt = conn.prepare(
-            "SELECT id, timestamp, key, event_type, window_title, application, text_content, created_at
+            "SELECT id, timestamp, key, event_type, window_title, application, text_content, created_at, session_id, modifiers
              FROM events
-             WHERE timestamp BETWEEN ?1 AND ?2
-             ORDER BY timestamp DESC
-             LIMIT ?3"
+             WHERE id > ?1
+             ORDER BY id ASC
+             LIMIT ?2"
         )?;
 
-        let rows = stmt.query_map(params![start_timestamp, end_timestamp, limit], |row| {
-            Ok(StoredEvent {
-                id: row.get(0)?,
-                timestamp: row.get(1)?,
-                key: row.get(2)?,
-                event_type: row.get(3)?,
-                window_title: row.get(4)?,
-                application: row.get(5)?,
-                text_content: row.get(6)?,
-                created_at: row.get(7)?,
-            })
-        })?;
+        let rows = stmt.query_map(params![last_id, limit], stored_event_from_row)?;
 
         let mut results = Vec::new();
         for row in rows {
             results.push(row?);
         }
 
-        debug!("📅 Busca por período retornou {} resultados", results.len());
+        debug!("🆕 {} eventos novos após o id {}", results.len(), last_id);
         Ok(results)
     }
 
     pub async fn get_stats(&self) -> Result<DatabaseStats> {
-        let conn = self.connection.lock().await;
+        let conn = self.reader()?;
         
-        let total_events: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM events",
-            [],
-            |row| row.get(0)
-        )?;
+        let summary = summarize_events(&conn, 0, u64::MAX, None)?;
 
         let total_size_bytes: i64 = conn.query_row(
             "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
@@ -285,32 +750,37 @@ impl Database {
             |row| row.get(0)
         ).unwrap_or(0);
 
-        let oldest_event: Option<u64> = conn.query_row(
-            "SELECT MIN(timestamp) FROM events",
-            [],
-            |row| row.get(0)
-        ).ok();
-
-        let newest_event: Option<u64> = conn.query_row(
-            "SELECT MAX(timestamp) FROM events",
-            [],
-            |row| row.get(0)
-        ).ok();
+        let (events_bytes, embeddings_bytes, fts_bytes) = storage_breakdown(&conn).unwrap_or_else(|e| {
+            debug!("📊 dbstat indisponível, detalhamento por tabela omitido: {}", e);
+            (0, 0, 0)
+        });
 
         Ok(DatabaseStats {
-            total_events,
+            total_events: summary.event_count,
             total_size_bytes,
-            oldest_event,
-            newest_event,
+            oldest_event: summary.oldest_event,
+            newest_event: summary.newest_event,
+            events_bytes,
+            embeddings_bytes,
+            fts_bytes,
+            events_by_application: summary.events_by_application,
         })
     }
 
+    /// Resume os eventos que `delete_events` removeria, sem remover nada
+    pub async fn preview_delete_events(&self, start_timestamp: u64, end_timestamp: u64, application: Option<&str>) -> Result<EventSummary> {
+        let conn = self.reader()?;
+        Ok(summarize_events(&conn, start_timestamp, end_timestamp, application)?)
+    }
+
     pub async fn clear_all_data(&self) -> Result<()> {
-        let conn = self.connection.lock().await;
+        let conn = self.writer.lock().await;
         
         conn.execute("DELETE FROM embeddings", [])?;
         conn.execute("DELETE FROM events", [])?;
         conn.execute("DELETE FROM text_search", [])?;
+        conn.execute("DELETE FROM tokens", [])?;
+        conn.execute("DELETE FROM config WHERE key = ?1", params![tokens::TOKEN_INDEX_KEY])?;
         
         // Vacuum to reclaim space
         conn.execute("VACUUM", [])?;
@@ -319,8 +789,76 @@ impl Database {
         Ok(())
     }
 
+    /// Remove eventos no intervalo de timestamps (inclusivo), opcionalmente só de uma
+    /// aplicação. Embeddings caem por cascata e o índice FTS é atualizado pelos triggers.
+    pub async fn delete_events(&self, start_timestamp: u64, end_timestamp: u64, application: Option<&str>) -> Result<usize> {
+        let conn = self.writer.lock().await;
+
+        let deleted = conn.execute(
+            "DELETE FROM events
+             WHERE timestamp BETWEEN ?1 AND ?2
+               AND (?3 IS NULL OR application = ?3)",
+            params![sql_timestamp(start_timestamp), sql_timestamp(end_timestamp), application],
+        )?;
+
+        info!("🗑️ {} eventos removidos ({}..={}, aplicação: {:?})", deleted, start_timestamp, end_timestamp, application);
+        Ok(deleted)
+    }
+
+    /// Remove eventos com timestamp anterior a `cutoff`. A remoção é feita em lotes,
+    /// liberando o escritor entre eles para não bloquear os flushes do agente.
+    pub async fn delete_older_than(&self, cutoff: u64) -> Result<usize> {
+        self.delete_older_than_in_batches(cutoff, RETENTION_BATCH_SIZE).await
+    }
+
+    async fn delete_older_than_in_batches(&self, cutoff: u64, batch_size: usize) -> Result<usize> {
+        let mut total = 0;
+
+        loop {
+            let deleted = {
+                let conn = self.writer.lock().await;
+                conn.execute(
+                    "DELETE FROM events
+                     WHERE id IN (SELECT id FROM events WHERE timestamp < ?1 LIMIT ?2)",
+                    params![cutoff, batch_size],
+                )?
+            };
+            total += deleted;
+
+            if deleted < batch_size {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+
+        info!("🧹 {} eventos anteriores a {} removidos por retenção", total, cutoff);
+        Ok(total)
+    }
+
+    /// Define o modelo de embeddings ativo
+    pub fn set_embedding_model(&self, model: EmbeddingModelInfo) {
+        info!("🧠 Modelo de embeddings ativo: {} ({} dimensões)", model.name, model.dimension);
+        *self.embedding_model.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(model);
+    }
+
+    pub fn embedding_model(&self) -> Option<EmbeddingModelInfo> {
+        self.embedding_model.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
     pub async fn store_embedding(&self, event_id: i64, embedding: &[f32]) -> Result<()> {
-        let conn = self.connection.lock().await;
+        let model = self.embedding_model();
+        if let Some(model) = &model {
+            if embedding.len() != model.dimension {
+                return Err(anyhow!(
+                    "Embedding com {} dimensões incompatível com o modelo '{}' ({} dimensões)",
+                    embedding.len(),
+                    model.name,
+                    model.dimension
+                ));
+            }
+        }
+
+        let conn = self.writer.lock().await;
         
         // Convert f32 array to bytes
         let embedding_bytes: Vec<u8> = embedding
@@ -329,22 +867,28 @@ impl Database {
             .collect();
 
         conn.execute(
-            "INSERT OR REPLACE INTO embeddings (event_id, embedding) VALUES (?1, ?2)",
-            params![event_id, embedding_bytes],
+            "INSERT OR REPLACE INTO embeddings (event_id, embedding, model_name, dim) VALUES (?1, ?2, ?3, ?4)",
+            params![event_id, embedding_bytes, model.map(|m| m.name), embedding.len()],
         )?;
 
         debug!("🧠 Embedding armazenado para evento {}", event_id);
         Ok(())
     }
 
+    /// Embedding do evento. Com um modelo ativo, vetores de outra dimensão ou
+    /// gerados por outro modelo são ignorados, para que sejam regerados
     pub async fn get_embedding(&self, event_id: i64) -> Result<Option<Vec<f32>>> {
-        let conn = self.connection.lock().await;
+        let model = self.embedding_model();
+        let conn = self.reader()?;
         
         let embedding_bytes: Option<Vec<u8>> = conn.query_row(
-            "SELECT embedding FROM embeddings WHERE event_id = ?1",
-            params![event_id],
+            "SELECT embedding FROM embeddings
+             WHERE event_id = ?1
+               AND (?2 IS NULL OR dim = ?2)
+               AND (?3 IS NULL OR model_name IS NULL OR model_name = ?3)",
+            params![event_id, model.as_ref().map(|m| m.dimension), model.as_ref().map(|m| m.name.as_str())],
             |row| row.get(0)
-        ).ok();
+        ).optional()?;
 
         if let Some(bytes) = embedding_bytes {
             // Convert bytes back to f32 array
@@ -358,22 +902,355 @@ impl Database {
         }
     }
 
-    pub async fn vacuum(&self) -> Result<()> {
-        let conn = self.connection.lock().await;
-        conn.execute("VACUUM", [])?;
+    /// Modelo ativo e quantos embeddings armazenados são (in)compatíveis com ele
+    pub async fn embedding_info(&self) -> Result<EmbeddingInfo> {
+        let model = self.embedding_model();
+        let conn = self.reader()?;
+
+        let (indexed_events, stale_embeddings): (i64, i64) = conn.query_row(
+            "SELECT COALESCE(SUM(matches), 0), COALESCE(SUM(1 - matches), 0) FROM (
+                 SELECT (?1 IS NULL OR dim = ?1)
+                        AND (?2 IS NULL OR model_name IS NULL OR model_name = ?2) AS matches
+                 FROM embeddings
+             )",
+            params![model.as_ref().map(|m| m.dimension), model.as_ref().map(|m| m.name.as_str())],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        Ok(EmbeddingInfo {
+            model_name: model.as_ref().map(|m| m.name.clone()),
+            dimension: model.as_ref().map(|m| m.dimension),
+            indexed_events,
+            stale_embeddings,
+        })
+    }
+
+    /// Grava a configuração do agente para ser restaurada na próxima execução
+    pub async fn save_agent_config(&self, config: &AgentConfig) -> Result<()> {
+        let value = serde_json::to_string(config)?;
+        let conn = self.writer.lock().await;
+        conn.execute(
+            "INSERT INTO config (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+            params![AGENT_CONFIG_KEY, value],
+        )?;
+        debug!("💾 Configuração do agente salva");
+        Ok(())
+    }
+
+    /// Configuração do agente salva anteriormente (`None` se nunca foi salva).
+    /// Campos ausentes, de versões antigas, assumem o valor padrão
+    pub async fn load_agent_config(&self) -> Result<Option<AgentConfig>> {
+        let conn = self.reader()?;
+        let value: Option<String> = conn
+            .query_row(
+                "SELECT value FROM config WHERE key = ?1",
+                params![AGENT_CONFIG_KEY],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(value.map(|value| serde_json::from_str(&value)).transpose()?)
+    }
+
+    pub async fn vacuum(&self) -> Result<()> {
+        let conn = self.writer.lock().await;
+        conn.execute("VACUUM", [])?;
         info!("🧹 Banco de dados otimizado (VACUUM executado)");
         Ok(())
     }
 
-    /// Otimiza os índices FTS5
-    pub async fn optimize_fts_index(&self) -> Result<()> {
-        let conn = self.connection.lock().await;
+    /// Força checkpoint do WAL, truncando o arquivo de log
+    pub async fn checkpoint_wal(&self) -> Result<()> {
+        let conn = self.writer.lock().await;
+
+        let (busy, log_frames, checkpointed): (i64, i64, i64) = conn.query_row(
+            "PRAGMA wal_checkpoint(TRUNCATE)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        )?;
+
+        if busy != 0 {
+            return Err(anyhow::anyhow!("Checkpoint do WAL bloqueado por outra conexão"));
+        }
+
+        debug!("💾 Checkpoint do WAL concluído: {}/{} frames", checkpointed, log_frames);
+        Ok(())
+    }
+
+    /// Gera uma cópia consistente do banco em `path`, criptografada com a mesma chave
+    pub async fn backup_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(anyhow!("Arquivo de backup já existe: {:?}", path));
+        }
+
+        // Fold the WAL into the main file so the copy reflects every committed write
+        self.checkpoint_wal().await?;
+
+        let target = path.to_string_lossy().to_string();
+        let conn = self.writer.lock().await;
+
+        match self.key {
+            Some(ref key) => {
+                conn.execute("ATTACH DATABASE ?1 AS backup KEY ?2", params![target, key])?;
+                let exported = conn
+                    .query_row("SELECT sqlcipher_export('backup')", [], |_| Ok(()))
+                    .and_then(|_| {
+                        // sqlcipher_export does not carry the header over; without
+                        // user_version the copy would re-run every migration on open
+                        let version: u32 = conn.query_row("PRAGMA main.user_version", [], |row| row.get(0))?;
+                        conn.pragma_update(Some(rusqlite::DatabaseName::Attached("backup")), "user_version", version)
+                    });
+                conn.execute("DETACH DATABASE backup", [])?;
+                exported?;
+            }
+            None => {
+                conn.execute("VACUUM INTO ?1", params![target])?;
+            }
+        }
+
+        info!("💾 Backup do banco de dados criado em {:?}", path);
+        Ok(())
+    }
+
+    /// Cria o backup `created_at` (ms) em `dir` e mantém apenas os `keep` mais recentes
+    pub async fn backup_into_dir(&self, dir: &Path, created_at: u64, keep: usize) -> Result<BackupInfo> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(backups::backup_file_name(created_at));
+        self.backup_to(&path).await?;
+
+        let removed = prune_backups(dir, keep.max(1))?;
+        if !removed.is_empty() {
+            debug!("🗑️ {} backup(s) antigo(s) removido(s)", removed.len());
+        }
+
+        Ok(BackupInfo {
+            path: path.to_string_lossy().to_string(),
+            created_at,
+            size_bytes: std::fs::metadata(&path)?.len(),
+        })
+    }
+
+    /// Substitui o conteúdo do banco pelo backup em `path`, via API de backup online.
+    ///
+    /// O backup precisa ter a mesma chave do banco atual. Quem chama deve parar
+    /// o agente antes, para que nenhum evento seja gravado durante a cópia
+    pub async fn restore_from<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let source = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        if let Some(ref key) = self.key {
+            source.pragma_update(None, "key", key)?;
+        }
+        // Fail before touching the live database on a wrong key or a non-database file
+        source.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))?;
+        // ...and on a backup from a newer version, which migrations::run would
+        // only reject after it had replaced the live database
+        let backup_version = migrations::current_version(&source)?;
+        let latest = migrations::latest_version();
+        if backup_version > latest {
+            return Err(anyhow!(
+                "Backup na versão {} é mais novo que a versão suportada ({})",
+                backup_version,
+                latest
+            ));
+        }
+
+        let mut conn = self.writer.lock().await;
+        Backup::new(&source, &mut conn)?.run_to_completion(256, std::time::Duration::ZERO, None)?;
+
+        // Backups from older versions are brought up to the current schema
+        let version = migrations::run(&conn)?;
+
+        info!("♻️ Banco de dados restaurado de {:?} (versão {})", path, version);
+        Ok(())
+    }
+
+    /// Otimiza o índice FTS5, mesclando seus segmentos.
+    ///
+    /// Bem mais barato que `vacuum()`, que reescreve o arquivo inteiro
+    pub async fn optimize_fts(&self) -> Result<()> {
+        let conn = self.writer.lock().await;
         
         conn.execute("INSERT INTO text_search(text_search) VALUES('optimize')", [])?;
         info!("✅ Índices FTS5 otimizados");
         
         Ok(())
     }
+
+    /// Reconstrói o índice FTS5 a partir de `events`, para quando ele deixou de
+    /// refletir a tabela (ex.: escrita interrompida ou importação manual)
+    pub async fn rebuild_fts_index(&self) -> Result<()> {
+        let conn = self.writer.lock().await;
+
+        conn.execute("INSERT INTO text_search(text_search) VALUES('rebuild')", [])?;
+        info!("✅ Índice FTS5 reconstruído");
+
+        Ok(())
+    }
+}
+
+/// Termos que devem aparecer literalmente no texto na busca sensível a acentos.
+/// `lower()` do SQLite só converte ASCII, então maiúsculas acentuadas seguem
+/// precisando casar exatamente
+fn exact_search_terms(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .map(|term| term.trim_matches('"').to_lowercase())
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+/// Padrão `LIKE` que casa `needle` como substring literal (escapa `%`, `_` e `\`)
+fn like_contains_pattern(needle: &str) -> String {
+    let escaped = needle
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Até `window` eventos pesquisáveis antes e depois de `id`, na mesma sessão
+fn event_context(conn: &Connection, id: i64, window: usize) -> Result<EventContext> {
+    let event_types = serde_json::to_string(DEFAULT_SEARCH_EVENT_TYPES)?;
+    // `session_id IS` also groups legacy events without a session
+    let neighbours = |comparison: &str, order: &str| -> rusqlite::Result<Vec<StoredEvent>> {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT id, timestamp, key, event_type, window_title, application, text_content, created_at, session_id, modifiers
+             FROM events
+             WHERE id {} ?1
+               AND session_id IS (SELECT session_id FROM events WHERE id = ?1)
+               AND event_type IN (SELECT value FROM json_each(?3))
+             ORDER BY id {}
+             LIMIT ?2",
+            comparison, order
+        ))?;
+        let rows = stmt.query_map(params![id, window, event_types], stored_event_from_row)?;
+        rows.collect()
+    };
+
+    let mut before = neighbours("<", "DESC")?;
+    before.reverse();
+    let after = neighbours(">", "ASC")?;
+    Ok(EventContext { before, after })
+}
+
+/// Linha de `events` nas colunas selecionadas por `search_by_timerange` e `events_since`
+fn stored_event_from_row(row: &rusqlite::Row) -> rusqlite::Result<StoredEvent> {
+    let timestamp: u64 = row.get(1)?;
+    Ok(StoredEvent {
+        id: row.get(0)?,
+        timestamp,
+        timestamp_iso: millis_to_iso(timestamp),
+        key: row.get(2)?,
+        event_type: row.get(3)?,
+        window_title: row.get(4)?,
+        application: row.get(5)?,
+        text_content: row.get(6)?,
+        created_at: row.get(7)?,
+        session_id: row.get(8)?,
+        modifiers: row.get(9)?,
+    })
+}
+
+/// Condições da busca textual, compartilhadas com a contagem. Parâmetros:
+/// ?1 expressão FTS5, ?2 padrão LIKE do título, ?3 termos exatos e ?4 tipos
+/// de evento (ambos em JSON)
+const TEXT_SEARCH_FILTER: &str = "text_search MATCH ?1
+               AND (?2 IS NULL OR e.window_title LIKE ?2 ESCAPE '\\')
+               AND (?3 IS NULL OR NOT EXISTS (
+                    SELECT 1 FROM json_each(?3) term
+                    WHERE instr(lower(e.text_content), term.value) = 0))
+               AND e.event_type IN (SELECT value FROM json_each(?4))";
+
+struct TextSearchFilter {
+    match_expr: String,
+    title_pattern: Option<String>,
+    exact_terms: Option<String>,
+    event_types: String,
+}
+
+impl TextSearchFilter {
+    /// `None` quando a consulta não tem termos
+    fn new(query: &str, options: &SearchOptions) -> Result<Option<Self>> {
+        let Some(match_expr) = build_fts_query(query, options.advanced) else {
+            return Ok(None);
+        };
+
+        let event_types = match &options.event_types {
+            Some(types) => serde_json::to_string(types)?,
+            None => serde_json::to_string(DEFAULT_SEARCH_EVENT_TYPES)?,
+        };
+
+        // The index folds diacritics, so accent-sensitive searches re-check the raw text
+        let exact_terms = if options.diacritic_insensitive || options.advanced {
+            None
+        } else {
+            Some(serde_json::to_string(&exact_search_terms(query))?)
+        };
+
+        Ok(Some(Self {
+            match_expr,
+            title_pattern: options.window_title_contains.as_deref().map(like_contains_pattern),
+            exact_terms,
+            event_types,
+        }))
+    }
+}
+
+/// Timestamps acima de `i64::MAX` (ex.: `u64::MAX` como "sem limite") não
+/// cabem em um INTEGER do SQLite
+fn sql_timestamp(timestamp: u64) -> i64 {
+    i64::try_from(timestamp).unwrap_or(i64::MAX)
+}
+
+fn summarize_events(conn: &Connection, start_timestamp: u64, end_timestamp: u64, application: Option<&str>) -> rusqlite::Result<EventSummary> {
+    let (start, end) = (sql_timestamp(start_timestamp), sql_timestamp(end_timestamp));
+
+    let (event_count, oldest_event, newest_event): (i64, Option<u64>, Option<u64>) = conn.query_row(
+        "SELECT COUNT(*), MIN(timestamp), MAX(timestamp) FROM events
+         WHERE timestamp BETWEEN ?1 AND ?2
+           AND (?3 IS NULL OR application = ?3)",
+        params![start, end, application],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT application, COUNT(*) FROM events
+         WHERE timestamp BETWEEN ?1 AND ?2
+           AND (?3 IS NULL OR application = ?3)
+         GROUP BY application
+         ORDER BY COUNT(*) DESC, application"
+    )?;
+    let events_by_application = stmt
+        .query_map(params![start, end, application], |row| {
+            Ok(ApplicationCount {
+                application: row.get(0)?,
+                event_count: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(EventSummary {
+        event_count,
+        oldest_event,
+        newest_event,
+        events_by_application,
+    })
+}
+
+/// Bytes de (eventos, embeddings, FTS5) somando as páginas de cada tabela e
+/// dos seus índices, via a tabela virtual `dbstat`
+fn storage_breakdown(conn: &Connection) -> rusqlite::Result<(i64, i64, i64)> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(CASE WHEN s.tbl_name = 'events' THEN d.pgsize END), 0),
+                COALESCE(SUM(CASE WHEN s.tbl_name = 'embeddings' THEN d.pgsize END), 0),
+                COALESCE(SUM(CASE WHEN s.tbl_name LIKE 'text\\_search\\_%' ESCAPE '\\' THEN d.pgsize END), 0)
+         FROM dbstat d
+         JOIN sqlite_master s ON s.name = d.name",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
 }
 
 #[cfg(test)]
@@ -408,6 +1285,8 @@ mod tests {
                 }),
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 1001,
@@ -421,6 +1300,8 @@ mod tests {
                 }),
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 1002,
@@ -434,6 +1315,8 @@ mod tests {
                 }),
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 1003,
@@ -447,6 +1330,8 @@ mod tests {
                 }),
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 1004,
@@ -460,6 +1345,8 @@ mod tests {
                 }),
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
         ];
         
@@ -486,6 +1373,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 2000,
@@ -494,6 +1383,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 3000,
@@ -502,6 +1393,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
         ];
 
@@ -530,6 +1423,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
         ];
 
@@ -561,6 +1456,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
         ];
         db.store_events(&events).await.unwrap();
@@ -596,6 +1493,8 @@ mod tests {
             window_info: None,
             is_modifier: false,
             is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
         };
         
         // Store same event twice
@@ -633,6 +1532,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
         ];
         db.store_events(&events).await.unwrap();
@@ -642,6 +1543,31 @@ mod tests {
         db.vacuum().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_store_embedding_rejects_dimension_mismatch() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        let event_id = insert_with_app(&db, 1, "texto", "editor").await;
+
+        // Vetor gravado antes de existir um modelo ativo
+        db.store_embedding(event_id, &[0.1; 5]).await.unwrap();
+
+        db.set_embedding_model(EmbeddingModelInfo { name: "modelo-384".to_string(), dimension: 384 });
+        assert!(db.store_embedding(event_id, &[0.1; 5]).await.is_err());
+
+        // O vetor antigo é tratado como desatualizado
+        assert!(db.get_embedding(event_id).await.unwrap().is_none());
+        let info = db.embedding_info().await.unwrap();
+        assert_eq!((info.indexed_events, info.stale_embeddings), (0, 1));
+
+        db.store_embedding(event_id, &[0.1; 384]).await.unwrap();
+        assert_eq!(db.get_embedding(event_id).await.unwrap().unwrap().len(), 384);
+        let info = db.embedding_info().await.unwrap();
+        assert_eq!(info.model_name.as_deref(), Some("modelo-384"));
+        assert_eq!(info.dimension, Some(384));
+        assert_eq!((info.indexed_events, info.stale_embeddings), (1, 0));
+    }
+
     #[tokio::test]
     async fn test_get_embedding_nonexistent() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -652,6 +1578,16 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[tokio::test]
+    async fn test_get_embedding_surfaces_query_errors() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        db.writer.lock().await.execute_batch("DROP TABLE embeddings").unwrap();
+
+        // A failed query must not look like a missing embedding
+        assert!(db.get_embedding(1).await.is_err());
+    }
+
     #[tokio::test]
     async fn test_text_search() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -666,6 +1602,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 1001,
@@ -674,6 +1612,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 1002,
@@ -682,6 +1622,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 1003,
@@ -690,6 +1632,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
         ];
         
@@ -710,12 +1654,14 @@ mod tests {
         // Store some events
         for i in 0..5 {
             let event = KeyEvent {
-                timestamp: i as i64,
+                timestamp: i,
                 key: format!("key{}", i),
                 event_type: "press".to_string(),
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             };
             db.store_events(&[event]).await.unwrap();
         }
@@ -740,6 +1686,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
             KeyEvent {
                 timestamp: 1001,
@@ -748,6 +1696,8 @@ mod tests {
                 window_info: None,
                 is_modifier: false,
                 is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
             },
         ];
         
@@ -758,4 +1708,1085 @@ mod tests {
         let stats = db.get_stats().await.unwrap();
         assert_eq!(stats.total_events, 2);
     }
-} 
\ No newline at end of file
+
+    async fn insert_text(db: &Database, timestamp: u64, text: &str) {
+        let conn = db.writer.lock().await;
+        conn.execute(
+            "INSERT INTO events (timestamp, key, event_type, text_content) VALUES (?1, ?2, 'press', ?2)",
+            params![timestamp, text],
+        ).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_count_text_matches_ignores_pagination() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        for i in 0..5 {
+            insert_text(&db, 1_000 + i, &format!("relatorio {}", i)).await;
+        }
+        insert_text(&db, 2_000, "outro assunto").await;
+
+        let mut options = SearchOptions { limit: 2, ..Default::default() };
+        assert_eq!(db.count_text_matches("relatorio", &options).await.unwrap(), 5);
+        assert_eq!(db.count_text_matches("   ", &options).await.unwrap(), 0);
+
+        // Páginas sucessivas cobrem todas as ocorrências sem repetir
+        let mut ids = Vec::new();
+        for offset in [0, 2, 4] {
+            options.offset = offset;
+            ids.extend(db.search_text_with_options("relatorio", &options).await.unwrap().into_iter().map(|r| r.id));
+        }
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+
+        options.window_title_contains = Some("Editor".to_string());
+        assert_eq!(db.count_text_matches("relatorio", &options).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_results_serialize_numeric_and_iso_timestamps() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        insert_text(&db, 1_700_000_000_123, "relatorio").await;
+
+        let result = &db.search_text("relatorio", 10).await.unwrap()[0];
+        assert_eq!(result.timestamp_datetime().timestamp_millis(), 1_700_000_000_123);
+        let json = serde_json::to_value(result).unwrap();
+        assert_eq!(json["timestamp"], 1_700_000_000_123_u64);
+        assert_eq!(json["timestamp_iso"], "2023-11-14T22:13:20.123Z");
+
+        let event = &db.search_by_timerange(0, u64::MAX, 10).await.unwrap()[0];
+        assert_eq!(event.timestamp_datetime(), result.timestamp_datetime());
+        assert_eq!(serde_json::to_value(event).unwrap()["timestamp_iso"], "2023-11-14T22:13:20.123Z");
+    }
+
+    #[test]
+    fn test_build_fts_query() {
+        assert_eq!(build_fts_query("hello world", false), Some("\"hello\" \"world\"".to_string()));
+        assert_eq!(build_fts_query("say \"hi\"", false), Some("\"say\" \"\"\"hi\"\"\"".to_string()));
+        assert_eq!(build_fts_query("a:b -c ^d e*", false), Some("\"a:b\" \"-c\" \"^d\" \"e*\"".to_string()));
+        assert_eq!(build_fts_query("   ", false), None);
+        assert_eq!(build_fts_query("foo* OR bar", true), Some("foo* OR bar".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_search_text_with_special_characters() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_text(&db, 1, "learning C++ today").await;
+        insert_text(&db, 2, "the e-mail said \"urgent\"").await;
+        insert_text(&db, 3, "test+query field a:b").await;
+
+        // Nenhuma dessas consultas deve falhar com erro de sintaxe do FTS5
+        for query in ["C++", "e-mail", "\"urgent", "say \"urgent\"", "test+query", "a:b", "^test", "-", "\""] {
+            assert!(db.search_text(query, 10).await.is_ok(), "consulta falhou: {}", query);
+        }
+
+        let results = db.search_text("C++", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, 1);
+
+        let results = db.search_text("e-mail", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, 2);
+
+        let results = db.search_text("\"urgent\"", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+
+        let results = db.search_text("test+query", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, 3);
+
+        assert!(db.search_text("", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_text_diacritics() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_text(&db, 1, "voo para São Paulo").await;
+
+        let results = db.search_text("sao paulo", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, 1);
+
+        let exact = SearchOptions { diacritic_insensitive: false, ..Default::default() };
+        assert!(db.search_text_with_options("sao paulo", &exact).await.unwrap().is_empty());
+        assert_eq!(db.search_text_with_options("são paulo", &exact).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_text_advanced_mode() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_text(&db, 1, "quarterly report").await;
+        insert_text(&db, 2, "weekly summary").await;
+
+        let options = SearchOptions {
+            advanced: true,
+            ..Default::default()
+        };
+        let results = db.search_text_with_options("quart* OR weekly", &options).await.unwrap();
+        assert_eq!(results.len(), 2);
+
+        // Sem o modo avançado o operador é tratado como texto
+        let results = db.search_text("quart* OR weekly", 10).await.unwrap();
+        assert!(results.is_empty());
+    }
+
+    async fn insert_with_title(db: &Database, timestamp: u64, text: &str, title: &str) {
+        let conn = db.writer.lock().await;
+        conn.execute(
+            "INSERT INTO events (timestamp, key, event_type, text_content, window_title)
+             VALUES (?1, ?2, 'press', ?2, ?3)",
+            params![timestamp, text, title],
+        ).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bm25_column_weights() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_with_title(&db, 1, "budget numbers for march", "Notes").await;
+        insert_with_title(&db, 2, "meeting notes here", "Budget review").await;
+
+        let title_heavy = SearchOptions {
+            column_weights: crate::search::ColumnWeights {
+                content: 1.0,
+                application: 0.5,
+                window_title: 10.0,
+            },
+            ..Default::default()
+        };
+        let results = db.search_text_with_options("budget", &title_heavy).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, 2);
+        assert_eq!(results[0].relevance_score, 1.0);
+        assert!(results[1].relevance_score > 0.0 && results[1].relevance_score < 1.0);
+
+        let content_heavy = SearchOptions {
+            column_weights: crate::search::ColumnWeights {
+                content: 10.0,
+                application: 0.5,
+                window_title: 1.0,
+            },
+            ..Default::default()
+        };
+        let results = db.search_text_with_options("budget", &content_heavy).await.unwrap();
+        assert_eq!(results[0].id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_window_title_filter() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_with_title(&db, 1, "total pending", "Invoice #42 - Editor").await;
+        insert_with_title(&db, 2, "total lunch", "Chat feed").await;
+        insert_with_title(&db, 3, "total 100% done", "invoice_draft").await;
+
+        let options = SearchOptions {
+            window_title_contains: Some("invoice".to_string()),
+            ..Default::default()
+        };
+        let mut ids: Vec<_> = db.search_text_with_options("total", &options).await.unwrap()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 3]);
+
+        // Curingas do LIKE são tratados literalmente
+        let options = SearchOptions {
+            window_title_contains: Some("e_d".to_string()),
+            ..Default::default()
+        };
+        let results = db.search_text_with_options("total", &options).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].window_title.as_deref(), Some("invoice_draft"));
+
+        assert_eq!(db.search_text("total", 10).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_search_highlight_snippet() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_text(&db, 1, "one two three four five six budget seven eight nine ten eleven twelve").await;
+
+        let results = db.search_text("budget", 10).await.unwrap();
+        let highlight = results[0].highlight.as_deref().unwrap();
+        assert!(highlight.contains("<mark>budget</mark>"));
+
+        let options = SearchOptions {
+            snippet_tokens: 3,
+            highlight_start: "[".to_string(),
+            highlight_end: "]".to_string(),
+            ..Default::default()
+        };
+        let results = db.search_text_with_options("budget", &options).await.unwrap();
+        let highlight = results[0].highlight.as_deref().unwrap();
+        assert!(highlight.contains("[budget]"));
+
+        let words = highlight.trim_matches('…').split_whitespace().count();
+        assert_eq!(words, 3);
+        assert!(!highlight.contains("one") && !highlight.contains("twelve"));
+    }
+
+    #[tokio::test]
+    async fn test_legacy_fts_table_is_rebuilt() {
+        let temp_file = NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(temp_file.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE events (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    timestamp INTEGER NOT NULL,
+                    key TEXT NOT NULL,
+                    event_type TEXT NOT NULL,
+                    window_title TEXT,
+                    application TEXT,
+                    text_content TEXT,
+                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    UNIQUE(timestamp, key, event_type)
+                );
+                CREATE VIRTUAL TABLE text_search USING fts5(
+                    content, timestamp, application, window_title,
+                    content='events', content_rowid='id'
+                );
+                INSERT INTO events (timestamp, key, event_type, text_content)
+                VALUES (1, 'k', 'press', 'legacy budget row');"
+            ).unwrap();
+        }
+
+        let db = Database::new(temp_file.path()).await.unwrap();
+        let results = db.search_text("budget", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].highlight.as_deref().unwrap().contains("<mark>budget</mark>"));
+    }
+
+    #[tokio::test]
+    async fn test_suggest_terms_by_frequency() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_text(&db, 1, "send the email now").await;
+        insert_text(&db, 2, "email me later").await;
+        insert_text(&db, 3, "emerald city").await;
+        insert_text(&db, 4, "Email again").await;
+
+        let suggestions = db.suggest_terms("em", 5).await.unwrap();
+        assert_eq!(suggestions.first().map(String::as_str), Some("email"));
+        assert!(suggestions.contains(&"emerald".to_string()));
+        assert!(!suggestions.contains(&"send".to_string()));
+
+        assert_eq!(db.suggest_terms("EM", 1).await.unwrap(), vec!["email".to_string()]);
+        assert!(db.suggest_terms("  ", 5).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_popular_searches_from_log() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        for (query, timestamp) in [("report", 100), ("email", 101), ("report", 102), ("report", 103), ("email", 104), ("old", 10)] {
+            db.log_search(query, timestamp).await.unwrap();
+        }
+        db.log_search("   ", 105).await.unwrap();
+
+        let popular = db.popular_searches(50, 10).await.unwrap();
+        assert_eq!(popular, vec!["report".to_string(), "email".to_string()]);
+
+        // A janela inclui entradas antigas quando começa no zero
+        assert_eq!(db.popular_searches(0, 10).await.unwrap().len(), 3);
+
+        assert_eq!(db.clear_search_log().await.unwrap(), 6);
+        assert!(db.popular_searches(0, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_text_pagination() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        for i in 0..5 {
+            insert_text(&db, 100 + i, "pagina").await;
+        }
+        insert_text(&db, 200, "outro texto").await;
+
+        let mut seen = Vec::new();
+        let mut page_sizes = Vec::new();
+        for page in 0..4 {
+            let options = SearchOptions {
+                limit: 2,
+                offset: page * 2,
+                ..Default::default()
+            };
+            let results = db.search_text_with_options("pagina", &options).await.unwrap();
+            page_sizes.push(results.len());
+            seen.extend(results.into_iter().map(|r| r.id));
+        }
+
+        assert_eq!(page_sizes, vec![2, 2, 1, 0]);
+        let mut unique = seen.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 5, "páginas devem ser disjuntas e completas");
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("source.db");
+        let backup_path = dir.path().join("backup.db");
+        let key = "chave-de-teste";
+
+        let db = Database::with_key(&source_path, key).await.unwrap();
+        let events: Vec<KeyEvent> = (0..5)
+            .map(|i| KeyEvent {
+                timestamp: i,
+                key: format!("k{}", i),
+                event_type: "press".to_string(),
+                window_info: None,
+                is_modifier: false,
+                is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
+            })
+            .collect();
+        db.store_events(&events).await.unwrap();
+
+        db.backup_to(&backup_path).await.unwrap();
+        assert!(db.backup_to(&backup_path).await.is_err(), "não deve sobrescrever backup existente");
+
+        let restored = Database::with_key(&backup_path, key).await.unwrap();
+        assert_eq!(restored.get_stats().await.unwrap().total_events, 5);
+
+        // A cópia continua criptografada
+        assert!(Database::new(&backup_path).await.is_err());
+        assert!(Database::with_key(&backup_path, "chave-errada").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_locked_database_returns_error() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        db.writer.lock().await.busy_timeout(std::time::Duration::from_millis(50)).unwrap();
+
+        // Outra conexão segura o lock de escrita
+        let other = Connection::open(temp_file.path()).unwrap();
+        other.execute_batch("BEGIN EXCLUSIVE").unwrap();
+
+        let events = vec![KeyEvent {
+            timestamp: 1,
+            key: "a".to_string(),
+            event_type: "press".to_string(),
+            window_info: None,
+            is_modifier: false,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
+        }];
+        let error = db.store_events(&events).await.unwrap_err();
+        assert!(error.downcast_ref::<rusqlite::Error>().is_some(), "{}", error);
+
+        // Leituras continuam funcionando e o escritor se recupera quando o lock é liberado
+        assert_eq!(db.get_stats().await.unwrap().total_events, 0);
+        other.execute_batch("ROLLBACK").unwrap();
+        db.store_events(&events).await.unwrap();
+        assert_eq!(db.get_stats().await.unwrap().total_events, 1);
+    }
+
+    #[tokio::test]
+    async fn test_plain_backup_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("plain-backup.db");
+
+        let db = Database::new(dir.path().join("plain.db")).await.unwrap();
+        insert_text(&db, 1, "backup sem chave").await;
+
+        db.backup_to(&backup_path).await.unwrap();
+
+        let restored = Database::new(&backup_path).await.unwrap();
+        assert_eq!(restored.search_text("backup", 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_restore_from_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_dir = dir.path().join("backups");
+        let key = "chave-de-teste";
+
+        let db = Database::with_key(dir.path().join("live.db"), key).await.unwrap();
+        insert_text(&db, 1, "antes do backup").await;
+        let backup = db.backup_into_dir(&backup_dir, 1_000, 3).await.unwrap();
+        assert!(backup.size_bytes > 0);
+
+        insert_text(&db, 2, "depois do backup").await;
+        assert_eq!(db.get_stats().await.unwrap().total_events, 2);
+
+        db.restore_from(&backup.path).await.unwrap();
+        assert_eq!(db.get_stats().await.unwrap().total_events, 1);
+        assert_eq!(db.search_text("antes", 10).await.unwrap().len(), 1);
+        assert!(db.search_text("depois", 10).await.unwrap().is_empty());
+
+        // Backup de outro banco (outra chave) é recusado sem alterar os dados
+        let other = Database::with_key(dir.path().join("other.db"), "outra-chave").await.unwrap();
+        let foreign = other.backup_into_dir(&dir.path().join("foreign"), 2_000, 3).await.unwrap();
+        assert!(db.restore_from(&foreign.path).await.is_err());
+        assert_eq!(db.get_stats().await.unwrap().total_events, 1);
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_newer_backup_before_overwriting() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("live.db")).await.unwrap();
+        insert_text(&db, 1, "dados atuais").await;
+        let backup = db.backup_into_dir(&dir.path().join("backups"), 1_000, 3).await.unwrap();
+        {
+            // Simula um backup feito por uma versão mais nova do aplicativo
+            let conn = Connection::open(&backup.path).unwrap();
+            conn.pragma_update(None, "user_version", migrations::latest_version() + 1).unwrap();
+        }
+        insert_text(&db, 2, "depois do backup").await;
+
+        let error = db.restore_from(&backup.path).await.unwrap_err();
+        assert!(error.to_string().contains("mais novo"), "{}", error);
+        assert_eq!(db.get_stats().await.unwrap().total_events, 2);
+        assert_eq!(migrations::current_version(&*db.writer.lock().await).unwrap(), migrations::latest_version());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_search_timeout_interrupts_running_query() {
+        use crate::commands::{CommandError, SearchRegistry};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Arc::new(Database::new(temp_file.path()).await.unwrap());
+        let interrupted = Arc::new(AtomicBool::new(false));
+
+        let (reader_db, flag) = (db.clone(), interrupted.clone());
+        let result = SearchRegistry::default()
+            .run(None, std::time::Duration::from_millis(100), async move {
+                let conn = reader_db.reader().map_err(|e| CommandError::Internal(e.to_string()))?;
+                // Never finishes unless interrupted
+                let outcome = conn.query_row(
+                    "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c",
+                    [],
+                    |row| row.get::<_, i64>(0),
+                );
+                flag.store(
+                    matches!(outcome, Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::OperationInterrupted),
+                    Ordering::Relaxed,
+                );
+                Ok(())
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code(), "timeout");
+
+        let deadline = Instant::now() + std::time::Duration::from_secs(5);
+        while !interrupted.load(Ordering::Relaxed) {
+            assert!(Instant::now() < deadline, "a consulta continuou rodando após o tempo limite");
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        // The connection went back to the pool without a pending interrupt
+        assert!(db.search_text("qualquer", 10).await.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_parallel_reads_during_large_insert() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Arc::new(Database::new(temp_file.path()).await.unwrap());
+        const BATCHES: u64 = 50;
+        const BATCH_SIZE: u64 = 200;
+
+        let writer_db = db.clone();
+        let writer = tokio::spawn(async move {
+            for batch in 0..BATCHES {
+                let events: Vec<KeyEvent> = (0..BATCH_SIZE)
+                    .map(|i| KeyEvent {
+                        timestamp: batch * BATCH_SIZE + i,
+                        key: "x".to_string(),
+                        event_type: "press".to_string(),
+                        window_info: None,
+                        is_modifier: false,
+                        is_function_key: false,
+                        session_id: None,
+                        modifiers: Vec::new(),
+                    })
+                    .collect();
+                writer_db.store_events(&events).await.unwrap();
+            }
+        });
+
+        let mut readers = Vec::new();
+        for _ in 0..8 {
+            let reader_db = db.clone();
+            readers.push(tokio::spawn(async move {
+                let mut last_seen = 0;
+                for _ in 0..25 {
+                    let stats = reader_db.get_stats().await.unwrap();
+                    // Each read sees a committed snapshot, never a partial batch
+                    assert!(stats.total_events >= last_seen);
+                    assert_eq!(stats.total_events as u64 % BATCH_SIZE, 0);
+                    last_seen = stats.total_events;
+                    reader_db.search_text("x", 5).await.unwrap();
+                }
+            }));
+        }
+
+        for reader in readers {
+            reader.await.unwrap();
+        }
+        writer.await.unwrap();
+
+        let stats = db.get_stats().await.unwrap();
+        assert_eq!(stats.total_events as u64, BATCHES * BATCH_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_read_connections_are_read_only() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        let conn = db.reader().unwrap();
+        assert!(conn.execute("DELETE FROM events", []).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_store_large_batch_with_prepared_statement() {
+        let events: Vec<KeyEvent> = (0..10_000)
+            .map(|i| KeyEvent {
+                timestamp: i,
+                key: "a".to_string(),
+                event_type: "press".to_string(),
+                window_info: Some(crate::agent::WindowInfo {
+                    title: "Bench".to_string(),
+                    application: "bench".to_string(),
+                    process_id: None,
+                    timestamp: i,
+                }),
+                is_modifier: false,
+                is_function_key: false,
+                session_id: None,
+                modifiers: Vec::new(),
+            })
+            .collect();
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        let started = std::time::Instant::now();
+        db.store_events(&events).await.unwrap();
+        let prepared_elapsed = started.elapsed();
+
+        assert_eq!(db.get_stats().await.unwrap().total_events, 10_000);
+        // INSERT OR IGNORE semantics are preserved across chunks
+        db.store_events(&events).await.unwrap();
+        assert_eq!(db.get_stats().await.unwrap().total_events, 10_000);
+
+        // Naive path: re-parse the statement for every row in one transaction
+        let naive_file = NamedTempFile::new().unwrap();
+        let naive_db = Database::new(naive_file.path()).await.unwrap();
+        let started = std::time::Instant::now();
+        {
+            let conn = naive_db.writer.lock().await;
+            let tx = conn.unchecked_transaction().unwrap();
+            for event in &events {
+                tx.execute(
+                    "INSERT OR IGNORE INTO events 
+                    (timestamp, key, event_type, window_title, application, text_content)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![event.timestamp, event.key, event.event_type, "Bench", "bench", event.key],
+                ).unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        let naive_elapsed = started.elapsed();
+
+        // Loose bound only, timing on CI machines is noisy; the benchmark has the real numbers
+        assert!(
+            prepared_elapsed <= naive_elapsed * 3,
+            "prepared {:?} vs naive {:?}", prepared_elapsed, naive_elapsed
+        );
+    }
+
+    async fn insert_with_app(db: &Database, timestamp: u64, text: &str, application: &str) -> i64 {
+        let conn = db.writer.lock().await;
+        conn.execute(
+            "INSERT INTO events (timestamp, key, event_type, text_content, application)
+             VALUES (?1, ?2, 'press', ?2, ?3)",
+            params![timestamp, text, application],
+        ).unwrap();
+        conn.last_insert_rowid()
+    }
+
+    #[tokio::test]
+    async fn test_delete_events_time_window() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        let mut ids = Vec::new();
+        for ts in 1..=5 {
+            ids.push(insert_with_app(&db, ts, &format!("nota{}", ts), "editor").await);
+        }
+        db.store_embedding(ids[2], &[0.5; 4]).await.unwrap();
+
+        assert_eq!(db.delete_events(2, 4, None).await.unwrap(), 3);
+
+        assert_eq!(db.get_stats().await.unwrap().total_events, 2);
+        assert_eq!(db.search_text("nota1", 10).await.unwrap().len(), 1);
+        assert_eq!(db.search_text("nota5", 10).await.unwrap().len(), 1);
+        assert!(db.search_text("nota3", 10).await.unwrap().is_empty());
+        assert!(db.get_embedding(ids[2]).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_delete_events_by_application() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_with_app(&db, 1, "segredo banco", "bank").await;
+        insert_with_app(&db, 2, "lista de compras", "notes").await;
+        insert_with_app(&db, 3, "outro segredo", "bank").await;
+
+        assert_eq!(db.delete_events(0, u64::MAX, Some("bank")).await.unwrap(), 2);
+
+        assert!(db.search_text("segredo", 10).await.unwrap().is_empty());
+        let survivors = db.search_text("compras", 10).await.unwrap();
+        assert_eq!(survivors.len(), 1);
+        assert_eq!(survivors[0].context.as_deref(), Some("notes"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_older_than_prunes_only_old_events() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        for ts in 1..=5 {
+            insert_with_app(&db, ts * 100, &format!("antigo{}", ts), "editor").await;
+        }
+        insert_with_app(&db, 1_000, "recente", "editor").await;
+
+        // Lotes de 2 forçam várias iterações
+        assert_eq!(db.delete_older_than_in_batches(1_000, 2).await.unwrap(), 5);
+
+        assert_eq!(db.get_stats().await.unwrap().total_events, 1);
+        assert_eq!(db.search_text("recente", 10).await.unwrap().len(), 1);
+        assert!(db.search_text("antigo3", 10).await.unwrap().is_empty());
+
+        assert_eq!(db.delete_older_than(1_000).await.unwrap(), 0);
+    }
+
+    /// Grava o evento e o retira do índice, como se os triggers não tivessem rodado
+    async fn insert_unindexed(db: &Database, timestamp: u64, text: &str) {
+        insert_text(db, timestamp, text).await;
+        let conn = db.writer.lock().await;
+        conn.execute(
+            "INSERT INTO text_search(text_search, rowid, content, timestamp, application, window_title)
+             SELECT 'delete', id, content, timestamp, application, window_title
+             FROM events_fts_source WHERE timestamp = ?1",
+            params![timestamp],
+        ).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_fts_index_restores_missing_rows() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_text(&db, 1, "relatorio indexado").await;
+        insert_unindexed(&db, 2, "relatorio perdido").await;
+        assert_eq!(db.search_text("relatorio", 10).await.unwrap().len(), 1);
+        assert!(db.search_text("perdido", 10).await.unwrap().is_empty());
+
+        db.rebuild_fts_index().await.unwrap();
+
+        assert_eq!(db.search_text("relatorio", 10).await.unwrap().len(), 2);
+        let results = db.search_text("perdido", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "relatorio perdido");
+    }
+
+    #[tokio::test]
+    async fn test_optimize_fts_keeps_results() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        // Inserções individuais criam vários segmentos no índice
+        for i in 0..300 {
+            let text = if i % 3 == 0 { format!("relatorio{}", i) } else { format!("nota{}", i) };
+            insert_with_app(&db, i, &text, "editor").await;
+        }
+        insert_with_app(&db, 1_000, "orcamento anual", "planilha").await;
+
+        db.optimize_fts().await.unwrap();
+
+        let results = db.search_text("orcamento", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "orcamento anual");
+        assert_eq!(results[0].context.as_deref(), Some("planilha"));
+        assert_eq!(db.search_text("relatorio150", 10).await.unwrap().len(), 1);
+        assert!(db.search_text("relatorio151", 10).await.unwrap().is_empty());
+
+        // Continua íntegro para novas inserções após a mesclagem
+        insert_with_app(&db, 1_001, "orcamento revisado", "planilha").await;
+        assert_eq!(db.search_text("orcamento", 10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stats_storage_breakdown() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        let mut ids = Vec::new();
+        for i in 0..200 {
+            let application = if i % 4 == 0 { "navegador" } else { "editor" };
+            ids.push(insert_with_app(&db, i, &format!("palavra{} texto de exemplo", i), application).await);
+        }
+
+        let before = db.get_stats().await.unwrap();
+        assert!(before.events_bytes > 0 && before.fts_bytes > 0);
+
+        for id in ids.iter().take(20) {
+            db.store_embedding(*id, &[0.5; 384]).await.unwrap();
+        }
+
+        let stats = db.get_stats().await.unwrap();
+        assert!(stats.embeddings_bytes > before.embeddings_bytes);
+
+        // O restante (search_log, schema, páginas livres) é pequeno
+        let breakdown = stats.events_bytes + stats.embeddings_bytes + stats.fts_bytes;
+        assert!(breakdown <= stats.total_size_bytes);
+        assert!(breakdown * 10 >= stats.total_size_bytes * 7);
+
+        let counts: Vec<_> = stats
+            .events_by_application
+            .iter()
+            .map(|c| (c.application.as_deref(), c.event_count))
+            .collect();
+        assert_eq!(counts, vec![(Some("editor"), 150), (Some("navegador"), 50)]);
+    }
+
+    #[tokio::test]
+    async fn test_preview_delete_events_leaves_data_intact() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_with_app(&db, 100, "extrato", "bank").await;
+        insert_with_app(&db, 200, "saldo", "bank").await;
+        insert_with_app(&db, 300, "rascunho", "editor").await;
+        insert_with_app(&db, 900, "fora", "bank").await;
+
+        let preview = db.preview_delete_events(100, 500, None).await.unwrap();
+        assert_eq!(preview.event_count, 3);
+        assert_eq!((preview.oldest_event, preview.newest_event), (Some(100), Some(300)));
+        let apps: Vec<_> = preview
+            .events_by_application
+            .iter()
+            .map(|c| (c.application.as_deref(), c.event_count))
+            .collect();
+        assert_eq!(apps, vec![(Some("bank"), 2), (Some("editor"), 1)]);
+
+        let bank_only = db.preview_delete_events(0, u64::MAX, Some("bank")).await.unwrap();
+        assert_eq!(bank_only.event_count, 3);
+        assert_eq!(bank_only.newest_event, Some(900));
+
+        let empty = db.preview_delete_events(1_000, 2_000, None).await.unwrap();
+        assert_eq!(empty.event_count, 0);
+        assert_eq!(empty.oldest_event, None);
+        assert!(empty.events_by_application.is_empty());
+
+        // Nada foi removido
+        assert_eq!(db.get_stats().await.unwrap().total_events, 4);
+        assert_eq!(db.search_text("rascunho", 10).await.unwrap().len(), 1);
+
+        assert_eq!(db.delete_events(100, 500, None).await.unwrap(), preview.event_count as usize);
+    }
+
+    #[tokio::test]
+    async fn test_search_regex_digit_pattern() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_with_app(&db, 100, "pin 1234 e 5678", "editor").await;
+        insert_with_app(&db, 200, "sem números aqui", "editor").await;
+        insert_with_app(&db, 300, "ano 2024", "editor").await;
+        insert_with_app(&db, 400, "código 9999", "terminal").await;
+
+        let regex = Regex::new(r"\d{4}").unwrap();
+        let deadline = Instant::now() + std::time::Duration::from_secs(5);
+
+        let results = db.search_regex(&regex, 0, 1_000, Some("editor"), 10, deadline).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].content, "ano 2024");
+        assert_eq!(results[1].spans, vec![MatchSpan { start: 4, end: 8 }, MatchSpan { start: 11, end: 15 }]);
+        assert_eq!(&results[1].content[4..8], "1234");
+
+        // Offsets em bytes: "código " ocupa 8 bytes
+        let results = db.search_regex(&regex, 0, 1_000, None, 10, deadline).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].spans, vec![MatchSpan { start: 8, end: 12 }]);
+
+        assert_eq!(db.search_regex(&regex, 0, 1_000, None, 1, deadline).await.unwrap().len(), 1);
+        assert!(db.search_regex(&regex, 0, 1_000, None, 10, Instant::now()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_agent_config_roundtrip() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        assert!(db.load_agent_config().await.unwrap().is_none());
+
+        let mut config = AgentConfig {
+            buffer_size: 7,
+            ..Default::default()
+        };
+        db.save_agent_config(&config).await.unwrap();
+        config.capture_clipboard = true;
+        db.save_agent_config(&config).await.unwrap();
+
+        let loaded = db.load_agent_config().await.unwrap().unwrap();
+        assert_eq!(loaded.buffer_size, 7);
+        assert!(loaded.capture_clipboard);
+
+        // JSON de uma versão anterior, sem os campos novos
+        db.writer.lock().await.execute(
+            "UPDATE config SET value = '{\"buffer_size\": 3}' WHERE key = ?1",
+            params![AGENT_CONFIG_KEY],
+        ).unwrap();
+        let loaded = db.load_agent_config().await.unwrap().unwrap();
+        assert_eq!(loaded.buffer_size, 3);
+        assert_eq!(loaded.session_idle_secs, AgentConfig::default().session_idle_secs);
+    }
+
+    #[tokio::test]
+    async fn test_modifiers_and_session_round_trip() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        let event = KeyEvent {
+            timestamp: 20,
+            key: "s".to_string(),
+            event_type: "press".to_string(),
+            window_info: None,
+            is_modifier: false,
+            is_function_key: false,
+            session_id: Some("s1".to_string()),
+            modifiers: vec!["Shift".to_string(), "Ctrl".to_string()],
+        };
+        db.store_events(&[event]).await.unwrap();
+        // Linha gravada sem as colunas, como nas versões anteriores
+        insert_text(&db, 10, "x").await;
+
+        let stored = db.search_by_timerange(0, u64::MAX, 10).await.unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].session_id.as_deref(), Some("s1"));
+        assert_eq!(stored[0].modifiers.as_deref(), Some("Ctrl+Shift"));
+        assert_eq!(stored[1].session_id, None);
+        assert_eq!(stored[1].modifiers, None);
+    }
+
+    #[tokio::test]
+    async fn test_list_sessions() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        let events: Vec<KeyEvent> = [(10, "s1"), (11, "s1"), (12, "s1"), (50, "s2"), (51, "s2")]
+            .iter()
+            .map(|(ts, session)| KeyEvent {
+                timestamp: *ts,
+                key: "k".to_string(),
+                event_type: "press".to_string(),
+                window_info: None,
+                is_modifier: false,
+                is_function_key: false,
+                session_id: Some(session.to_string()),
+                modifiers: Vec::new(),
+            })
+            .collect();
+        db.store_events(&events).await.unwrap();
+
+        let sessions = db.list_sessions().await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session_id, "s2");
+        assert_eq!((sessions[0].started_at, sessions[0].ended_at, sessions[0].event_count), (50, 51, 2));
+        assert_eq!((sessions[1].started_at, sessions[1].ended_at, sessions[1].event_count), (10, 12, 3));
+
+        let stored = db.search_by_timerange(0, u64::MAX, 10).await.unwrap();
+        assert_eq!(stored[0].session_id.as_deref(), Some("s2"));
+    }
+
+    #[tokio::test]
+    async fn test_events_since_pages_by_id() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        // Timestamps repetidos: paginar por tempo repetiria ou pularia eventos
+        for i in 0..7 {
+            insert_text(&db, 100 + i / 3, &format!("evento {}", i)).await;
+        }
+
+        let mut seen = Vec::new();
+        let mut last_id = 0;
+        loop {
+            let page = db.events_since(last_id, 3).await.unwrap();
+            if page.is_empty() {
+                break;
+            }
+            assert!(page.len() <= 3);
+            last_id = page.last().unwrap().id;
+            seen.extend(page.into_iter().map(|event| event.id));
+        }
+
+        assert_eq!(seen.len(), 7);
+        assert!(seen.windows(2).all(|pair| pair[1] == pair[0] + 1), "ids sem lacunas nem repetições: {:?}", seen);
+
+        // Eventos gravados depois do último id visto aparecem na próxima consulta
+        insert_text(&db, 100, "atrasado").await;
+        let newer = db.events_since(last_id, 10).await.unwrap();
+        assert_eq!(newer.len(), 1);
+        assert_eq!(newer[0].text_content.as_deref(), Some("atrasado"));
+        assert!(db.events_since(newer[0].id, 10).await.unwrap().is_empty());
+    }
+
+    async fn insert_in_session(db: &Database, timestamp: u64, text: &str, event_type: &str, session: &str) -> i64 {
+        let conn = db.writer.lock().await;
+        conn.execute(
+            "INSERT INTO events (timestamp, key, event_type, text_content, session_id) VALUES (?1, ?2, ?3, ?2, ?4)",
+            params![timestamp, text, event_type, session],
+        ).unwrap();
+        conn.last_insert_rowid()
+    }
+
+    #[tokio::test]
+    async fn test_event_context_is_adjacent_and_session_scoped() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_in_session(&db, 1, "um", "press", "s1").await;
+        insert_in_session(&db, 2, "dois", "press", "s1").await;
+        insert_in_session(&db, 3, "intruso", "press", "s2").await;
+        insert_in_session(&db, 4, "solto", "release", "s1").await;
+        let target = insert_in_session(&db, 5, "alvo", "press", "s1").await;
+        insert_in_session(&db, 6, "cinco", "press", "s1").await;
+        insert_in_session(&db, 7, "outro", "press", "s2").await;
+        insert_in_session(&db, 8, "seis", "press", "s1").await;
+        insert_in_session(&db, 9, "sete", "press", "s1").await;
+
+        let text = |events: &[StoredEvent]| -> Vec<String> {
+            events.iter().map(|e| e.text_content.clone().unwrap()).collect()
+        };
+
+        let context = db.get_event_context(target, 2).await.unwrap();
+        assert_eq!(text(&context.before), vec!["um", "dois"]);
+        assert_eq!(text(&context.after), vec!["cinco", "seis"]);
+
+        let wide = db.get_event_context(target, 10).await.unwrap();
+        assert_eq!(wide.before.len(), 2);
+        assert_eq!(text(&wide.after), vec!["cinco", "seis", "sete"]);
+
+        let options = SearchOptions {
+            context_size: 1,
+            ..Default::default()
+        };
+        let results = db.search_text_with_options("alvo", &options).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(text(results[0].context_before.as_ref().unwrap()), vec!["dois"]);
+        assert_eq!(text(results[0].context_after.as_ref().unwrap()), vec!["cinco"]);
+
+        // Sem `context_size`, nenhum contexto é buscado
+        let results = db.search_text("alvo", 10).await.unwrap();
+        assert!(results[0].context_before.is_none());
+        assert!(results[0].context_after.is_none());
+    }
+
+    async fn insert_typed(db: &Database, timestamp: u64, key: &str, event_type: &str, text: Option<&str>) {
+        let conn = db.writer.lock().await;
+        conn.execute(
+            "INSERT INTO events (timestamp, key, event_type, text_content) VALUES (?1, ?2, ?3, ?4)",
+            params![timestamp, key, event_type, text],
+        ).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_search_filters_by_event_type() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        insert_typed(&db, 1, "r", "press", Some("relatorio")).await;
+        // Liberação com texto, como gravado por versões antigas
+        insert_typed(&db, 2, "r", "release", Some("relatorio")).await;
+        insert_typed(&db, 3, "v", PASTE_EVENT_TYPE, Some("relatorio anual")).await;
+        insert_typed(&db, 4, "Backspace", "press", None).await;
+        insert_typed(&db, 5, "Backspace", "release", None).await;
+        insert_typed(&db, 6, "Backspace", "press", None).await;
+
+        // Por padrão as liberações ficam de fora
+        let results = db.search_text("relatorio", 10).await.unwrap();
+        let mut timestamps: Vec<u64> = results.iter().map(|r| r.timestamp).collect();
+        timestamps.sort();
+        assert_eq!(timestamps, vec![1, 3]);
+
+        let options = SearchOptions {
+            event_types: Some(vec!["release".to_string()]),
+            ..Default::default()
+        };
+        let releases = db.search_text_with_options("relatorio", &options).await.unwrap();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].timestamp, 2);
+
+        let presses = db
+            .search_by_timerange_filtered(0, u64::MAX, 10, Some(&["press".to_string()]))
+            .await
+            .unwrap();
+        let backspaces = presses.iter().filter(|e| e.key == "Backspace").count();
+        assert_eq!(presses.len(), 3);
+        assert_eq!(backspaces, 2);
+        assert!(presses.iter().all(|e| e.event_type == "press"));
+
+        // Sem filtro, a listagem por período continua trazendo todos os tipos
+        assert_eq!(db.search_by_timerange(0, u64::MAX, 10).await.unwrap().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_search_shortcuts_matches_modifier_set() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+
+        let event = |timestamp: u64, modifiers: &[&str]| KeyEvent {
+            timestamp,
+            key: "s".to_string(),
+            event_type: "press".to_string(),
+            window_info: Some(crate::agent::WindowInfo {
+                title: "notes.md".to_string(),
+                application: "code".to_string(),
+                process_id: None,
+                timestamp,
+            }),
+            is_modifier: false,
+            is_function_key: false,
+            session_id: None,
+            modifiers: modifiers.iter().map(|m| m.to_string()).collect(),
+        };
+        db.store_events(&[event(1, &["Ctrl"]), event(2, &[]), event(3, &["Ctrl", "Shift"])]).await.unwrap();
+
+        let matches = db.search_shortcuts(&["CtrlLeft".to_string()], "S", 10).await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].timestamp, 1);
+        assert_eq!(matches[0].modifiers, vec!["Ctrl"]);
+        assert_eq!(matches[0].application.as_deref(), Some("code"));
+
+        // Sem modificadores encontra apenas o S simples
+        let plain = db.search_shortcuts(&[], "s", 10).await.unwrap();
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].timestamp, 2);
+
+        assert!(db.search_shortcuts(&[], "  ", 10).await.is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! Unidade dos timestamps dos eventos.
+//!
+//! Os eventos são gravados em milissegundos desde a época. Versões antigas do
+//! agente gravavam segundos; esses valores são reconhecidos por ficarem abaixo
+//! de [`LEGACY_SECONDS_THRESHOLD`] e convertidos pela migração v9 e na
+//! importação de exportações antigas.
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+
+/// Abaixo deste valor o timestamp está em segundos: em milissegundos ele
+/// corresponde a março de 1973, em segundos ao ano 5138
+pub const LEGACY_SECONDS_THRESHOLD: u64 = 100_000_000_000;
+
+/// Converte para milissegundos um timestamp que ainda esteja em segundos
+pub fn normalize_millis(timestamp: u64) -> u64 {
+    if timestamp < LEGACY_SECONDS_THRESHOLD {
+        timestamp.saturating_mul(1000)
+    } else {
+        timestamp
+    }
+}
+
+/// Instante UTC de um timestamp em milissegundos
+pub fn millis_to_datetime(timestamp: u64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(i64::try_from(timestamp).unwrap_or(i64::MAX))
+        .single()
+        .unwrap_or_default()
+}
+
+/// ISO-8601 com milissegundos, ex.: `2023-11-14T22:13:20.123Z`
+pub fn millis_to_iso(timestamp: u64) -> String {
+    millis_to_datetime(timestamp).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_millis_conversion() {
+        let datetime = millis_to_datetime(1_700_000_000_123);
+        assert_eq!(datetime.timestamp(), 1_700_000_000);
+        assert_eq!(datetime.timestamp_subsec_millis(), 123);
+        assert_eq!(millis_to_iso(1_700_000_000_123), "2023-11-14T22:13:20.123Z");
+        assert_eq!(millis_to_iso(0), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_legacy_seconds_are_detected() {
+        assert_eq!(normalize_millis(1_700_000_000), 1_700_000_000_000);
+        assert_eq!(normalize_millis(1_700_000_000_000), 1_700_000_000_000);
+        assert_eq!(normalize_millis(LEGACY_SECONDS_THRESHOLD), LEGACY_SECONDS_THRESHOLD);
+        assert_eq!(normalize_millis(u64::MAX), u64::MAX);
+    }
+}
@@ -0,0 +1,481 @@
+//! Índice de palavras para busca por frase.
+//!
+//! Cada evento guarda uma tecla, então o FTS5 indexa caracteres soltos e não
+//! casa frases. Aqui as teclas são remontadas em palavras e gravadas em
+//! `tokens` com a posição dentro do segmento (trecho contínuo de uma mesma
+//! sessão e aplicação). O índice é atualizado sob demanda, antes de cada
+//! busca, a partir do ponto salvo em `config`; a palavra ainda sendo digitada
+//! entra como provisória e é refeita na atualização seguinte.
+//!
+//! As teclas são gravadas uma a uma e nenhuma sozinha casa um padrão de PII,
+//! então as palavras remontadas passam pelo masker antes de entrar no índice:
+//! as que ele alteraria ficam de fora, ocupando sua posição.
+
+use anyhow::{Result, anyhow};
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use serde::{Serialize, Deserialize};
+use tracing::debug;
+
+use crate::agent::PASTE_EVENT_TYPE;
+use super::Database;
+use super::compaction::SEGMENT_EVENT_TYPE;
+
+/// Chave do ponto de retomada na tabela `config`
+pub(super) const TOKEN_INDEX_KEY: &str = "token_index";
+
+/// Máximo de trechos retornados por `search_phrase`
+const PHRASE_SEARCH_LIMIT: i64 = 100;
+
+/// Eventos indexados por transação; o writer é liberado entre os lotes para
+/// que a primeira indexação de um banco grande não trave a captura
+const INDEX_BATCH_SIZE: i64 = 5_000;
+
+/// Frase encontrada por `search_phrase`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhraseMatch {
+    /// Evento da primeira tecla da frase
+    pub start_event_id: i64,
+    /// Evento da primeira tecla da última palavra
+    pub end_event_id: i64,
+    pub timestamp: u64,
+    pub application: Option<String>,
+}
+
+/// Ponto de retomada da indexação
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct IndexState {
+    next_event_id: i64,
+    /// Id do primeiro evento do segmento atual (0 antes do primeiro evento)
+    segment: i64,
+    /// Posição da próxima palavra no segmento
+    position: i64,
+    session_id: Option<String>,
+    application: Option<String>,
+    /// Palavra em digitação, gravada como provisória em `position`
+    word: String,
+    word_event_id: i64,
+    word_timestamp: i64,
+}
+
+#[derive(Debug, PartialEq)]
+struct Token {
+    word: String,
+    segment: i64,
+    position: i64,
+    event_id: i64,
+    timestamp: i64,
+}
+
+struct TextEvent {
+    id: i64,
+    timestamp: i64,
+    key: String,
+    text_content: Option<String>,
+    session_id: Option<String>,
+    application: Option<String>,
+}
+
+/// Remonta palavras a partir das teclas, na ordem dos eventos
+struct Tokenizer {
+    state: IndexState,
+    tokens: Vec<Token>,
+}
+
+impl Tokenizer {
+    fn new(state: IndexState) -> Self {
+        Self { state, tokens: Vec::new() }
+    }
+
+    fn push(&mut self, event: &TextEvent) {
+        let new_segment = self.state.segment == 0
+            || event.session_id != self.state.session_id
+            || event.application != self.state.application;
+        if new_segment {
+            self.end_word();
+            self.state.segment = event.id;
+            self.state.position = 0;
+            self.state.session_id = event.session_id.clone();
+            self.state.application = event.application.clone();
+        }
+
+        match &event.text_content {
+            Some(text) => {
+                for c in text.chars() {
+                    if c.is_alphanumeric() {
+                        self.push_char(c, event);
+                    } else {
+                        self.end_word();
+                    }
+                }
+            }
+            None => match event.key.as_str() {
+                "Space" | "Return" | "Tab" => self.end_word(),
+                "Backspace" => {
+                    self.state.word.pop();
+                }
+                _ => {}
+            },
+        }
+
+        self.state.next_event_id = event.id + 1;
+    }
+
+    fn push_char(&mut self, c: char, event: &TextEvent) {
+        if self.state.word.is_empty() {
+            self.state.word_event_id = event.id;
+            self.state.word_timestamp = event.timestamp;
+        }
+        self.state.word.extend(c.to_lowercase());
+    }
+
+    fn end_word(&mut self) {
+        if self.state.word.is_empty() {
+            return;
+        }
+        self.tokens.push(Token {
+            word: std::mem::take(&mut self.state.word),
+            segment: self.state.segment,
+            position: self.state.position,
+            event_id: self.state.word_event_id,
+            timestamp: self.state.word_timestamp,
+        });
+        self.state.position += 1;
+    }
+
+    /// Tokens completos mais a palavra em digitação, e o estado para retomar
+    fn finish(mut self) -> (Vec<Token>, IndexState) {
+        if !self.state.word.is_empty() {
+            self.tokens.push(Token {
+                word: self.state.word.clone(),
+                segment: self.state.segment,
+                position: self.state.position,
+                event_id: self.state.word_event_id,
+                timestamp: self.state.word_timestamp,
+            });
+        }
+        (self.tokens, self.state)
+    }
+}
+
+/// Palavras da frase, normalizadas como no índice
+fn phrase_words(phrase: &str) -> Vec<String> {
+    phrase
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Indexa até `batch_size` eventos novos desde a última atualização, sem as
+/// palavras que `mask` alteraria. Retorna quantos eventos foram lidos e
+/// quantas palavras gravadas
+fn update_index(
+    conn: &Connection,
+    mask: &(dyn Fn(&str) -> String + Sync),
+    batch_size: i64,
+) -> Result<(usize, usize)> {
+    let tx = conn.unchecked_transaction()?;
+
+    let saved: Option<String> = tx
+        .query_row("SELECT value FROM config WHERE key = ?1", params![TOKEN_INDEX_KEY], |row| row.get(0))
+        .optional()?;
+    let mut state: IndexState = saved.map(|value| serde_json::from_str(&value)).transpose()?.unwrap_or_default();
+
+    if !state.word.is_empty() {
+        // The provisional token is rebuilt from the saved partial word
+        tx.execute(
+            "DELETE FROM tokens WHERE segment = ?1 AND position = ?2",
+            params![state.segment, state.position],
+        )?;
+        let word_exists: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM events WHERE id = ?1)",
+            params![state.word_event_id],
+            |row| row.get(0),
+        )?;
+        if !word_exists {
+            state.word.clear();
+        }
+    }
+
+    let mut tokenizer = Tokenizer::new(state);
+    let mut read = 0;
+    {
+        let mut stmt = tx.prepare(
+            "SELECT id, timestamp, key, text_content, session_id, application
+             FROM events
+             WHERE id >= ?1 AND event_type IN ('press', ?2, ?3)
+             ORDER BY id
+             LIMIT ?4",
+        )?;
+        let from = tokenizer.state.next_event_id;
+        let rows = stmt.query_map(params![from, PASTE_EVENT_TYPE, SEGMENT_EVENT_TYPE, batch_size], |row| {
+            Ok(TextEvent {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                key: row.get(2)?,
+                text_content: row.get(3)?,
+                session_id: row.get(4)?,
+                application: row.get(5)?,
+            })
+        })?;
+        for event in rows {
+            tokenizer.push(&event?);
+            read += 1;
+        }
+    }
+
+    let (mut tokens, state) = tokenizer.finish();
+    tokens.retain(|token| mask(&token.word) == token.word);
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT OR REPLACE INTO tokens (word, segment, position, event_id, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for token in &tokens {
+            insert.execute(params![token.word, token.segment, token.position, token.event_id, token.timestamp])?;
+        }
+    }
+
+    tx.execute(
+        "INSERT INTO config (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        params![TOKEN_INDEX_KEY, serde_json::to_string(&state)?],
+    )?;
+    tx.commit()?;
+
+    Ok((read, tokens.len()))
+}
+
+impl Database {
+    /// Busca a frase palavra por palavra, em ordem; entre palavras consecutivas
+    /// podem existir até `max_gap` outras (0 = frase exata). Antes, indexa os
+    /// eventos novos usando `mask` para descartar palavras com PII
+    pub async fn search_phrase(
+        &self,
+        phrase: &str,
+        max_gap: usize,
+        mask: &(dyn Fn(&str) -> String + Sync),
+    ) -> Result<Vec<PhraseMatch>> {
+        let words = phrase_words(phrase);
+        if words.is_empty() {
+            return Err(anyhow!("Frase sem palavras para buscar"));
+        }
+
+        let mut indexed = 0;
+        loop {
+            let (read, tokens) = {
+                let conn = self.writer.lock().await;
+                update_index(&conn, mask, INDEX_BATCH_SIZE)?
+            };
+            indexed += tokens;
+            if (read as i64) < INDEX_BATCH_SIZE {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        debug!("🔤 {} palavras indexadas para busca por frase", indexed);
+
+        let gap_param = words.len() + 1;
+        let mut joins = String::new();
+        let mut conditions = vec!["t0.word = ?1".to_string()];
+        for i in 1..words.len() {
+            joins.push_str(&format!(
+                " JOIN tokens t{i} ON t{i}.segment = t0.segment
+                   AND t{i}.position BETWEEN t{prev}.position + 1 AND t{prev}.position + 1 + ?{gap}",
+                i = i,
+                prev = i - 1,
+                gap = gap_param,
+            ));
+            conditions.push(format!("t{}.word = ?{}", i, i + 1));
+        }
+        let last = words.len() - 1;
+        let sql = format!(
+            "SELECT t0.event_id, MIN(t{last}.event_id), t0.timestamp, e.application
+             FROM tokens t0{joins}
+             JOIN events e ON e.id = t0.event_id
+             WHERE {conditions}
+             GROUP BY t0.id
+             ORDER BY t0.timestamp DESC
+             LIMIT ?{limit}",
+            last = last,
+            joins = joins,
+            conditions = conditions.join(" AND "),
+            limit = gap_param + 1,
+        );
+
+        let mut values: Vec<rusqlite::types::Value> = words.into_iter().map(Into::into).collect();
+        values.push((max_gap as i64).into());
+        values.push(PHRASE_SEARCH_LIMIT.into());
+
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let matches = stmt
+            .query_map(params_from_iter(values), |row| {
+                Ok(PhraseMatch {
+                    start_event_id: row.get(0)?,
+                    end_event_id: row.get(1)?,
+                    timestamp: row.get::<_, i64>(2)? as u64,
+                    application: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_event(id: i64, key: &str) -> TextEvent {
+        TextEvent {
+            id,
+            timestamp: id,
+            key: key.to_string(),
+            text_content: (key.chars().count() == 1).then(|| key.to_string()),
+            session_id: Some("s1".to_string()),
+            application: Some("editor".to_string()),
+        }
+    }
+
+    fn no_mask(text: &str) -> String {
+        text.to_string()
+    }
+
+    fn words(tokens: &[Token]) -> Vec<(&str, i64)> {
+        tokens.iter().map(|token| (token.word.as_str(), token.position)).collect()
+    }
+
+    #[test]
+    fn test_phrase_words() {
+        assert_eq!(phrase_words("  Quick, brown-FOX! "), vec!["quick", "brown", "fox"]);
+        assert!(phrase_words("... ").is_empty());
+    }
+
+    #[test]
+    fn test_tokenizer_builds_words_and_segments() {
+        let mut tokenizer = Tokenizer::new(IndexState::default());
+        let keys = ["O", "i", "Space", "m", "x", "Backspace", "u", "n", "d", "o", "Return"];
+        for (i, key) in keys.iter().enumerate() {
+            tokenizer.push(&key_event(i as i64 + 1, key));
+        }
+
+        // Outra aplicação inicia um novo segmento, fechando a palavra anterior
+        let mut other = key_event(20, "z");
+        other.application = Some("chat".to_string());
+        tokenizer.push(&other);
+
+        let (tokens, state) = tokenizer.finish();
+        assert_eq!(words(&tokens), vec![("oi", 0), ("mundo", 1), ("z", 0)]);
+        assert_eq!(tokens[1].event_id, 4);
+        assert_eq!(tokens[2].segment, 20);
+
+        // A palavra em digitação continua salva para a próxima atualização
+        assert_eq!(state.word, "z");
+        assert_eq!(state.position, 0);
+        assert_eq!(state.next_event_id, 21);
+    }
+
+    async fn type_text(db: &Database, start: u64, text: &str) {
+        let events: Vec<crate::agent::KeyEvent> = text
+            .chars()
+            .enumerate()
+            .map(|(i, c)| crate::agent::KeyEvent {
+                timestamp: start + i as u64,
+                key: if c == ' ' { "Space".to_string() } else { c.to_string() },
+                event_type: "press".to_string(),
+                window_info: None,
+                is_modifier: false,
+                is_function_key: false,
+                session_id: Some("sessao".to_string()),
+                modifiers: Vec::new(),
+            })
+            .collect();
+        db.store_events(&events).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_search_phrase() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        type_text(&db, 1_000, "the quick brown fox").await;
+
+        let exact = db.search_phrase("Quick Brown fox", 0, &no_mask).await.unwrap();
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].timestamp, 1_004);
+
+        assert!(db.search_phrase("quick fox", 0, &no_mask).await.unwrap().is_empty());
+        assert_eq!(db.search_phrase("quick fox", 1, &no_mask).await.unwrap().len(), 1);
+        assert!(db.search_phrase("fox quick", 5, &no_mask).await.unwrap().is_empty());
+        assert!(db.search_phrase("  ", 0, &no_mask).await.is_err());
+
+        // A última palavra continua sendo digitada em outro lote
+        type_text(&db, 2_000, "es jump").await;
+        assert!(db.search_phrase("brown fox", 0, &no_mask).await.unwrap().is_empty());
+        assert_eq!(db.search_phrase("brown foxes jump", 0, &no_mask).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_phrase_skips_masked_words() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        type_text(&db, 1_000, "meu cpf 12345678901 aqui").await;
+
+        let masker = crate::masker::Masker::new();
+        let mask = |text: &str| masker.mask_text(text);
+        assert_eq!(db.search_phrase("meu cpf", 0, &mask).await.unwrap().len(), 1);
+        assert!(db.search_phrase("12345678901", 0, &mask).await.unwrap().is_empty());
+
+        // A palavra descartada ainda ocupa sua posição
+        assert!(db.search_phrase("cpf aqui", 0, &mask).await.unwrap().is_empty());
+        assert_eq!(db.search_phrase("cpf aqui", 1, &mask).await.unwrap().len(), 1);
+
+        let conn = db.reader().unwrap();
+        let stored: i64 = conn
+            .query_row("SELECT COUNT(*) FROM tokens WHERE word GLOB '*[0-9]*'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, 0);
+    }
+
+    #[tokio::test]
+    async fn test_update_index_in_batches() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let db = Database::new(temp_file.path()).await.unwrap();
+        type_text(&db, 1_000, "um dois tres quatro").await;
+
+        let conn = db.writer.lock().await;
+        let mut batches = 0;
+        loop {
+            let (read, _) = update_index(&conn, &no_mask, 3).unwrap();
+            batches += 1;
+            if read < 3 {
+                break;
+            }
+        }
+        drop(conn);
+        assert_eq!(batches, 7);
+
+        // Palavras cortadas entre lotes são remontadas pela palavra provisória
+        assert_eq!(db.search_phrase("um dois tres quatro", 0, &no_mask).await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_tokenizer_resumes_partial_word() {
+        let mut first = Tokenizer::new(IndexState::default());
+        for (i, key) in ["a", "b", "Space", "c"].iter().enumerate() {
+            first.push(&key_event(i as i64 + 1, key));
+        }
+        let (tokens, state) = first.finish();
+        assert_eq!(words(&tokens), vec![("ab", 0), ("c", 1)]);
+
+        let mut second = Tokenizer::new(state);
+        second.push(&key_event(5, "d"));
+        second.push(&key_event(6, "Space"));
+        let (tokens, state) = second.finish();
+        assert_eq!(words(&tokens), vec![("cd", 1)]);
+        assert_eq!(tokens[0].event_id, 4);
+        assert!(state.word.is_empty());
+        assert_eq!(state.position, 2);
+    }
+}
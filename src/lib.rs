@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, RwLock};
 
 // Re-export modules for tests and benchmarks
 pub mod agent;
@@ -7,26 +7,52 @@ pub mod masker;
 pub mod db;
 pub mod search;
 pub mod commands;
+pub mod logging;
+pub mod profiles;
+pub mod settings;
+#[cfg(test)]
+mod test_support;
+#[cfg(feature = "rest-bridge")]
+pub mod bridge;
+#[cfg(feature = "metrics-endpoint")]
+pub mod metrics_server;
+#[cfg(any(feature = "rest-bridge", feature = "metrics-endpoint"))]
+pub mod local_http;
 
 // AppState for Tauri commands
 #[derive(Clone)]
 pub struct AppState {
-    pub database: Arc<db::Database>,
-    pub search_engine: Arc<search::SearchEngine>,
+    /// Banco e engine de busca do perfil ativo (`switch_profile`)
+    pub profile: Arc<profiles::ActiveProfile>,
     pub agent: Arc<Mutex<agent::Agent>>,
+    pub masker: Arc<RwLock<masker::Masker>>,
+    pub searches: Arc<commands::SearchRegistry>,
 }
 
 impl AppState {
     pub async fn new(db_path: &std::path::Path) -> anyhow::Result<Self> {
         let database = Arc::new(db::Database::new(db_path).await?);
         let search_engine = Arc::new(search::SearchEngine::new(database.clone()).await?);
-        let masker = masker::Masker::new();
-        let agent = Arc::new(Mutex::new(agent::Agent::new(masker, database.clone()).await?));
+        let profile = profiles::Profile {
+            name: profiles::DEFAULT_PROFILE.to_string(),
+            database: database.clone(),
+            search_engine,
+        };
+        let profiles_dir = db_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(std::path::Path::new("."));
+        let settings = settings::AppSettings::load(profiles_dir)?;
+        let masker = masker::Masker::from_env();
+        let agent = agent::Agent::new(masker, database.clone()).await?;
+        let masker = agent.shared_masker();
+        let agent = Arc::new(Mutex::new(agent));
         
         Ok(Self {
-            database,
-            search_engine,
+            profile: Arc::new(profiles::ActiveProfile::new(profiles_dir, profile)),
             agent,
+            masker,
+            searches: Arc::new(commands::SearchRegistry::new(settings.search_timeouts)),
         })
     }
 } 
\ No newline at end of file
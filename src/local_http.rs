@@ -0,0 +1,63 @@
+//! Servidor HTTP em `127.0.0.1` usado pela ponte REST e pelo endpoint de
+//! métricas.
+//!
+//! Cada um monta o seu `Router`; aqui ficam o bind no loopback, a tarefa do
+//! `axum::serve` e o encerramento quando o handle é descartado.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use anyhow::Result;
+use axum::Router;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tracing::{error, warn};
+
+/// Servidor em execução; é encerrado quando o handle é descartado
+pub struct LocalServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl LocalServer {
+    /// Serve `app` em `127.0.0.1:port` (0 escolhe uma porta livre). `name`
+    /// identifica o servidor nos logs de erro
+    pub async fn start(app: Router, port: u16, name: &'static str) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+        let addr = listener.local_addr()?;
+
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let server = axum::serve(listener, app).with_graceful_shutdown(async {
+                let _ = stopped.await;
+            });
+            if let Err(e) = server.await {
+                error!("❌ Servidor local '{}' encerrado com erro: {}", name, e);
+            }
+        });
+
+        Ok(Self { addr, shutdown: Some(shutdown) })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Porta lida da variável `var`; `None` se ela não existir ou for inválida
+pub fn port_from_env(var: &str) -> Option<u16> {
+    let port = std::env::var(var).ok()?;
+    match port.parse::<u16>() {
+        Ok(port) => Some(port),
+        Err(_) => {
+            warn!("⚠️ {} inválida: '{}' - servidor não iniciado", var, port);
+            None
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Configuração de logs da aplicação.
+//!
+//! Além do console (somente em builds de debug), os logs são gravados em JSON
+//! num arquivo diário dentro do diretório de dados da aplicação, para que o
+//! usuário possa anexá-los ao reportar problemas (`get_log_path`). O nível
+//! segue `RUST_LOG`, mas o arquivo nunca passa de INFO: os níveis debug e
+//! trace incluem o texto das consultas e eventos ainda não mascarados.
+//!
+//! Com a feature `otel` e `KEYAI_OTEL_ENDPOINT` definida, os spans também são
+//! exportados via OTLP, permitindo seguir uma busca do app até os serviços.
+
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use tracing::{warn, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{Builder, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// Sobrescreve o diretório dos logs
+pub const LOG_DIR_ENV: &str = "KEYAI_LOG_DIR";
+
+/// Prefixo dos arquivos; a data da rotação é acrescentada ao nome
+pub const LOG_FILE_PREFIX: &str = "keyai-desktop.log";
+
+/// Endpoint OTLP (gRPC) que recebe os spans; sem ele nada é exportado
+pub const OTEL_ENDPOINT_ENV: &str = "KEYAI_OTEL_ENDPOINT";
+
+/// Arquivos diários mantidos antes de remover os mais antigos
+const LOG_FILES_KEPT: usize = 7;
+
+/// Diretório onde os arquivos de log são gravados
+pub fn log_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(LOG_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+
+    tauri::api::path::local_data_dir()
+        .map(|dir| dir.join("keyai-desktop").join("logs"))
+        .unwrap_or_else(|| PathBuf::from("logs"))
+}
+
+/// Camada que grava eventos e spans em JSON, um por linha, com rotação diária,
+/// até o nível INFO. O `WorkerGuard` precisa viver até o fim do processo para
+/// esvaziar o buffer
+pub fn json_file_layer<S>(dir: &Path) -> Result<(impl Layer<S>, WorkerGuard)>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    std::fs::create_dir_all(dir)?;
+    let appender = Builder::new()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .max_log_files(LOG_FILES_KEPT)
+        .build(dir)?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let layer = tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_ansi(false)
+        .with_writer(writer)
+        .with_filter(LevelFilter::INFO);
+    Ok((layer, guard))
+}
+
+/// Camada que exporta os spans para `endpoint` via OTLP, em lotes
+#[cfg(feature = "otel")]
+pub fn otel_layer<S>(endpoint: &str) -> Result<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{trace, Resource};
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(trace::config().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", "keyai-desktop"),
+        ])))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Exportação OTLP configurada pelo ambiente. O erro é registrado depois que
+/// o subscriber estiver ativo
+fn otel_from_env() -> std::result::Result<Option<Box<dyn Layer<Registry> + Send + Sync>>, String> {
+    let Some(endpoint) = std::env::var(OTEL_ENDPOINT_ENV).ok().filter(|endpoint| !endpoint.trim().is_empty()) else {
+        return Ok(None);
+    };
+
+    #[cfg(feature = "otel")]
+    {
+        otel_layer(&endpoint)
+            .map(|layer| Some(layer.boxed()))
+            .map_err(|e| format!("Exportação OTLP para {} desativada: {}", endpoint, e))
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        Err(format!("{}={} ignorada: compilado sem a feature `otel`", OTEL_ENDPOINT_ENV, endpoint))
+    }
+}
+
+/// Envia os spans ainda pendentes para o endpoint OTLP antes de encerrar
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Registra o subscriber global. Se o arquivo não puder ser criado, os logs
+/// seguem apenas no console
+pub fn init() -> Option<WorkerGuard> {
+    let default_level = if cfg!(debug_assertions) { "debug" } else { "info" };
+    let filter = || EnvFilter::try_from_default_env().unwrap_or_else(|_| default_level.into());
+    let console = cfg!(debug_assertions).then(tracing_subscriber::fmt::layer);
+    let (otel, otel_error) = match otel_from_env() {
+        Ok(otel) => (otel, None),
+        Err(e) => (None, Some(e)),
+    };
+
+    let dir = log_dir();
+    match json_file_layer(&dir) {
+        Ok((file, guard)) => {
+            tracing_subscriber::registry()
+                .with(otel)
+                .with(filter())
+                .with(console)
+                .with(file)
+                .init();
+            if let Some(e) = otel_error {
+                warn!("⚠️ {}", e);
+            }
+            Some(guard)
+        }
+        Err(e) => {
+            tracing_subscriber::registry()
+                .with(otel)
+                .with(filter())
+                .with(tracing_subscriber::fmt::layer())
+                .init();
+            warn!("⚠️ Não foi possível gravar logs em {:?}: {}", dir, e);
+            if let Some(e) = otel_error {
+                warn!("⚠️ {}", e);
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::info_span;
+
+    #[test]
+    fn test_file_layer_creates_dir_and_writes_json() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("app").join("logs");
+
+        let (layer, guard) = json_file_layer(&dir).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!("flush", events = 3);
+            let _entered = span.enter();
+            tracing::info!("lote gravado");
+            tracing::debug!("consulta: senha do banco");
+            tracing::trace!("evento: senha do banco");
+        });
+        // Esvazia o writer não bloqueante
+        drop(guard);
+
+        assert!(dir.is_dir());
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].file_name().unwrap().to_string_lossy().starts_with(LOG_FILE_PREFIX));
+
+        let contents = std::fs::read_to_string(&files[0]).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+        assert!(!contents.contains("senha do banco"));
+        let line: serde_json::Value = serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!(line["fields"]["message"], "lote gravado");
+        assert_eq!(line["span"]["name"], "flush");
+        assert_eq!(line["span"]["events"], 3);
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test]
+    async fn test_search_emits_spans() {
+        use std::sync::Arc;
+        use opentelemetry::trace::TracerProvider as _;
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use crate::db::Database;
+        use crate::search::{SearchEngine, SearchOptions};
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("keyai-test")));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let database = Arc::new(Database::new(temp_file.path()).await.unwrap());
+        let engine = SearchEngine::new(database).await.unwrap();
+        engine.search_text("relatorio secreto", &SearchOptions::default()).await.unwrap();
+
+        provider.force_flush();
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = spans.iter().find(|span| span.name == "search_text").expect("span search_text");
+        let attributes: Vec<String> = span.attributes.iter().map(|kv| kv.key.to_string()).collect();
+        assert!(attributes.contains(&"query_len".to_string()));
+        // O texto da consulta não vai para o span nem para os eventos dele
+        assert!(span.attributes.iter().all(|kv| !kv.value.as_str().contains("secreto")));
+        assert!(!span.events.is_empty());
+        for event in span.events.iter() {
+            assert!(!event.name.contains("secreto"), "evento exportado com a consulta: {}", event.name);
+            assert!(event.attributes.iter().all(|kv| !kv.value.as_str().contains("secreto")));
+        }
+    }
+}
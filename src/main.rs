@@ -2,83 +2,97 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::{Mutex, RwLock};
 use tauri::Manager;
 use tracing::{info, error, warn, debug};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod agent;
 mod masker;
 mod db;
 mod search;
 mod commands;
+mod logging;
+mod profiles;
+mod settings;
+#[cfg(test)]
+mod test_support;
+#[cfg(feature = "rest-bridge")]
+mod bridge;
+#[cfg(feature = "metrics-endpoint")]
+mod metrics_server;
+#[cfg(any(feature = "rest-bridge", feature = "metrics-endpoint"))]
+mod local_http;
 
 use agent::Agent;
 use masker::Masker;
-use db::Database;
-use search::SearchEngine;
+use profiles::{ActiveProfile, Profile, DEFAULT_PROFILE};
+use settings::AppSettings;
 
 pub struct AppState {
-    pub database: Arc<Database>,
-    pub search_engine: Arc<SearchEngine>,
+    /// Banco e engine de busca do perfil ativo (`switch_profile`)
+    pub profile: Arc<ActiveProfile>,
     pub agent: Arc<Mutex<Agent>>,
+    pub masker: Arc<RwLock<Masker>>,
+    pub searches: Arc<commands::SearchRegistry>,
 }
 
+/// Fechamento já em andamento; novos pedidos de fechar a janela são ignorados
+static CLOSING: AtomicBool = AtomicBool::new(false);
+
 #[tokio::main]
 async fn main() {
-    // Configurar logging com nível mais detalhado para debug
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "debug".into()), // Mudado de "info" para "debug"
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // Console em debug e arquivo JSON diário; o guard mantém o arquivo sendo esvaziado
+    let _log_guard = logging::init();
 
     info!("🚀 Iniciando KeyAI Desktop v1.0");
+    info!("📝 Logs gravados em {:?}", logging::log_dir());
     debug!("🔧 Modo debug ativado - logs detalhados habilitados");
 
     // Verificar permissões antes de inicializar componentes
-    if !Agent::check_permissions() {
+    let permissions = Agent::check_permissions();
+    debug!("🔐 Status de permissões: {:?}", permissions);
+    if !permissions.accessibility {
         error!("❌ Permissões insuficientes detectadas");
         warn!("⚠️ A aplicação pode não funcionar corretamente sem as permissões adequadas");
     }
+    if !permissions.window_detection {
+        warn!("⚠️ Detecção de janela ativa indisponível ou limitada em {}", permissions.platform);
+    }
 
-    // Inicializar banco de dados com tratamento robusto de erros
-    let database = match Database::new("keyai.db").await {
-        Ok(db) => {
-            info!("✅ Banco de dados inicializado com sucesso");
-            Arc::new(db)
-        },
+    let settings = match AppSettings::load(std::path::Path::new(".")) {
+        Ok(settings) => settings,
         Err(e) => {
-            error!("❌ Erro crítico ao inicializar banco de dados: {}", e);
-            error!("💡 Verifique se o arquivo keyai.db pode ser criado/acessado");
-            std::process::exit(1);
+            warn!("⚠️ Configuração em {} inválida, usando padrão: {}", settings::SETTINGS_FILE, e);
+            AppSettings::default()
         }
     };
+    debug!("🔧 Configuração: {:?}", settings);
 
-    // Inicializar engine de busca
-    let search_engine = match SearchEngine::new(Arc::clone(&database)).await {
-        Ok(engine) => {
-            info!("✅ Engine de busca inicializada com sucesso");
-            Arc::new(engine)
+    // Inicializar banco de dados e engine de busca do perfil padrão
+    let profile = match Profile::open(std::path::Path::new("."), DEFAULT_PROFILE, None).await {
+        Ok(profile) => {
+            info!("✅ Banco de dados e engine de busca inicializados com sucesso");
+            profile
         },
         Err(e) => {
-            error!("❌ Erro crítico ao inicializar engine de busca: {}", e);
-            error!("💡 Verifique se as dependências de busca estão disponíveis");
+            error!("❌ Erro crítico ao inicializar banco de dados: {}", e);
+            error!("💡 Verifique se o arquivo keyai.db pode ser criado/acessado");
             std::process::exit(1);
         }
     };
+    let database = Arc::clone(&profile.database);
 
     // Inicializar masker
-    let masker = Masker::new();
+    let masker = Masker::from_env();
     info!("✅ Masker de PII inicializado");
 
     // Inicializar agente de captura com tratamento robusto
-    let agent = match Agent::new(masker, Arc::clone(&database)).await {
+    let (agent, shared_masker) = match Agent::new(masker, Arc::clone(&database)).await {
         Ok(agent) => {
             info!("✅ Agente de captura inicializado");
-            Arc::new(Mutex::new(agent))
+            let shared_masker = agent.shared_masker();
+            (Arc::new(Mutex::new(agent)), shared_masker)
         },
         Err(e) => {
             error!("❌ Erro crítico ao inicializar agente: {}", e);
@@ -87,11 +101,28 @@ async fn main() {
         }
     };
 
+    let active_profile = Arc::new(ActiveProfile::new(".", profile));
+
+    // Ponte REST local, só quando compilada com `rest-bridge` e com a porta configurada
+    #[cfg(feature = "rest-bridge")]
+    let _rest_bridge = start_rest_bridge(Arc::clone(&active_profile)).await;
+
+    let searches = Arc::new(commands::SearchRegistry::new(settings.search_timeouts));
+
+    // Endpoint Prometheus, só quando compilado com `metrics-endpoint` e com a porta configurada
+    #[cfg(feature = "metrics-endpoint")]
+    let _metrics_server = start_metrics_server(
+        agent.lock().await.shared_metrics(),
+        Arc::clone(&active_profile),
+        Arc::clone(&searches),
+    ).await;
+
     // Criar estado da aplicação
     let app_state = AppState {
-        database: Arc::clone(&database),
-        search_engine: Arc::clone(&search_engine),
+        profile: active_profile,
         agent: Arc::clone(&agent),
+        masker: shared_masker,
+        searches,
     };
 
     info!("✅ Todos os componentes inicializados com sucesso");
@@ -107,24 +138,53 @@ async fn main() {
             commands::search_hybrid,
             commands::get_search_suggestions,
             commands::get_popular_searches,
+            commands::search_regex,
+            commands::get_embedding_info,
+            commands::cancel_search,
             
             // Comandos do agente
             commands::toggle_agent,
+            commands::pause_capture,
+            commands::resume_capture,
             commands::get_agent_status,
             commands::update_agent_config,
             commands::get_agent_config,
             commands::get_current_window,
             commands::get_agent_metrics,
+            commands::get_permission_status,
+            
+            // Comandos do masker
+            commands::add_masker_pattern,
+            commands::validate_masker_pattern,
+            commands::remove_masker_pattern,
+            commands::list_masker_patterns,
+            commands::preview_masking,
+            commands::mask_text,
+            commands::get_sensitive_contexts,
+            commands::set_sensitive_contexts,
             
             // Comandos de dados
             commands::get_stats,
             commands::clear_data,
+            commands::clear_search_log,
+            commands::delete_events,
+            commands::compact_history,
+            commands::list_sessions,
+            commands::search_shortcuts,
+            commands::poll_new_events,
             commands::export_data,
             commands::import_data,
             
             // Comandos de sistema
             commands::optimize_search_index,
+            commands::rebuild_search_index,
+            commands::backup_database,
+            commands::list_backups,
+            commands::restore_backup,
             commands::health_check,
+            commands::get_log_path,
+            commands::list_profiles,
+            commands::switch_profile,
         ])
         .setup(|app| {
             info!("✅ Aplicação Tauri inicializada");
@@ -132,11 +192,16 @@ async fn main() {
             
             // Configurar handlers de eventos de janela
             let app_handle = app.handle();
-            
-            // Handler para quando a janela é fechada
-            app.listen_global("tauri://close-requested", move |_event| {
-                info!("🔄 Aplicação sendo fechada graciosamente...");
-                // TODO: Implementar graceful shutdown do agente
+
+            // Eventos ao vivo só são enviados quando `emit_live_events` está ativo;
+            // falhas do listener (`keyai://capture-error`) são sempre enviadas.
+            // O setup já roda dentro do runtime do tokio, então não pode usar block_on
+            let live_agent = Arc::clone(&app.state::<AppState>().agent);
+            tauri::async_runtime::spawn(async move {
+                live_agent
+                    .lock()
+                    .await
+                    .set_live_emitter(Arc::new(agent::TauriEmitter::new(app_handle)));
             });
             
             // Handler para erros não capturados
@@ -146,6 +211,28 @@ async fn main() {
             
             Ok(())
         })
+        .on_window_event(|event| {
+            // Handler para quando a janela é fechada: o fechamento espera o agente
+            // descarregar o buffer e fazer o checkpoint do WAL. O handler roda na
+            // thread do event loop, então o encerramento vai para uma task
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event.event() {
+                api.prevent_close();
+                if CLOSING.swap(true, Ordering::SeqCst) {
+                    return;
+                }
+                info!("🔄 Aplicação sendo fechada graciosamente...");
+
+                let app_handle = event.window().app_handle();
+                let agent = Arc::clone(&app_handle.state::<AppState>().agent);
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = agent.lock().await.shutdown().await {
+                        error!("❌ Erro durante o encerramento do agente: {}", e);
+                    }
+                    logging::shutdown();
+                    app_handle.exit(0);
+                });
+            }
+        })
         .run(tauri::generate_context!());
 
     // Tratar erros de execução da aplicação Tauri
@@ -154,4 +241,39 @@ async fn main() {
         error!("💡 Verifique se todas as dependências do Tauri estão instaladas");
         std::process::exit(1);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "rest-bridge")]
+async fn start_rest_bridge(profile: Arc<ActiveProfile>) -> Option<bridge::RestBridge> {
+    let port = local_http::port_from_env(bridge::BRIDGE_PORT_ENV)?;
+
+    match bridge::RestBridge::start(profile, port).await {
+        Ok(rest_bridge) => {
+            if let Err(e) = rest_bridge.write_token(std::path::Path::new(".")) {
+                error!("❌ Erro ao gravar token da ponte REST: {}", e);
+            }
+            Some(rest_bridge)
+        },
+        Err(e) => {
+            error!("❌ Erro ao iniciar ponte REST: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(feature = "metrics-endpoint")]
+async fn start_metrics_server(
+    metrics: Arc<agent::AgentMetrics>,
+    profile: Arc<ActiveProfile>,
+    searches: Arc<commands::SearchRegistry>,
+) -> Option<metrics_server::MetricsServer> {
+    let port = local_http::port_from_env(metrics_server::METRICS_PORT_ENV)?;
+
+    match metrics_server::MetricsServer::start(metrics, profile, searches, port).await {
+        Ok(server) => Some(server),
+        Err(e) => {
+            error!("❌ Erro ao iniciar endpoint de métricas: {}", e);
+            None
+        }
+    }
+}
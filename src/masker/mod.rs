@@ -1,37 +1,175 @@
-use regex::Regex;
-use std::collections::HashMap;
+use regex::{Regex, RegexSet};
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
 use tracing::{debug, warn};
 use crate::agent::KeyEvent;
 
+mod validation;
+pub use validation::{validate_pattern, PatternRisk, PatternValidation};
+
 #[derive(Debug, Clone)]
 pub struct Masker {
     patterns: HashMap<String, Regex>,
+    /// Padrões adicionados com `add_custom_pattern`, que podem casar sem os
+    /// caracteres de `has_trigger_char`
+    custom_patterns: HashSet<String>,
+    prefilter: Prefilter,
+    sensitive_contexts: Vec<String>,
+}
+
+/// Todos os padrões num único `RegexSet`, para descobrir numa só varredura
+/// quais podem casar e pular os demais
+#[derive(Debug, Clone)]
+struct Prefilter {
+    /// `None` se o conjunto não compilou (ex.: limite de tamanho); aí cada
+    /// padrão é testado individualmente
+    set: Option<RegexSet>,
+    names: Vec<String>,
+    /// Só há padrões embutidos: texto sem caractere de gatilho não tem candidatos
+    quick_reject: bool,
+}
+
+impl Prefilter {
+    fn build(patterns: &HashMap<String, Regex>, quick_reject: bool) -> Self {
+        let names: Vec<String> = patterns.keys().cloned().collect();
+        let set = match RegexSet::new(names.iter().map(|name| patterns[name].as_str())) {
+            Ok(set) => Some(set),
+            Err(e) => {
+                warn!("⚠️ Pré-filtro de padrões indisponível: {}", e);
+                None
+            }
+        };
+        Self { set, names, quick_reject }
+    }
+
+    /// Nomes dos padrões que casam em `text`, ou `None` sem pré-filtro
+    fn candidates(&self, text: &str) -> Option<Vec<&str>> {
+        if self.quick_reject && !has_trigger_char(text) {
+            return Some(Vec::new());
+        }
+        let set = self.set.as_ref()?;
+        Some(set.matches(text).into_iter().map(|index| self.names[index].as_str()).collect())
+    }
+}
+
+/// Todo padrão embutido exige um dígito (`\d` inclui dígitos Unicode), um `@`
+/// (email) ou um `:` (IPv6). A maior parte do texto digitado não tem nenhum, e
+/// esta varredura de bytes custa bem menos que o `RegexSet`
+fn has_trigger_char(text: &str) -> bool {
+    text.bytes().any(|b| b.is_ascii_digit() || b == b'@' || b == b':')
+        || (!text.is_ascii() && text.chars().any(char::is_numeric))
+}
+
+/// Resultado detalhado do mascaramento, no mesmo formato do masker-service
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaskingResult {
+    pub masked_text: String,
+    pub detected_patterns: Vec<String>,
+    pub pattern_counts: HashMap<String, usize>,
+}
+
+/// Grau de mascaramento aplicado às teclas de uma aplicação
+/// (`AgentConfig.app_masking_policies`), do mais leve ao mais restritivo
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MaskingLevel {
+    /// Teclas gravadas sem os padrões de PII
+    None,
+    /// Padrões de PII (email, CPF, cartão...)
+    #[default]
+    Standard,
+    /// Todo o conteúdo vira asteriscos, como em contextos sensíveis
+    Aggressive,
 }
 
+/// Palavras-chave de janela que indicam campos sensíveis (senha, login etc.)
+pub const DEFAULT_SENSITIVE_CONTEXTS: &[&str] = &[
+    "password", "senha", "login", "sign in", "signin", "entrar",
+    "credential", "credencial", "token", "secret", "2fa", "otp",
+];
+
+/// Conjunto de padrões brasileiros, habilitado por padrão
+pub const PATTERN_SET_BR: &str = "br";
+/// Conjunto de padrões norte-americanos (SSN, telefone, ZIP+4)
+pub const PATTERN_SET_US: &str = "us";
+
+/// Conjuntos de padrões separados por vírgula (ex.: `br,us`); sem a variável, só `br`
+pub const MASKER_LOCALES_ENV: &str = "KEYAI_MASKER_LOCALES";
+
 impl Masker {
     pub fn new() -> Self {
+        Self::new_with_sets(&[PATTERN_SET_BR])
+    }
+
+    /// Cria um masker com os conjuntos definidos em `KEYAI_MASKER_LOCALES`
+    pub fn from_env() -> Self {
+        match std::env::var(MASKER_LOCALES_ENV) {
+            Ok(value) => Self::from_locale_list(&value),
+            Err(_) => Self::new(),
+        }
+    }
+
+    /// Lista separada por vírgula; vazia equivale ao padrão
+    fn from_locale_list(value: &str) -> Self {
+        let sets: Vec<&str> = value.split(',').map(str::trim).filter(|set| !set.is_empty()).collect();
+        if sets.is_empty() {
+            return Self::new();
+        }
+        Self::new_with_sets(&sets)
+    }
+
+    /// Cria um masker com os conjuntos de padrões informados (ex.: `["br", "us"]`).
+    /// Email, cartão de crédito, IBAN e IPv6 independem de localidade e estão sempre ativos.
+    pub fn new_with_sets<S: AsRef<str>>(pattern_sets: &[S]) -> Self {
         let mut patterns = HashMap::new();
         
+        // Email pattern (local part and domain may contain accented letters)
+        if let Ok(email_regex) = Regex::new(r"\b[\p{L}\p{N}._%+-]+@[\p{L}\p{N}.-]+\.[A-Z|a-z]{2,}\b") {
+            patterns.insert("email".to_string(), email_regex);
+        }
+        
+        // Credit card pattern (basic)
+        if let Ok(cc_regex) = Regex::new(r"\b\d{4}[\s-]?\d{4}[\s-]?\d{4}[\s-]?\d{4}\b") {
+            patterns.insert("credit_card".to_string(), cc_regex);
+        }
+
+        // IBAN pattern (country code + check digits + BBAN), validated via mod-97
+        if let Ok(iban_regex) = Regex::new(r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]){11,30}\b") {
+            patterns.insert("iban".to_string(), iban_regex);
+        }
+
+        // IPv6 candidates (full, compressed and IPv4-suffixed forms); the match is
+        // parsed with Ipv6Addr, so times like 12:34:56 and MAC addresses are kept
+        if let Ok(ipv6_regex) = Regex::new(r"(?i)(?:\b[0-9a-f]{1,4}|\B:)(?::[0-9a-f]{0,4}){1,7}(?:(?:\.\d{1,3}){3})?") {
+            patterns.insert("ipv6".to_string(), ipv6_regex);
+        }
+
+        for set in pattern_sets {
+            match set.as_ref().to_lowercase().as_str() {
+                PATTERN_SET_BR => Self::insert_br_patterns(&mut patterns),
+                PATTERN_SET_US => Self::insert_us_patterns(&mut patterns),
+                other => warn!("⚠️ Conjunto de padrões desconhecido: {}", other),
+            }
+        }
+
+        Self {
+            prefilter: Prefilter::build(&patterns, true),
+            patterns,
+            custom_patterns: HashSet::new(),
+            sensitive_contexts: DEFAULT_SENSITIVE_CONTEXTS.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+
+    fn insert_br_patterns(patterns: &mut HashMap<String, Regex>) {
         // CPF pattern (000.000.000-00 or 00000000000)
         if let Ok(cpf_regex) = Regex::new(r"\b\d{3}\.?\d{3}\.?\d{3}-?\d{2}\b") {
             patterns.insert("cpf".to_string(), cpf_regex);
         }
         
-        // Email pattern
-        if let Ok(email_regex) = Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b") {
-            patterns.insert("email".to_string(), email_regex);
-        }
-        
         // Phone pattern (Brazilian format)
         if let Ok(phone_regex) = Regex::new(r"\b(?:\+55\s?)?\(?[1-9]{2}\)?\s?9?\d{4}-?\d{4}\b") {
             patterns.insert("phone".to_string(), phone_regex);
         }
         
-        // Credit card pattern (basic)
-        if let Ok(cc_regex) = Regex::new(r"\b\d{4}[\s-]?\d{4}[\s-]?\d{4}[\s-]?\d{4}\b") {
-            patterns.insert("credit_card".to_string(), cc_regex);
-        }
-        
         // RG pattern (Brazilian ID)
         if let Ok(rg_regex) = Regex::new(r"\b\d{1,2}\.?\d{3}\.?\d{3}-?[0-9X]\b") {
             patterns.insert("rg".to_string(), rg_regex);
@@ -41,13 +179,46 @@ impl Masker {
         if let Ok(cnpj_regex) = Regex::new(r"\b\d{2}\.?\d{3}\.?\d{3}/?\d{4}-?\d{2}\b") {
             patterns.insert("cnpj".to_string(), cnpj_regex);
         }
+    }
+
+    fn insert_us_patterns(patterns: &mut HashMap<String, Regex>) {
+        // SSN pattern (000-00-0000), area/group/serial validated in is_valid_match
+        if let Ok(ssn_regex) = Regex::new(r"\b\d{3}-\d{2}-\d{4}\b") {
+            patterns.insert("ssn".to_string(), ssn_regex);
+        }
+
+        // Phone pattern (NANP: (555) 234-5678, 555-234-5678, +1 555.234.5678)
+        if let Ok(us_phone_regex) = Regex::new(r"(?:\+1[\s.-]?)?(?:\([2-9]\d{2}\)|\b[2-9]\d{2})[\s.-]?[2-9]\d{2}[\s.-]\d{4}\b") {
+            patterns.insert("us_phone".to_string(), us_phone_regex);
+        }
+
+        // ZIP+4 pattern (00000-0000)
+        if let Ok(zip_regex) = Regex::new(r"\b\d{5}-\d{4}\b") {
+            patterns.insert("zip_plus4".to_string(), zip_regex);
+        }
+    }
 
-        Self { patterns }
+    pub fn mask_event(&self, event: KeyEvent) -> KeyEvent {
+        self.mask_event_with_level(event, MaskingLevel::Standard)
     }
 
-    pub fn mask_event(&self, mut event: KeyEvent) -> KeyEvent {
-        // Mascara o conteúdo da tecla
-        event.key = self.mask_text(&event.key);
+    /// Mascara o evento no nível informado. Contextos sensíveis continuam
+    /// recebendo o mascaramento reforçado mesmo com `MaskingLevel::None`
+    pub fn mask_event_with_level(&self, mut event: KeyEvent, level: MaskingLevel) -> KeyEvent {
+        // Em contextos sensíveis nenhum caractere digitado é persistido legível
+        let sensitive = event
+            .window_info
+            .as_ref()
+            .map(|window_info| self.is_sensitive_context(&window_info.title, &window_info.application))
+            .unwrap_or(false);
+
+        if (sensitive || level == MaskingLevel::Aggressive) && !event.is_modifier {
+            debug!("🔒 Contexto sensível ou política agressiva - aplicando mascaramento reforçado");
+            event.key = Self::apply_enhanced_masking(&event.key);
+        } else if level == MaskingLevel::Standard {
+            // Mascara o conteúdo da tecla
+            event.key = self.mask_text(&event.key);
+        }
         
         // Mascara informações da janela se existirem
         if let Some(window_info) = &mut event.window_info {
@@ -58,26 +229,174 @@ impl Masker {
         event
     }
 
+    /// Nível da política cuja chave aparece no nome da aplicação (sem diferenciar
+    /// maiúsculas). Se mais de uma casar, vale a mais restritiva
+    pub fn level_for_application(application: &str, policies: &HashMap<String, MaskingLevel>) -> MaskingLevel {
+        let application = application.to_lowercase();
+        policies
+            .iter()
+            .filter(|(name, _)| !name.is_empty() && application.contains(&name.to_lowercase()))
+            .map(|(_, level)| *level)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Indica se o título ou aplicação da janela contém alguma palavra-chave sensível
+    pub fn is_sensitive_context(&self, title: &str, application: &str) -> bool {
+        let title = title.to_lowercase();
+        let application = application.to_lowercase();
+
+        self.sensitive_contexts
+            .iter()
+            .any(|keyword| title.contains(keyword.as_str()) || application.contains(keyword.as_str()))
+    }
+
+    /// Substitui todo o conteúdo por asteriscos, preservando apenas o comprimento
+    fn apply_enhanced_masking(text: &str) -> String {
+        "*".repeat(text.chars().count())
+    }
+
+    /// Substitui a lista de palavras-chave de contexto sensível
+    pub fn set_sensitive_contexts(&mut self, keywords: Vec<String>) {
+        self.sensitive_contexts = keywords
+            .into_iter()
+            .map(|keyword| keyword.trim().to_lowercase())
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+    }
+
+    pub fn sensitive_contexts(&self) -> &[String] {
+        &self.sensitive_contexts
+    }
+
     pub fn mask_text(&self, text: &str) -> String {
+        self.mask_text_detailed(text).masked_text
+    }
+
+    /// Mascara o texto e informa quais padrões foram aplicados e quantas vezes
+    pub fn mask_text_detailed(&self, text: &str) -> MaskingResult {
+        self.apply_patterns(text, self.ordered_patterns())
+    }
+
+    /// Mascara o texto usando apenas o padrão `pattern_name` (`None` se ele não existe)
+    pub fn mask_text_with_pattern(&self, text: &str, pattern_name: &str) -> Option<MaskingResult> {
+        let pattern = self.patterns.get_key_value(pattern_name)?;
+        Some(self.apply_patterns(text, vec![pattern]))
+    }
+
+    fn apply_patterns(&self, text: &str, patterns: Vec<(&String, &Regex)>) -> MaskingResult {
         let mut masked_text = text.to_string();
+        let mut detected_patterns = Vec::new();
+        let mut pattern_counts = HashMap::new();
+        // Valid only while nothing was replaced; a mask may create or remove matches
+        let candidates = self.prefilter.candidates(text);
+        if candidates.as_ref().is_some_and(Vec::is_empty) {
+            return MaskingResult { masked_text, detected_patterns, pattern_counts };
+        }
+        let mut changed = false;
         
-        for (pattern_name, regex) in &self.patterns {
-            if regex.is_match(&masked_text) {
-                debug!("🔒 Mascarando padrão {} no texto", pattern_name);
+        for (pattern_name, regex) in patterns {
+            let may_match = match &candidates {
+                Some(candidates) if !changed => candidates.contains(&pattern_name.as_str()),
+                _ => regex.is_match(&masked_text),
+            };
+            if may_match {
+                let mut count = 0;
                 masked_text = regex.replace_all(&masked_text, |caps: &regex::Captures| {
-                    self.generate_mask(&caps[0], pattern_name)
+                    let next = caps.get(0).and_then(|m| masked_text[m.end()..].chars().next());
+                    if Self::is_valid_match(&caps[0], pattern_name) && Self::ends_cleanly(next, pattern_name) {
+                        count += 1;
+                        self.generate_mask(&caps[0], pattern_name)
+                    } else {
+                        caps[0].to_string()
+                    }
                 }).to_string();
+
+                if count > 0 {
+                    changed = true;
+                    debug!("🔒 Mascarando padrão {} no texto ({} ocorrências)", pattern_name, count);
+                    detected_patterns.push(pattern_name.clone());
+                    pattern_counts.insert(pattern_name.clone(), count);
+                }
             }
         }
         
-        masked_text
+        MaskingResult {
+            masked_text,
+            detected_patterns,
+            pattern_counts,
+        }
+    }
+
+    /// Padrões em ordem de aplicação: IBAN antes dos demais, para que padrões
+    /// numéricos (ex.: cartão) não consumam parte do BBAN antes da validação
+    fn ordered_patterns(&self) -> Vec<(&String, &Regex)> {
+        let mut ordered: Vec<_> = self.patterns.iter().collect();
+        ordered.sort_by_key(|(name, _)| (name.as_str() != "iban", name.as_str()));
+        ordered
+    }
+
+    /// Valida um IBAN pelo algoritmo mod-97 (ISO 13616)
+    fn is_valid_iban(candidate: &str) -> bool {
+        let compact: String = candidate.chars().filter(|c| !c.is_whitespace()).collect();
+        // IBAN é sempre ASCII; `\d` da regex também aceita dígitos Unicode
+        if !compact.is_ascii() || !(15..=34).contains(&compact.len()) {
+            return false;
+        }
+
+        // Move country code + check digits to the end and fold letters as A=10..Z=35
+        let (head, tail) = compact.split_at(4);
+        let mut remainder: u32 = 0;
+        for c in tail.chars().chain(head.chars()) {
+            let value = match c.to_digit(36) {
+                Some(value) => value,
+                None => return false,
+            };
+            remainder = if value >= 10 {
+                (remainder * 100 + value) % 97
+            } else {
+                (remainder * 10 + value) % 97
+            };
+        }
+
+        remainder == 1
+    }
+
+    /// Validações que a regex sozinha não expressa (o crate `regex` não suporta lookahead)
+    fn is_valid_match(original: &str, pattern_type: &str) -> bool {
+        match pattern_type {
+            "ssn" => {
+                let mut parts = original.split('-');
+                let (area, group, serial) = match (parts.next(), parts.next(), parts.next()) {
+                    (Some(area), Some(group), Some(serial)) => (area, group, serial),
+                    _ => return false,
+                };
+                // SSA nunca emite área 000, 666 ou 900-999, grupo 00 ou serial 0000
+                area != "000" && area != "666" && !area.starts_with('9')
+                    && group != "00" && serial != "0000"
+            },
+            "iban" => Self::is_valid_iban(original),
+            // Letter-only forms like a::b or cafe::dead are far more often code
+            "ipv6" => original.bytes().any(|b| b.is_ascii_digit())
+                && original.parse::<std::net::Ipv6Addr>().is_ok(),
+            _ => true,
+        }
+    }
+
+    /// O padrão IPv6 não tem fronteira final: `db::connect` casaria `db::c`.
+    /// O caractere seguinte precisa encerrar o endereço
+    fn ends_cleanly(next: Option<char>, pattern_type: &str) -> bool {
+        match pattern_type {
+            "ipv6" => !next.is_some_and(|c| c.is_alphanumeric() || matches!(c, ':' | '.' | '_')),
+            _ => true,
+        }
     }
 
     fn generate_mask(&self, original: &str, pattern_type: &str) -> String {
         match pattern_type {
             "cpf" => {
-                if original.len() >= 11 {
-                    format!("***.***.***-{}", &original[original.len()-2..])
+                if original.chars().count() >= 11 {
+                    format!("***.***.***-{}", Self::last_chars(original, 2))
                 } else {
                     "***.***.**-**".to_string()
                 }
@@ -85,8 +404,8 @@ impl Masker {
             "email" => {
                 if let Some(at_pos) = original.find('@') {
                     let (local, domain) = original.split_at(at_pos);
-                    if local.len() > 2 {
-                        format!("{}***{}", &local[..1], &domain)
+                    if local.chars().count() > 2 {
+                        format!("{}***{}", Self::first_chars(local, 1), &domain)
                     } else {
                         "***@***".to_string()
                     }
@@ -96,16 +415,16 @@ impl Masker {
             },
             "phone" => {
                 let digits_only: String = original.chars().filter(|c| c.is_digit(10)).collect();
-                if digits_only.len() >= 8 {
-                    format!("(***) ***-{}", &digits_only[digits_only.len()-4..])
+                if digits_only.chars().count() >= 8 {
+                    format!("(***) ***-{}", Self::last_chars(&digits_only, 4))
                 } else {
                     "(***) ***-****".to_string()
                 }
             },
             "credit_card" => {
                 let digits_only: String = original.chars().filter(|c| c.is_digit(10)).collect();
-                if digits_only.len() >= 4 {
-                    format!("**** **** **** {}", &digits_only[digits_only.len()-4..])
+                if digits_only.chars().count() >= 4 {
+                    format!("**** **** **** {}", Self::last_chars(&digits_only, 4))
                 } else {
                     "**** **** **** ****".to_string()
                 }
@@ -116,21 +435,80 @@ impl Masker {
             "cnpj" => {
                 "**.***.***/****-**".to_string()
             },
+            "ssn" => {
+                if original.chars().count() >= 4 {
+                    format!("***-**-{}", Self::last_chars(original, 4))
+                } else {
+                    "***-**-****".to_string()
+                }
+            },
+            "us_phone" => {
+                let digits_only: String = original.chars().filter(|c| c.is_ascii_digit()).collect();
+                if digits_only.chars().count() >= 10 {
+                    format!("(***) ***-{}", Self::last_chars(&digits_only, 4))
+                } else {
+                    "(***) ***-****".to_string()
+                }
+            },
+            "iban" => {
+                let compact: String = original.chars().filter(|c| !c.is_whitespace()).collect();
+                let hidden = compact.chars().count().saturating_sub(4);
+                format!("{}{}", "*".repeat(hidden), Self::last_chars(&compact, 4))
+            },
+            "zip_plus4" => {
+                "*****-****".to_string()
+            },
+            "ipv6" => {
+                "****:****::****".to_string()
+            },
             _ => {
-                warn!("⚠️ Tipo de padrão desconhecido: {}", pattern_type);
-                "*".repeat(original.len())
+                // Padrões customizados não têm formato próprio de máscara
+                debug!("🔒 Aplicando máscara genérica para o padrão {}", pattern_type);
+                "*".repeat(original.chars().count())
             }
         }
     }
 
+    /// Primeiros `n` caracteres (não bytes), seguro para UTF-8 multibyte
+    fn first_chars(text: &str, n: usize) -> &str {
+        match text.char_indices().nth(n) {
+            Some((end, _)) => &text[..end],
+            None => text,
+        }
+    }
+
+    /// Últimos `n` caracteres (não bytes), seguro para UTF-8 multibyte
+    fn last_chars(text: &str, n: usize) -> &str {
+        if n == 0 {
+            return "";
+        }
+        match text.char_indices().rev().nth(n - 1) {
+            Some((start, _)) => &text[start..],
+            None => text,
+        }
+    }
+
+    /// Compila com limite de tamanho; use `validate_pattern` antes para recusar
+    /// padrões com risco de deixar o mascaramento lento
     pub fn add_custom_pattern(&mut self, name: String, pattern: String) -> Result<(), regex::Error> {
-        let regex = Regex::new(&pattern)?;
-        self.patterns.insert(name, regex);
+        let regex = validation::compile_pattern(&pattern)?;
+        self.patterns.insert(name.clone(), regex);
+        self.custom_patterns.insert(name);
+        self.rebuild_prefilter();
         Ok(())
     }
 
     pub fn remove_pattern(&mut self, name: &str) -> bool {
-        self.patterns.remove(name).is_some()
+        let removed = self.patterns.remove(name).is_some();
+        if removed {
+            self.custom_patterns.remove(name);
+            self.rebuild_prefilter();
+        }
+        removed
+    }
+
+    fn rebuild_prefilter(&mut self) {
+        self.prefilter = Prefilter::build(&self.patterns, self.custom_patterns.is_empty());
     }
 
     pub fn list_patterns(&self) -> Vec<String> {
@@ -168,7 +546,7 @@ mod tests {
         // Test short email
         let text = "a@b.com";
         let masked = masker.mask_text(text);
-        assert!(masked.contains("a***@b.com"));
+        assert_eq!(masked, "***@***");
     }
 
     #[test]
@@ -238,15 +616,28 @@ mod tests {
             timestamp: 1234567890,
             key: "test@example.com".to_string(),
             event_type: "press".to_string(),
-            window_info: Some("Email: test@example.com - Phone: (11) 99999-1234".to_string()),
+            window_info: Some(crate::agent::WindowInfo {
+                title: "Email: test@example.com - Phone: (11) 99999-1234".to_string(),
+                application: "thunderbird".to_string(),
+                process_id: None,
+                timestamp: 1234567890,
+            }),
             is_modifier: false,
             is_function_key: false,
+            session_id: Some("sessao-1".to_string()),
+            modifiers: vec!["Shift".to_string()],
         };
         
         let masked_event = masker.mask_event(event);
         
         assert_eq!(masked_event.key, "t***@example.com");
-        assert_eq!(masked_event.window_info, Some("Email: t***@example.com - Phone: (***) ***-1234".to_string()));
+        let window_info = masked_event.window_info.unwrap();
+        assert!(window_info.title.starts_with("Email: t***@example.com - Phone: "), "{}", window_info.title);
+        assert!(!window_info.title.contains("99999-1234"));
+        assert_eq!(window_info.application, "thunderbird");
+        // Sessão e modificadores não passam pelo mascaramento
+        assert_eq!(masked_event.session_id.as_deref(), Some("sessao-1"));
+        assert_eq!(masked_event.modifiers, vec!["Shift".to_string()]);
     }
 
     #[test]
@@ -325,4 +716,403 @@ mod tests {
         // Test short phone number
         assert_eq!(masker.generate_mask("1234", "phone"), "(***) ***-****");
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_custom_pattern_lifecycle() {
+        let mut masker = Masker::new();
+
+        masker.add_custom_pattern("ticket".to_string(), r"TICKET-\d+".to_string()).unwrap();
+        assert!(masker.list_patterns().contains(&"ticket".to_string()));
+        assert_eq!(masker.mask_text("ver TICKET-42"), "ver *********");
+
+        assert!(masker.remove_pattern("ticket"));
+        assert!(!masker.list_patterns().contains(&"ticket".to_string()));
+        assert_eq!(masker.mask_text("ver TICKET-42"), "ver TICKET-42");
+    }
+
+    #[test]
+    fn test_add_invalid_custom_pattern() {
+        let mut masker = Masker::new();
+        let before = masker.list_patterns().len();
+
+        assert!(masker.add_custom_pattern("broken".to_string(), "(unclosed".to_string()).is_err());
+        assert_eq!(masker.list_patterns().len(), before);
+    }
+
+    #[test]
+    fn test_default_sets_are_br_only() {
+        let patterns = Masker::new().list_patterns();
+        assert!(patterns.contains(&"cpf".to_string()));
+        assert!(!patterns.contains(&"ssn".to_string()));
+        assert!(!patterns.contains(&"us_phone".to_string()));
+        assert!(!patterns.contains(&"zip_plus4".to_string()));
+    }
+
+    #[test]
+    fn test_ssn_masking() {
+        let masker = Masker::new_with_sets(&[PATTERN_SET_US]);
+
+        assert_eq!(masker.mask_text("SSN: 123-45-6789"), "SSN: ***-**-6789");
+
+        // Números que a SSA nunca emite não são mascarados
+        assert_eq!(masker.mask_text("000-12-3456"), "000-12-3456");
+        assert_eq!(masker.mask_text("666-12-3456"), "666-12-3456");
+        assert_eq!(masker.mask_text("912-34-5678"), "912-34-5678");
+        assert_eq!(masker.mask_text("123-00-4567"), "123-00-4567");
+        assert_eq!(masker.mask_text("123-45-0000"), "123-45-0000");
+    }
+
+    #[test]
+    fn test_us_phone_masking() {
+        let masker = Masker::new_with_sets(&[PATTERN_SET_US]);
+
+        assert_eq!(masker.mask_text("Call (415) 555-2671"), "Call (***) ***-2671");
+        assert_eq!(masker.mask_text("415-555-2671"), "(***) ***-2671");
+        assert_eq!(masker.mask_text("+1 415.555.2671"), "(***) ***-2671");
+    }
+
+    #[test]
+    fn test_zip_plus4_masking() {
+        let masker = Masker::new_with_sets(&[PATTERN_SET_US]);
+
+        assert_eq!(masker.mask_text("Beverly Hills, CA 90210-1234"), "Beverly Hills, CA *****-****");
+        // ZIP de 5 dígitos sozinho não é considerado PII
+        assert_eq!(masker.mask_text("CA 90210"), "CA 90210");
+    }
+
+    #[test]
+    fn test_br_and_us_sets_together() {
+        let masker = Masker::new_with_sets(&["br", "US"]);
+
+        let masked = masker.mask_text("CPF 123.456.789-01 SSN 123-45-6789");
+        assert!(masked.contains("***.***.***-01"));
+        assert!(masked.contains("***-**-6789"));
+
+        assert!(masker.mask_text("Telefone: (11) 99999-1234").contains("(***) ***-1234"));
+        assert!(masker.mask_text("joao@exemplo.com").contains("j***@exemplo.com"));
+    }
+
+    #[test]
+    fn test_locale_list_selects_pattern_sets() {
+        let masker = Masker::from_locale_list(" br, us ");
+        let masked = masker.mask_text("CPF 123.456.789-01 SSN 123-45-6789");
+        assert!(masked.contains("***.***.***-01"));
+        assert!(masked.contains("***-**-6789"));
+
+        let us_only = Masker::from_locale_list("us").list_patterns();
+        assert!(us_only.contains(&"ssn".to_string()));
+        assert!(!us_only.contains(&"cpf".to_string()));
+
+        assert_eq!(Masker::from_locale_list(" , ").list_patterns().len(), Masker::new().list_patterns().len());
+    }
+
+    #[test]
+    fn test_unknown_pattern_set_is_ignored() {
+        let masker = Masker::new_with_sets(&["xx"]);
+        let mut patterns = masker.list_patterns();
+        patterns.sort();
+        assert_eq!(patterns, vec!["credit_card", "email", "iban", "ipv6"]);
+    }
+
+    #[test]
+    fn test_iban_masking() {
+        let masker = Masker::new();
+
+        // DE com agrupamento em blocos de 4
+        let masked = masker.mask_text("IBAN: DE89 3704 0044 0532 0130 00");
+        assert_eq!(masked, "IBAN: ******************3000");
+
+        // BR compacto
+        let masked = masker.mask_text("BR1800360305000010009795493C1");
+        assert_eq!(masked, "*************************93C1");
+    }
+
+    #[test]
+    fn test_invalid_iban_left_alone() {
+        let masker = Masker::new();
+
+        // Dígito verificador errado
+        let text = "DE88370400440532013000";
+        assert_eq!(masker.mask_text(text), text);
+
+        // Alfanumérico qualquer com formato semelhante
+        let text = "AB12CDEFGHIJKLMN";
+        assert_eq!(masker.mask_text(text), text);
+    }
+
+    #[test]
+    fn test_ipv6_masking() {
+        let masker = Masker::new();
+
+        assert_eq!(masker.mask_text("host 2001:db8::1 fora"), "host ****:****::**** fora");
+        assert_eq!(masker.mask_text("::1"), "****:****::****");
+        assert_eq!(masker.mask_text("fe80::1ff:fe23:4567:890a"), "****:****::****");
+        assert_eq!(masker.mask_text("2001:0db8:85a3:0000:0000:8a2e:0370:7334"), "****:****::****");
+        assert_eq!(masker.mask_text("::ffff:192.0.2.1"), "****:****::****");
+        assert_eq!(masker.mask_text("[2001:db8::ff00:42:8329]:8080"), "[****:****::****]:8080");
+    }
+
+    #[test]
+    fn test_ipv6_lookalikes_left_alone() {
+        let masker = Masker::new();
+
+        for text in [
+            "reunião às 12:34:56",
+            "placar 3:2",
+            "mac 00:1A:2B:3C:4D:5E",
+            "std::vector<int>",
+            "use db::connect;",
+            "Foo::bar()",
+            "a::b",
+            "cafe::deadline",
+            "C::Users",
+            "fe80::1abcz",
+        ] {
+            assert_eq!(masker.mask_text(text), text);
+        }
+    }
+
+    #[test]
+    fn test_is_valid_iban() {
+        assert!(Masker::is_valid_iban("GB82 WEST 1234 5698 7654 32"));
+        assert!(Masker::is_valid_iban("DE89370400440532013000"));
+        assert!(!Masker::is_valid_iban("GB82 WEST 1234 5698 7654 33"));
+        assert!(!Masker::is_valid_iban("DE89"));
+    }
+
+    fn key_in_window(key: &str, title: &str) -> KeyEvent {
+        KeyEvent {
+            timestamp: 0,
+            key: key.to_string(),
+            event_type: "press".to_string(),
+            window_info: Some(crate::agent::WindowInfo {
+                title: title.to_string(),
+                application: "firefox".to_string(),
+                process_id: None,
+                timestamp: 0,
+            }),
+            is_modifier: false,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_sensitive_context_masks_keystrokes() {
+        let masker = Masker::new();
+
+        let masked = masker.mask_event(key_in_window("a", "Login - Banco"));
+        assert_eq!(masked.key, "*");
+
+        let masked = masker.mask_event(key_in_window("a", "Notes"));
+        assert_eq!(masked.key, "a");
+    }
+
+    #[test]
+    fn test_masking_levels_by_application() {
+        let masker = Masker::new();
+        let policies = HashMap::from([
+            ("gedit".to_string(), MaskingLevel::None),
+            ("Banco".to_string(), MaskingLevel::Aggressive),
+        ]);
+        let email_in = |application: &str| {
+            let mut event = key_in_window("joao@exemplo.com", "Notas");
+            event.window_info.as_mut().unwrap().application = application.to_string();
+            let level = Masker::level_for_application(application, &policies);
+            masker.mask_event_with_level(event, level).key
+        };
+
+        assert_eq!(email_in("gedit"), "joao@exemplo.com");
+        assert_eq!(email_in("firefox"), "j***@exemplo.com");
+        assert_eq!(email_in("App do banco"), "*".repeat("joao@exemplo.com".len()));
+
+        // Janelas sensíveis continuam mascaradas mesmo sem política de PII
+        let masked = masker.mask_event_with_level(key_in_window("a", "Login - Banco"), MaskingLevel::None);
+        assert_eq!(masked.key, "*");
+    }
+
+    #[test]
+    fn test_custom_sensitive_contexts() {
+        let mut masker = Masker::new();
+        masker.set_sensitive_contexts(vec![" Vault ".to_string(), "".to_string()]);
+        assert_eq!(masker.sensitive_contexts(), &["vault".to_string()]);
+
+        assert_eq!(masker.mask_event(key_in_window("x", "My Vault")).key, "*");
+        assert_eq!(masker.mask_event(key_in_window("x", "Login")).key, "x");
+    }
+
+    #[test]
+    fn test_mask_text_detailed_counts() {
+        let masker = Masker::new();
+
+        let result = masker.mask_text_detailed(
+            "Contatos: joao@exemplo.com, maria@exemplo.com, tel (11) 99999-1234"
+        );
+
+        assert_eq!(result.pattern_counts.get("email"), Some(&2));
+        assert_eq!(result.pattern_counts.get("phone"), Some(&1));
+        assert_eq!(result.pattern_counts.len(), 2);
+        assert!(result.detected_patterns.contains(&"email".to_string()));
+        assert!(result.detected_patterns.contains(&"phone".to_string()));
+        assert!(result.masked_text.contains("j***@exemplo.com"));
+        assert!(result.masked_text.contains("(***) ***-1234"));
+        assert_eq!(result.masked_text, masker.mask_text("Contatos: joao@exemplo.com, maria@exemplo.com, tel (11) 99999-1234"));
+    }
+
+    #[test]
+    fn test_mask_text_with_single_pattern() {
+        let masker = Masker::new();
+        let text = "Contatos: joao@exemplo.com, tel (11) 99999-1234";
+
+        let result = masker.mask_text_with_pattern(text, "email").unwrap();
+        assert_eq!(result.detected_patterns, vec!["email".to_string()]);
+        assert_eq!(result.pattern_counts.get("email"), Some(&1));
+        assert!(result.masked_text.contains("j***@exemplo.com"));
+        // O telefone fica intacto quando só o email é aplicado
+        assert!(result.masked_text.contains("(11) 99999-1234"));
+
+        assert!(masker.mask_text_with_pattern(text, "inexistente").is_none());
+    }
+
+    #[test]
+    fn test_mask_text_detailed_skips_invalid_matches() {
+        let masker = Masker::new();
+
+        let result = masker.mask_text_detailed("DE88370400440532013000");
+        assert!(result.detected_patterns.is_empty());
+        assert!(result.pattern_counts.is_empty());
+    }
+
+    #[test]
+    fn test_char_helpers_respect_utf8_boundaries() {
+        assert_eq!(Masker::first_chars("ágata", 1), "á");
+        assert_eq!(Masker::first_chars("é", 3), "é");
+        assert_eq!(Masker::last_chars("ação🎉", 2), "o🎉");
+        assert_eq!(Masker::last_chars("ab", 4), "ab");
+        assert_eq!(Masker::last_chars("ab", 0), "");
+    }
+
+    #[test]
+    fn test_mask_accented_email_local_part() {
+        let masker = Masker::new();
+
+        assert_eq!(masker.mask_text("ágata@exemplo.com"), "á***@exemplo.com");
+        assert_eq!(masker.mask_text("contato: joão.silva@correio.com.br"), "contato: j***@correio.com.br");
+        // Parte local curta continua totalmente mascarada
+        assert_eq!(masker.mask_text("é@exemplo.com"), "***@***");
+    }
+
+    #[test]
+    fn test_mask_with_emoji_around_matches() {
+        let masker = Masker::new();
+
+        assert_eq!(masker.mask_text("📧 joao@exemplo.com 🎉"), "📧 j***@exemplo.com 🎉");
+        assert_eq!(masker.mask_text("CPF 🔒 123.456.789-09 ✓"), "CPF 🔒 ***.***.***-09 ✓");
+    }
+
+    #[test]
+    fn test_mask_unicode_digits_does_not_panic() {
+        let masker = Masker::new();
+
+        // `\d` aceita dígitos árabe-índicos (2 bytes cada)
+        let masked = masker.mask_text("١٢٣.٤٥٦.٧٨٩-٠٩");
+        assert_eq!(masked, "***.***.***-٠٩");
+    }
+
+    #[test]
+    fn test_custom_mask_length_counts_chars() {
+        let mut masker = Masker::new();
+        masker.add_custom_pattern("nome".to_string(), r"Jo[aã]o".to_string()).unwrap();
+
+        assert_eq!(masker.mask_text("olá João"), "olá ****");
+    }
+
+    /// Implementação anterior ao pré-filtro: `is_match` seguido de `replace_all` em cada padrão
+    fn mask_without_prefilter(masker: &Masker, text: &str) -> String {
+        let mut masked = text.to_string();
+        for (name, regex) in masker.ordered_patterns() {
+            if regex.is_match(&masked) {
+                masked = regex.replace_all(&masked, |caps: &regex::Captures| {
+                    let next = caps.get(0).and_then(|m| masked[m.end()..].chars().next());
+                    if Masker::is_valid_match(&caps[0], name) && Masker::ends_cleanly(next, name) {
+                        masker.generate_mask(&caps[0], name)
+                    } else {
+                        caps[0].to_string()
+                    }
+                }).to_string();
+            }
+        }
+        masked
+    }
+
+    #[test]
+    fn test_prefilter_matches_previous_output() {
+        let corpus = [
+            "",
+            "texto comum sem dados pessoais",
+            "Meu CPF é 123.456.789-01",
+            "CPF: 12345678901",
+            "Contatos: joao@exemplo.com, maria@exemplo.com, tel (11) 99999-1234",
+            "cartão 4111 1111 1111 1111 vence 12/30",
+            "IBAN DE89 3704 0044 0532 0130 00 e GB82WEST12345698765432",
+            "CNPJ 12.345.678/0001-90 e RG 12.345.678-9",
+            "SSN 123-45-6789, inválido 000-12-3456, ZIP 12345-6789, (555) 234-5678",
+            "contato: joão.silva@correio.com.br 📧 🎉",
+            "١٢٣.٤٥٦.٧٨٩-٠٩",
+            "olá João",
+        ];
+
+        let mut masker = Masker::new_with_sets(&[PATTERN_SET_BR, PATTERN_SET_US]);
+        masker.add_custom_pattern("nome".to_string(), r"Jo[aã]o".to_string()).unwrap();
+
+        for text in corpus {
+            assert_eq!(masker.mask_text(text), mask_without_prefilter(&masker, text), "texto: {}", text);
+        }
+
+        // Sem o pré-filtro (conjunto não compilado) o resultado é o mesmo
+        let mut fallback = masker.clone();
+        fallback.prefilter.set = None;
+        for text in corpus {
+            assert_eq!(fallback.mask_text(text), masker.mask_text(text), "texto: {}", text);
+        }
+
+        // Padrões removidos saem também do pré-filtro
+        assert!(masker.remove_pattern("nome"));
+        assert_eq!(masker.mask_text("olá João"), "olá João");
+    }
+
+    #[test]
+    fn test_quick_reject_matches_regex_set() {
+        let corpus = [
+            "texto comum sem dados pessoais",
+            "reunião às dez com a equipe 🎉",
+            "a@b.co",
+            "::1 e fe80::1",
+            "١٢٣.٤٥٦.٧٨٩-٠٩",
+            "DE89 3704 0044 0532 0130 00",
+            "Meu CPF é 123.456.789-01",
+        ];
+
+        let masker = Masker::new_with_sets(&[PATTERN_SET_BR, PATTERN_SET_US]);
+        assert!(masker.prefilter.quick_reject);
+        let mut without_quick_reject = masker.clone();
+        without_quick_reject.prefilter.quick_reject = false;
+
+        for text in corpus {
+            assert_eq!(masker.mask_text_detailed(text), without_quick_reject.mask_text_detailed(text), "texto: {}", text);
+            assert_eq!(masker.mask_text(text), mask_without_prefilter(&masker, text), "texto: {}", text);
+        }
+        assert!(!has_trigger_char("reunião às dez com a equipe 🎉"));
+        assert!(has_trigger_char("٣"));
+
+        // Padrões customizados podem casar só com letras
+        let mut custom = masker.clone();
+        custom.add_custom_pattern("nome".to_string(), r"Jo[aã]o".to_string()).unwrap();
+        assert!(!custom.prefilter.quick_reject);
+        assert_eq!(custom.mask_text("olá João"), "olá ****");
+
+        assert!(custom.remove_pattern("nome"));
+        assert!(custom.prefilter.quick_reject);
+    }
+}
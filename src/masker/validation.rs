@@ -0,0 +1,184 @@
+//! Validação de padrões customizados antes de entrarem no masker.
+//!
+//! O crate `regex` não faz backtracking, mas repetições aninhadas ainda geram
+//! autômatos enormes e deixam o mascaramento de cada tecla lento. O padrão é
+//! compilado com limites de tamanho, inspecionado em busca de quantificadores
+//! aninhados e executado contra textos adversariais com prazo.
+
+use std::sync::mpsc;
+use std::time::Duration;
+use regex::{Regex, RegexBuilder};
+use serde::{Serialize, Deserialize};
+
+const MAX_PATTERN_LEN: usize = 512;
+const PATTERN_SIZE_LIMIT: usize = 256 * 1024;
+const PATTERN_DFA_SIZE_LIMIT: usize = 1024 * 1024;
+
+/// Tamanho de cada texto adversarial e prazo para varrer todos
+const SAMPLE_LEN: usize = 4096;
+const SAMPLE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Risco estimado de o padrão travar ou tornar lento o mascaramento
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternRisk {
+    Low,
+    High,
+}
+
+/// Resultado de `validate_pattern`; só padrões `valid` são aceitos
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternValidation {
+    pub valid: bool,
+    pub compile_error: Option<String>,
+    pub estimated_risk: PatternRisk,
+}
+
+impl PatternValidation {
+    fn rejected(compile_error: Option<String>, estimated_risk: PatternRisk) -> Self {
+        Self { valid: false, compile_error, estimated_risk }
+    }
+}
+
+/// Verifica se `pattern` pode ser usado como padrão de mascaramento
+pub fn validate_pattern(pattern: &str) -> PatternValidation {
+    if pattern.trim().is_empty() {
+        return PatternValidation::rejected(Some("Expressão regular vazia".to_string()), PatternRisk::Low);
+    }
+    if pattern.chars().count() > MAX_PATTERN_LEN {
+        return PatternValidation::rejected(
+            Some(format!("Expressão regular excede {} caracteres", MAX_PATTERN_LEN)),
+            PatternRisk::High,
+        );
+    }
+
+    let regex = match compile_pattern(pattern) {
+        Ok(regex) => regex,
+        Err(regex::Error::CompiledTooBig(limit)) => {
+            return PatternValidation::rejected(
+                Some(format!("Expressão regular complexa demais (limite de {} bytes)", limit)),
+                PatternRisk::High,
+            );
+        }
+        Err(e) => return PatternValidation::rejected(Some(e.to_string()), PatternRisk::Low),
+    };
+
+    if has_nested_quantifier(pattern) || !finishes_on_samples(regex) {
+        return PatternValidation::rejected(None, PatternRisk::High);
+    }
+
+    PatternValidation { valid: true, compile_error: None, estimated_risk: PatternRisk::Low }
+}
+
+/// Compila com os mesmos limites usados na validação
+pub(super) fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .size_limit(PATTERN_SIZE_LIMIT)
+        .dfa_size_limit(PATTERN_DFA_SIZE_LIMIT)
+        .build()
+}
+
+/// Detecta um grupo com quantificador interno repetido por fora, como `(a+)+`
+/// ou `(\w*\s?)*`, a forma clássica de ReDoS em motores com backtracking
+fn has_nested_quantifier(pattern: &str) -> bool {
+    // Para cada grupo aberto: se já contém algum quantificador
+    let mut groups: Vec<bool> = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    let mut in_class = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            _ if in_class => {}
+            '(' => groups.push(false),
+            ')' => {
+                let inner_quantified = groups.pop().unwrap_or(false);
+                let repeated = matches!(chars.peek(), Some('*' | '+' | '{'));
+                if inner_quantified && repeated {
+                    return true;
+                }
+                if let Some(parent) = groups.last_mut() {
+                    *parent |= inner_quantified || repeated;
+                }
+            }
+            '*' | '+' | '{' => {
+                if let Some(group) = groups.last_mut() {
+                    *group = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Varre textos longos e repetitivos numa thread separada; o padrão é
+/// reprovado se não terminar dentro de `SAMPLE_TIMEOUT`
+fn finishes_on_samples(regex: Regex) -> bool {
+    let (done_tx, done_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for sample in adversarial_samples() {
+            let _ = regex.find_iter(&sample).count();
+        }
+        let _ = done_tx.send(());
+    });
+    done_rx.recv_timeout(SAMPLE_TIMEOUT).is_ok()
+}
+
+fn adversarial_samples() -> Vec<String> {
+    ["a", "0", " ", "aA0_-.@ "]
+        .iter()
+        .map(|unit| {
+            let mut sample = unit.repeat(SAMPLE_LEN / unit.len());
+            // Um caractere final que não casa força a falha depois da repetição
+            sample.push('!');
+            sample
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benign_pattern_is_accepted() {
+        let result = validate_pattern(r"\bMAT-\d{4}\b");
+        assert!(result.valid);
+        assert_eq!(result.compile_error, None);
+        assert_eq!(result.estimated_risk, PatternRisk::Low);
+
+        // Quantificadores em sequência, sem aninhamento, são aceitos
+        assert!(validate_pattern(r"(?:\+55\s?)?\(?\d{2}\)?\s?\d{4,5}-?\d{4}").valid);
+        assert!(validate_pattern(r"[(+*]+\w+").valid);
+    }
+
+    #[test]
+    fn test_nested_quantifier_is_flagged() {
+        for pattern in [r"(a+)+$", r"(\w*\s?)*x", r"((ab)*c)+", r"(?:\d+){2,}"] {
+            let result = validate_pattern(pattern);
+            assert!(!result.valid, "{} deveria ser recusado", pattern);
+            assert_eq!(result.estimated_risk, PatternRisk::High);
+            assert_eq!(result.compile_error, None);
+        }
+    }
+
+    #[test]
+    fn test_invalid_and_oversized_patterns() {
+        let result = validate_pattern("(unclosed");
+        assert!(!result.valid);
+        assert!(result.compile_error.is_some());
+        assert_eq!(result.estimated_risk, PatternRisk::Low);
+
+        let result = validate_pattern(r"\w{1000}\w{1000}\w{1000}");
+        assert!(!result.valid);
+        assert_eq!(result.estimated_risk, PatternRisk::High);
+
+        assert!(!validate_pattern("   ").valid);
+        assert!(!validate_pattern(&"a".repeat(MAX_PATTERN_LEN + 1)).valid);
+    }
+}
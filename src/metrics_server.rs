@@ -0,0 +1,162 @@
+//! Endpoint Prometheus local (feature `metrics-endpoint`).
+//!
+//! Publica em `127.0.0.1/metrics` os contadores do agente, o tamanho do banco
+//! do perfil ativo e a latência das buscas, para acompanhar a captura no
+//! Grafana. Os valores são lidos a cada coleta; nada é guardado em separado.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use prometheus::{Counter, Encoder, IntCounter, IntGauge, Registry, TextEncoder};
+use tracing::{info, error, warn};
+
+use crate::agent::AgentMetrics;
+use crate::commands::{SearchLatency, SearchRegistry};
+use crate::db::DatabaseStats;
+use crate::local_http::LocalServer;
+use crate::profiles::ActiveProfile;
+
+/// Porta do endpoint; sem a variável ele não é iniciado
+pub const METRICS_PORT_ENV: &str = "KEYAI_METRICS_PORT";
+
+/// Contadores do agente exportados, com a descrição de cada um
+const AGENT_COUNTERS: &[(&str, &str)] = &[
+    ("events_captured", "Eventos recebidos do listener"),
+    ("events_processed", "Eventos mascarados e enviados ao buffer"),
+    ("events_filtered", "Eventos descartados pelos filtros de aplicação e janela"),
+    ("events_stored", "Eventos gravados no banco"),
+    ("events_discarded", "Eventos perdidos após falhas repetidas de gravação"),
+    ("events_dropped_overflow", "Eventos descartados com a fila do processador cheia"),
+    ("forced_flushes", "Flushes forçados por atingir max_buffer_size"),
+];
+
+#[derive(Clone)]
+struct MetricsState {
+    agent: Arc<AgentMetrics>,
+    profile: Arc<ActiveProfile>,
+    searches: Arc<SearchRegistry>,
+}
+
+/// Endpoint `/metrics` em execução; as coletas passam a falhar quando o
+/// handle é descartado, o que encerra o servidor
+pub struct MetricsServer {
+    server: LocalServer,
+}
+
+impl MetricsServer {
+    /// Publica `/metrics` em `127.0.0.1:port` (0 escolhe uma porta livre).
+    /// Não há autenticação: só contadores e tamanhos saem por aqui, nunca
+    /// o conteúdo dos eventos
+    pub async fn start(
+        agent: Arc<AgentMetrics>,
+        profile: Arc<ActiveProfile>,
+        searches: Arc<SearchRegistry>,
+        port: u16,
+    ) -> Result<Self> {
+        let app = Router::new()
+            .route("/metrics", get(metrics))
+            .with_state(MetricsState { agent, profile, searches });
+        let server = LocalServer::start(app, port, "metrics").await?;
+
+        info!("📈 Métricas Prometheus em http://{}/metrics", server.addr());
+        Ok(Self { server })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.server.addr()
+    }
+}
+
+async fn metrics(State(state): State<MetricsState>) -> Response {
+    // Sem as estatísticas do banco, os contadores do agente ainda são publicados
+    let db_stats = match state.profile.database().get_stats().await {
+        Ok(stats) => Some(stats),
+        Err(e) => {
+            warn!("⚠️ Estatísticas do banco indisponíveis para as métricas: {}", e);
+            None
+        }
+    };
+
+    match render(&state.agent.get_summary(), db_stats.as_ref(), state.searches.latency()) {
+        Ok(body) => ([(header::CONTENT_TYPE, TextEncoder::new().format_type().to_string())], body).into_response(),
+        Err(e) => {
+            error!("❌ Erro ao gerar métricas: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao gerar métricas: {}", e)).into_response()
+        }
+    }
+}
+
+/// Texto no formato de exposição do Prometheus
+fn render(summary: &HashMap<String, u64>, db_stats: Option<&DatabaseStats>, latency: SearchLatency) -> Result<String> {
+    let registry = Registry::new();
+
+    for (name, help) in AGENT_COUNTERS {
+        let counter = IntCounter::new(format!("keyai_{}_total", name), *help)?;
+        counter.inc_by(summary.get(*name).copied().unwrap_or(0));
+        registry.register(Box::new(counter))?;
+    }
+
+    if let Some(stats) = db_stats {
+        let size = IntGauge::new("keyai_database_size_bytes", "Tamanho do banco do perfil ativo")?;
+        size.set(stats.total_size_bytes);
+        registry.register(Box::new(size))?;
+
+        let events = IntGauge::new("keyai_database_events", "Eventos armazenados no perfil ativo")?;
+        events.set(stats.total_events);
+        registry.register(Box::new(events))?;
+    }
+
+    let searches = IntCounter::new("keyai_searches_total", "Buscas concluídas com sucesso")?;
+    searches.inc_by(latency.completed);
+    registry.register(Box::new(searches))?;
+
+    let duration = Counter::new("keyai_search_duration_seconds_total", "Tempo somado das buscas concluídas")?;
+    duration.inc_by(latency.total_secs);
+    registry.register(Box::new(duration))?;
+
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&registry.gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use crate::profiles::{Profile, DEFAULT_PROFILE};
+    use crate::test_support::http_request;
+
+    #[tokio::test]
+    async fn test_scrape_renders_counters() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = Profile::open(dir.path(), DEFAULT_PROFILE, None).await.unwrap();
+        let profile = Arc::new(ActiveProfile::new(dir.path(), profile));
+
+        let agent = Arc::new(AgentMetrics::new());
+        agent.events_captured.fetch_add(3, Ordering::Relaxed);
+        agent.events_stored.fetch_add(2, Ordering::Relaxed);
+
+        let searches = Arc::new(SearchRegistry::default());
+        searches.run(None, Duration::from_secs(5), async { Ok(()) }).await.unwrap();
+
+        let server = MetricsServer::start(agent, profile, searches, 0).await.unwrap();
+        assert!(server.addr().ip().is_loopback());
+
+        let (status, body) = http_request(server.addr(), "GET", "/metrics", "", "").await;
+        assert_eq!(status, 200);
+        assert!(body.contains("keyai_events_captured_total 3"), "{}", body);
+        assert!(body.contains("keyai_events_stored_total 2"));
+        assert!(body.contains("keyai_events_filtered_total 0"));
+        assert!(body.contains("keyai_database_events 0"));
+        assert!(body.contains("keyai_database_size_bytes"));
+        assert!(body.contains("keyai_searches_total 1"));
+        assert!(body.contains("# TYPE keyai_search_duration_seconds_total counter"));
+    }
+}
@@ -0,0 +1,255 @@
+//! Perfis de captura (ex.: "trabalho" e "pessoal") com bancos separados.
+//!
+//! O perfil padrão usa `keyai.db`; os demais, `keyai-<nome>.db` no mesmo
+//! diretório, cada um com a própria chave. Os comandos obtêm o banco e a
+//! engine do perfil ativo a cada chamada, então a troca vale para a próxima
+//! operação; buscas em andamento terminam no perfil anterior.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+use tracing::info;
+
+use crate::agent::Agent;
+use crate::db::Database;
+use crate::search::SearchEngine;
+
+/// Perfil usado quando nenhum outro foi escolhido
+pub const DEFAULT_PROFILE: &str = "default";
+
+const MAX_PROFILE_NAME_LEN: usize = 32;
+
+/// Perfil aberto: banco e engine de busca sobre ele
+pub struct Profile {
+    pub name: String,
+    pub database: Arc<Database>,
+    pub search_engine: Arc<SearchEngine>,
+}
+
+impl Profile {
+    /// Abre (ou cria) o banco do perfil em `dir` e monta a engine de busca
+    pub async fn open(dir: &Path, name: &str, key: Option<&str>) -> Result<Self> {
+        validate_profile_name(name)?;
+        let path = profile_path(dir, name);
+        let database = match key {
+            Some(key) => Database::with_key(&path, key).await?,
+            None => Database::new(&path).await?,
+        };
+        let database = Arc::new(database);
+        let search_engine = Arc::new(SearchEngine::new(Arc::clone(&database)).await?);
+
+        Ok(Self {
+            name: name.to_string(),
+            database,
+            search_engine,
+        })
+    }
+}
+
+/// Perfil listado por `list_profiles`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileInfo {
+    pub name: String,
+    pub active: bool,
+}
+
+/// Perfil ativo, trocado por `switch_profile`
+pub struct ActiveProfile {
+    dir: PathBuf,
+    current: RwLock<Arc<Profile>>,
+}
+
+impl ActiveProfile {
+    pub fn new(dir: impl Into<PathBuf>, profile: Profile) -> Self {
+        Self {
+            dir: dir.into(),
+            current: RwLock::new(Arc::new(profile)),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.current().name.clone()
+    }
+
+    pub fn database(&self) -> Arc<Database> {
+        Arc::clone(&self.current().database)
+    }
+
+    pub fn search_engine(&self) -> Arc<SearchEngine> {
+        Arc::clone(&self.current().search_engine)
+    }
+
+    /// Perfis com banco em disco, incluindo o ativo mesmo que ainda não tenha arquivo
+    pub fn list(&self) -> Result<Vec<ProfileInfo>> {
+        let active = self.name();
+        let mut names = list_profile_names(&self.dir)?;
+        if !names.contains(&active) {
+            names.push(active.clone());
+            names.sort();
+        }
+
+        Ok(names
+            .into_iter()
+            .map(|name| ProfileInfo { active: name == active, name })
+            .collect())
+    }
+
+    /// Abre o perfil `name` e passa a usá-lo, inclusive no agente.
+    ///
+    /// O agente é parado durante a troca e religado em seguida se estava
+    /// ativo; o `stop` espera as tarefas de retenção e backup terminarem, então
+    /// nenhuma delas usa o banco anterior depois da troca. Se o banco não abrir
+    /// (ex.: chave errada), o perfil atual continua
+    pub async fn switch(&self, agent: &mut Agent, name: &str, key: Option<&str>) -> Result<()> {
+        let profile = Profile::open(&self.dir, name, key).await?;
+
+        let was_running = agent.is_running();
+        if was_running {
+            agent.stop().await?;
+        }
+
+        agent.set_database(Arc::clone(&profile.database)).await?;
+        *self.current.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(profile);
+        info!("👤 Perfil ativo: {}", name);
+
+        if was_running {
+            agent.start().await?;
+        }
+        Ok(())
+    }
+
+    fn current(&self) -> Arc<Profile> {
+        Arc::clone(&self.current.read().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+}
+
+/// Nomes aceitos: letras, dígitos, `-` e `_`, até 32 caracteres
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_PROFILE_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!("Nome de perfil inválido: '{}'", name))
+    }
+}
+
+/// Arquivo do banco do perfil
+pub fn profile_path(dir: &Path, name: &str) -> PathBuf {
+    if name == DEFAULT_PROFILE {
+        dir.join("keyai.db")
+    } else {
+        dir.join(format!("keyai-{}.db", name))
+    }
+}
+
+fn list_profile_names(dir: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let file_name = entry?.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+
+        let name = if file_name == "keyai.db" {
+            Some(DEFAULT_PROFILE)
+        } else {
+            file_name.strip_prefix("keyai-").and_then(|rest| rest.strip_suffix(".db"))
+        };
+        if let Some(name) = name.filter(|name| validate_profile_name(name).is_ok()) {
+            names.push(name.to_string());
+        }
+    }
+
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::masker::Masker;
+    use crate::test_support::press;
+
+    #[test]
+    fn test_profile_names() {
+        assert!(validate_profile_name("trabalho").is_ok());
+        assert!(validate_profile_name("casa_2-b").is_ok());
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("../fora").is_err());
+        assert!(validate_profile_name(&"a".repeat(33)).is_err());
+
+        let dir = Path::new("/dados");
+        assert_eq!(profile_path(dir, DEFAULT_PROFILE), dir.join("keyai.db"));
+        assert_eq!(profile_path(dir, "trabalho"), dir.join("keyai-trabalho.db"));
+    }
+
+    #[tokio::test]
+    async fn test_switch_isolates_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = Profile::open(dir.path(), DEFAULT_PROFILE, None).await.unwrap();
+        let mut agent = Agent::new(Masker::new(), Arc::clone(&profile.database)).await.unwrap();
+        let active = ActiveProfile::new(dir.path(), profile);
+
+        active.database().store_events(&[press("p")]).await.unwrap();
+
+        active.switch(&mut agent, "trabalho", Some("chave-trabalho")).await.unwrap();
+        assert_eq!(active.name(), "trabalho");
+        assert_eq!(active.database().get_stats().await.unwrap().total_events, 0);
+        active.database().store_events(&[press("t"), press("u")]).await.unwrap();
+
+        active.switch(&mut agent, DEFAULT_PROFILE, None).await.unwrap();
+        assert_eq!(active.database().get_stats().await.unwrap().total_events, 1);
+
+        // O perfil protegido não abre com outra chave e o ativo continua o mesmo
+        assert!(active.switch(&mut agent, "trabalho", Some("errada")).await.is_err());
+        assert_eq!(active.name(), DEFAULT_PROFILE);
+
+        active.switch(&mut agent, "trabalho", Some("chave-trabalho")).await.unwrap();
+        assert_eq!(active.database().get_stats().await.unwrap().total_events, 2);
+
+        let profiles = active.list().unwrap();
+        assert_eq!(
+            profiles,
+            vec![
+                ProfileInfo { name: DEFAULT_PROFILE.to_string(), active: false },
+                ProfileInfo { name: "trabalho".to_string(), active: true },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_switch_releases_previous_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = Profile::open(dir.path(), DEFAULT_PROFILE, None).await.unwrap();
+        let mut agent = Agent::new(Masker::new(), Arc::clone(&profile.database)).await.unwrap();
+        let active = ActiveProfile::new(dir.path(), profile);
+
+        agent.start().await.unwrap();
+        let previous = active.database();
+        active.switch(&mut agent, "trabalho", None).await.unwrap();
+        assert!(agent.is_running());
+
+        // Só esta referência resta: nenhuma tarefa do agente segura o banco anterior
+        assert_eq!(Arc::strong_count(&previous), 1);
+        agent.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_agent_follows_profile_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = Profile::open(dir.path(), DEFAULT_PROFILE, None).await.unwrap();
+        let mut agent = Agent::new(Masker::new(), Arc::clone(&profile.database)).await.unwrap();
+        let active = ActiveProfile::new(dir.path(), profile);
+
+        agent.update_config(crate::agent::AgentConfig { buffer_size: 7, ..Default::default() }).await.unwrap();
+
+        active.switch(&mut agent, "pessoal", None).await.unwrap();
+        assert_eq!(agent.get_config().await.buffer_size, crate::agent::AgentConfig::default().buffer_size);
+
+        active.switch(&mut agent, DEFAULT_PROFILE, None).await.unwrap();
+        assert_eq!(agent.get_config().await.buffer_size, 7);
+    }
+}
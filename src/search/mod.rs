@@ -1,40 +1,130 @@
 use std::sync::Arc;
-use anyhow::Result;
-use tracing::{info, debug, warn};
+use anyhow::{Result, anyhow};
+use regex::{Regex, RegexBuilder};
+use tracing::{info, debug, warn, instrument};
 use serde::{Serialize, Deserialize};
 
-use crate::db::{Database, SearchResult};
+use chrono::{DateTime, Utc};
+
+use crate::db::{default_search_event_types, millis_to_datetime, millis_to_iso, Database, EmbeddingInfo, EmbeddingModelInfo, SearchResult};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HybridSearchResult {
     pub id: i64,
     pub content: String,
+    /// Milissegundos desde a época
     pub timestamp: u64,
+    /// `timestamp` em ISO-8601, para exibição no frontend
+    #[serde(default)]
+    pub timestamp_iso: String,
     pub text_score: f64,
     pub semantic_score: f64,
     pub combined_score: f64,
     pub context: Option<String>,
 }
 
+impl HybridSearchResult {
+    pub fn timestamp_datetime(&self) -> DateTime<Utc> {
+        millis_to_datetime(self.timestamp)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SearchOptions {
     pub limit: usize,
+    /// Quantidade de resultados a pular (paginação)
+    pub offset: usize,
     pub text_weight: f64,
     pub semantic_weight: f64,
+    /// Similaridade mínima dos resultados semânticos, aplicada antes da fusão:
+    /// as notas RRF (no máximo `1 / (rrf_k + 1)`) não são comparáveis a ela
     pub min_score_threshold: f64,
+    /// Repassa operadores do FTS5 sem escapar a consulta
+    pub advanced: bool,
+    /// Pesos por coluna usados no ranking BM25
+    pub column_weights: ColumnWeights,
+    /// Quantidade de tokens no trecho destacado (máximo 64)
+    pub snippet_tokens: usize,
+    pub highlight_start: String,
+    pub highlight_end: String,
+    /// Restringe aos eventos cujo título da janela contém o texto (sem diferenciar maiúsculas)
+    pub window_title_contains: Option<String>,
+    /// Ignora acentos na busca textual ("sao paulo" encontra "São Paulo").
+    /// Desativado, os termos precisam aparecer com os mesmos acentos
+    pub diacritic_insensitive: bool,
+    /// Constante `k` do RRF: valores menores favorecem os primeiros colocados de cada fonte
+    pub rrf_k: f64,
+    /// Resultados textuais considerados na fusão híbrida (`None` = todos)
+    pub text_top_k: Option<usize>,
+    /// Resultados semânticos considerados na fusão híbrida (`None` = todos)
+    pub semantic_top_k: Option<usize>,
+    /// Tipos de evento considerados (`None` = teclas pressionadas, colagens e
+    /// segmentos compactados, sem as liberações)
+    pub event_types: Option<Vec<String>>,
+    /// Eventos vizinhos da mesma sessão anexados a cada resultado textual (0 = nenhum)
+    pub context_size: usize,
+}
+
+/// Tamanho mínimo das consultas, em caracteres, exigido pelos comandos de
+/// busca. Não faz parte de `SearchOptions` para que o cliente não o reduza
+pub const DEFAULT_MIN_QUERY_LEN: usize = 2;
+
+/// Pesos das colunas do índice FTS5 passados para `bm25()`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnWeights {
+    pub content: f64,
+    pub application: f64,
+    pub window_title: f64,
+}
+
+impl Default for ColumnWeights {
+    fn default() -> Self {
+        Self {
+            content: 1.0,
+            application: 0.5,
+            window_title: 0.5,
+        }
+    }
 }
 
 impl Default for SearchOptions {
     fn default() -> Self {
         Self {
             limit: 50,
+            offset: 0,
             text_weight: 0.7,
             semantic_weight: 0.3,
             min_score_threshold: 0.1,
+            advanced: false,
+            column_weights: ColumnWeights::default(),
+            snippet_tokens: 16,
+            highlight_start: "<mark>".to_string(),
+            highlight_end: "</mark>".to_string(),
+            window_title_contains: None,
+            diacritic_insensitive: true,
+            rrf_k: 60.0,
+            text_top_k: None,
+            semantic_top_k: None,
+            event_types: None,
+            context_size: 0,
         }
     }
 }
 
+/// Busca semântica solicitada sem um modelo de embeddings carregado
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemanticUnavailable;
+
+impl std::fmt::Display for SemanticUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Busca semântica indisponível: modelo de embeddings não carregado")
+    }
+}
+
+impl std::error::Error for SemanticUnavailable {}
+
 pub struct SearchEngine {
     database: Arc<Database>,
     embedding_model: Option<EmbeddingModel>,
@@ -45,17 +135,28 @@ struct EmbeddingModel {
     // TODO: Implement with rust-bert
 }
 
+/// Identificação gravada junto de cada embedding gerado pelo placeholder
+const EMBEDDING_MODEL_NAME: &str = "placeholder";
+const EMBEDDING_DIMENSION: usize = 384;
+
 impl EmbeddingModel {
     fn new() -> Result<Self> {
         // TODO: Initialize rust-bert model
         Ok(Self {})
     }
 
+    fn info(&self) -> EmbeddingModelInfo {
+        EmbeddingModelInfo {
+            name: EMBEDDING_MODEL_NAME.to_string(),
+            dimension: EMBEDDING_DIMENSION,
+        }
+    }
+
     fn encode(&self, _text: &str) -> Result<Vec<f32>> {
         // TODO: Implement actual embedding generation
         // For now, return a dummy embedding
         warn!("🚧 Usando embedding dummy - implementar rust-bert");
-        Ok(vec![0.1; 384]) // Dummy 384-dimensional embedding
+        Ok(vec![0.1; EMBEDDING_DIMENSION]) // Dummy 384-dimensional embedding
     }
 
     fn similarity(&self, embedding1: &[f32], embedding2: &[f32]) -> f64 {
@@ -88,6 +189,7 @@ impl SearchEngine {
         let embedding_model = match EmbeddingModel::new() {
             Ok(model) => {
                 info!("✅ Modelo de embeddings inicializado");
+                database.set_embedding_model(model.info());
                 Some(model)
             },
             Err(e) => {
@@ -102,20 +204,23 @@ impl SearchEngine {
         })
     }
 
+    // Spans and their events carry only sizes, never the query the user typed
+    #[instrument(skip_all, fields(query_len = query.chars().count(), limit = options.limit))]
     pub async fn search_text(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>> {
-        debug!("🔍 Executando busca textual para: {}", query);
+        debug!("🔍 Executando busca textual");
         
-        self.database.search_text(query, options.limit).await
+        self.database.search_text_with_options(query, options).await
     }
 
+    #[instrument(skip_all, fields(query_len = query.chars().count(), limit = options.limit))]
     pub async fn search_semantic(&self, query: &str, options: &SearchOptions) -> Result<Vec<HybridSearchResult>> {
-        debug!("🧠 Executando busca semântica para: {}", query);
+        debug!("🧠 Executando busca semântica");
         
         let embedding_model = match &self.embedding_model {
             Some(model) => model,
             None => {
                 warn!("⚠️ Modelo de embeddings não disponível para busca semântica");
-                return Ok(Vec::new());
+                return Err(SemanticUnavailable.into());
             }
         };
 
@@ -124,11 +229,25 @@ impl SearchEngine {
         
         // Get all events with embeddings (this is a simplified approach)
         // In production, you'd want to use a proper vector database or indexing
-        let all_events = self.database.search_by_timerange(0, u64::MAX, 10000).await?;
+        let event_types = options.event_types.clone().unwrap_or_else(default_search_event_types);
+        let all_events = self.database
+            .search_by_timerange_filtered(0, u64::MAX, 10000, Some(event_types.as_slice()))
+            .await?;
         
         let mut semantic_results = Vec::new();
+        let title_filter = options.window_title_contains.as_deref().map(str::to_lowercase);
         
         for event in all_events {
+            if let Some(needle) = &title_filter {
+                let title_matches = event.window_title
+                    .as_deref()
+                    .map(|title| title.to_lowercase().contains(needle.as_str()))
+                    .unwrap_or(false);
+                if !title_matches {
+                    continue;
+                }
+            }
+
             if let Some(content) = &event.text_content {
                 if content.trim().is_empty() {
                     continue;
@@ -138,7 +257,7 @@ impl SearchEngine {
                 let event_embedding = match self.database.get_embedding(event.id).await? {
                     Some(embedding) => embedding,
                     None => {
-                        // Generate and store embedding
+                        // Generate and store embedding (also replaces one from another model)
                         let embedding = embedding_model.encode(content)?;
                         if let Err(e) = self.database.store_embedding(event.id, &embedding).await {
                             warn!("⚠️ Falha ao armazenar embedding para evento {}: {}", event.id, e);
@@ -147,6 +266,13 @@ impl SearchEngine {
                     }
                 };
 
+                // Similaridade entre dimensões diferentes não tem significado
+                if event_embedding.len() != query_embedding.len() {
+                    warn!("⚠️ Embedding do evento {} com {} dimensões ignorado (consulta: {})",
+                          event.id, event_embedding.len(), query_embedding.len());
+                    continue;
+                }
+
                 // Calculate similarity
                 let similarity = embedding_model.similarity(&query_embedding, &event_embedding);
                 
@@ -155,6 +281,7 @@ impl SearchEngine {
                         id: event.id,
                         content: content.clone(),
                         timestamp: event.timestamp,
+                        timestamp_iso: event.timestamp_iso.clone(),
                         text_score: 0.0,
                         semantic_score: similarity,
                         combined_score: similarity,
@@ -175,28 +302,41 @@ impl SearchEngine {
         Ok(semantic_results)
     }
 
+    /// `false` quando não há modelo de embeddings; a busca híbrida passa a ser só textual
+    pub fn semantic_available(&self) -> bool {
+        self.embedding_model.is_some()
+    }
+
+    #[instrument(skip_all, fields(query_len = query.chars().count(), limit = options.limit))]
     pub async fn search_hybrid(&self, query: &str, options: &SearchOptions) -> Result<Vec<HybridSearchResult>> {
-        debug!("🔍🧠 Executando busca híbrida para: {}", query);
+        debug!("🔍🧠 Executando busca híbrida");
 
         // Perform both text and semantic search
         let text_results = self.search_text(query, options).await?;
-        let semantic_results = self.search_semantic(query, options).await?;
+        let semantic_results = if self.semantic_available() {
+            self.search_semantic(query, options).await?
+        } else {
+            Vec::new()
+        };
 
         // Combine results using Reciprocal Rank Fusion (RRF)
-        let combined_results = self.combine_results_rrf(text_results, semantic_results, options);
+        let combined_results = Self::combine_results_rrf(text_results, semantic_results, options);
 
         debug!("🔍🧠 Busca híbrida retornou {} resultados", combined_results.len());
         Ok(combined_results)
     }
 
-    fn combine_results_rrf(&self, text_results: Vec<SearchResult>, semantic_results: Vec<HybridSearchResult>, options: &SearchOptions) -> Vec<HybridSearchResult> {
+    fn combine_results_rrf(text_results: Vec<SearchResult>, semantic_results: Vec<HybridSearchResult>, options: &SearchOptions) -> Vec<HybridSearchResult> {
         use std::collections::HashMap;
 
         let mut combined_scores: HashMap<i64, (f64, f64, String, u64, Option<String>)> = HashMap::new();
+        let k = options.rrf_k.max(0.0);
+        let text_top_k = options.text_top_k.unwrap_or(usize::MAX);
+        let semantic_top_k = options.semantic_top_k.unwrap_or(usize::MAX);
         
         // Add text search scores
-        for (rank, result) in text_results.iter().enumerate() {
-            let rrf_score = 1.0 / (60.0 + rank as f64 + 1.0); // RRF with k=60
+        for (rank, result) in text_results.iter().take(text_top_k).enumerate() {
+            let rrf_score = 1.0 / (k + rank as f64 + 1.0);
             combined_scores.insert(result.id, (
                 rrf_score * options.text_weight,
                 0.0,
@@ -207,8 +347,8 @@ impl SearchEngine {
         }
 
         // Add semantic search scores
-        for (rank, result) in semantic_results.iter().enumerate() {
-            let rrf_score = 1.0 / (60.0 + rank as f64 + 1.0); // RRF with k=60
+        for (rank, result) in semantic_results.iter().take(semantic_top_k).enumerate() {
+            let rrf_score = 1.0 / (k + rank as f64 + 1.0);
             let semantic_score = rrf_score * options.semantic_weight;
             
             combined_scores.entry(result.id)
@@ -224,13 +364,13 @@ impl SearchEngine {
                     id,
                     content,
                     timestamp,
+                    timestamp_iso: millis_to_iso(timestamp),
                     text_score,
                     semantic_score,
                     combined_score: text_score + semantic_score,
                     context,
                 }
             })
-            .filter(|result| result.combined_score >= options.min_score_threshold)
             .collect();
 
         final_results.sort_by(|a, b| {
@@ -242,17 +382,20 @@ impl SearchEngine {
         final_results
     }
 
+    /// Modelo de embeddings ativo e cobertura do índice semântico
+    pub async fn embedding_info(&self) -> Result<EmbeddingInfo> {
+        self.database.embedding_info().await
+    }
+
+    /// Completa a última palavra da consulta com termos frequentes do índice
     pub async fn get_search_suggestions(&self, partial_query: &str, limit: usize) -> Result<Vec<String>> {
-        // Implementação básica usando busca textual
-        match self.database.search_text(partial_query, limit).await {
-            Ok(results) => {
-                let suggestions: Vec<String> = results
-                    .into_iter()
-                    .map(|r| r.content)
-                    .take(limit)
-                    .collect();
-                Ok(suggestions)
-            },
+        let (head, last_word) = split_last_word(partial_query);
+
+        match self.database.suggest_terms(last_word, limit).await {
+            Ok(terms) => Ok(terms
+                .into_iter()
+                .map(|term| format!("{}{}", head, term))
+                .collect()),
             Err(e) => {
                 warn!("Erro ao gerar sugestões: {}", e);
                 Ok(vec![])
@@ -260,25 +403,26 @@ impl SearchEngine {
         }
     }
 
-    pub async fn get_popular_searches(&self, limit: usize) -> Result<Vec<String>> {
+    /// Consultas mais frequentes nos últimos `window_secs` segundos
+    pub async fn get_popular_searches(&self, limit: usize, window_secs: u64) -> Result<Vec<String>> {
         debug!("📊 Obtendo buscas populares");
-        
-        // This would typically be based on search analytics
-        // For now, return some common patterns
-        Ok(vec![
-            "email".to_string(),
-            "password".to_string(),
-            "login".to_string(),
-            "document".to_string(),
-            "meeting".to_string(),
-        ].into_iter().take(limit).collect())
-    }
-
-    pub async fn optimize_search_index(&self) -> Result<()> {
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+
+        self.database.popular_searches(now.saturating_sub(window_secs), limit).await
+    }
+
+    /// Mescla os segmentos do índice FTS5; `vacuum` também reescreve o
+    /// arquivo do banco, recuperando espaço (operação lenta)
+    pub async fn optimize_search_index(&self, vacuum: bool) -> Result<()> {
         info!("🔧 Otimizando índices de busca...");
         
-        // Optimize database
-        self.database.vacuum().await?;
+        self.database.optimize_fts().await?;
+        if vacuum {
+            self.database.vacuum().await?;
+        }
         
         // TODO: Optimize vector index if using a proper vector database
         
@@ -289,6 +433,42 @@ impl SearchEngine {
 
 }
 
+/// Separa a consulta parcial em (texto já digitado, última palavra a completar)
+fn split_last_word(partial_query: &str) -> (String, &str) {
+    match partial_query.trim().rsplit_once(char::is_whitespace) {
+        Some((head, last)) => (format!("{} ", head.trim_end()), last),
+        None => (String::new(), partial_query.trim()),
+    }
+}
+
+/// Janela padrão das buscas populares (30 dias)
+pub const DEFAULT_POPULAR_WINDOW_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Tamanho máximo, em caracteres, de uma expressão regular do usuário
+pub const MAX_REGEX_PATTERN_LEN: usize = 256;
+
+/// Limites do programa compilado; repetições aninhadas estouram esses tetos
+const REGEX_SIZE_LIMIT: usize = 256 * 1024;
+const REGEX_DFA_SIZE_LIMIT: usize = 1024 * 1024;
+
+/// Compila a expressão regular de `Database::search_regex`. O crate `regex`
+/// não faz backtracking, então o custo está no tamanho do autômato: padrões
+/// longos demais ou que compilam para programas enormes são recusados
+pub fn compile_search_regex(pattern: &str) -> Result<Regex> {
+    if pattern.trim().is_empty() {
+        return Err(anyhow!("Expressão regular vazia"));
+    }
+    if pattern.chars().count() > MAX_REGEX_PATTERN_LEN {
+        return Err(anyhow!("Expressão regular excede {} caracteres", MAX_REGEX_PATTERN_LEN));
+    }
+
+    RegexBuilder::new(pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_DFA_SIZE_LIMIT)
+        .build()
+        .map_err(|e| anyhow!("Expressão regular inválida ou complexa demais: {}", e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -314,5 +494,157 @@ mod tests {
         assert_eq!(options.text_weight, 0.7);
         assert_eq!(options.semantic_weight, 0.3);
         assert_eq!(options.min_score_threshold, 0.1);
+        assert!(!options.advanced);
+        assert_eq!(options.column_weights, ColumnWeights::default());
     }
-} 
\ No newline at end of file
+
+    fn text_result(id: i64) -> SearchResult {
+        SearchResult {
+            id,
+            content: format!("texto {}", id),
+            timestamp: id as u64,
+            timestamp_iso: millis_to_iso(id as u64),
+            relevance_score: 1.0,
+            context: None,
+            highlight: None,
+            window_title: None,
+            context_before: None,
+            context_after: None,
+        }
+    }
+
+    fn semantic_result(id: i64) -> HybridSearchResult {
+        HybridSearchResult {
+            id,
+            content: format!("texto {}", id),
+            timestamp: id as u64,
+            timestamp_iso: millis_to_iso(id as u64),
+            text_score: 0.0,
+            semantic_score: 0.9,
+            combined_score: 0.9,
+            context: None,
+        }
+    }
+
+    fn fused_ids(text: &[i64], semantic: &[i64], options: &SearchOptions) -> Vec<i64> {
+        SearchEngine::combine_results_rrf(
+            text.iter().copied().map(text_result).collect(),
+            semantic.iter().copied().map(semantic_result).collect(),
+            options,
+        )
+        .into_iter()
+        .map(|result| result.id)
+        .collect()
+    }
+
+    #[test]
+    fn test_rrf_k_changes_fusion_order() {
+        // 1 lidera só o textual; 3 é o terceiro textual e o primeiro semântico
+        let options = SearchOptions { min_score_threshold: 0.0, ..Default::default() };
+        assert_eq!(fused_ids(&[1, 2, 3], &[3], &options)[0], 3);
+
+        let sharp = SearchOptions { rrf_k: 1.0, ..options };
+        assert_eq!(fused_ids(&[1, 2, 3], &[3], &sharp)[0], 1);
+    }
+
+    #[test]
+    fn test_rrf_keeps_results_with_default_threshold() {
+        // Notas fundidas ficam perto de 1/61; o limite padrão vale para a similaridade
+        let ids = fused_ids(&[1, 2, 3], &[3, 4], &SearchOptions::default());
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids[0], 3);
+    }
+
+    #[test]
+    fn test_rrf_top_k_per_source() {
+        let options = SearchOptions {
+            min_score_threshold: 0.0,
+            text_top_k: Some(2),
+            semantic_top_k: Some(1),
+            ..Default::default()
+        };
+
+        let mut ids = fused_ids(&[1, 2, 3, 4, 5], &[6, 7], &options);
+        ids.sort();
+        assert_eq!(ids, vec![1, 2, 6]);
+
+        let all = SearchOptions { min_score_threshold: 0.0, ..Default::default() };
+        assert_eq!(fused_ids(&[1, 2, 3, 4, 5], &[6, 7], &all).len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_semantic_search_with_model() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let database = Arc::new(Database::new(temp_file.path()).await.unwrap());
+        let search_engine = SearchEngine::new(database).await.unwrap();
+
+        assert!(search_engine.semantic_available());
+        let results = search_engine.search_semantic("teste", &SearchOptions::default()).await.unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_semantic_search_without_model() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let database = Arc::new(Database::new(temp_file.path()).await.unwrap());
+        let search_engine = SearchEngine { database, embedding_model: None };
+
+        assert!(!search_engine.semantic_available());
+        let error = search_engine.search_semantic("teste", &SearchOptions::default()).await.unwrap_err();
+        assert!(error.downcast_ref::<SemanticUnavailable>().is_some());
+
+        // A híbrida continua funcionando com a parte textual
+        assert!(search_engine.search_hybrid("teste", &SearchOptions::default()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_engine_registers_embedding_model() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let database = Arc::new(Database::new(temp_file.path()).await.unwrap());
+        let search_engine = SearchEngine::new(database.clone()).await.unwrap();
+
+        let info = search_engine.embedding_info().await.unwrap();
+        assert_eq!(info.model_name.as_deref(), Some(EMBEDDING_MODEL_NAME));
+        assert_eq!(info.dimension, Some(EMBEDDING_DIMENSION));
+        assert_eq!(info.indexed_events, 0);
+        assert!(database.store_embedding(1, &[0.1; 5]).await.is_err());
+    }
+
+    #[test]
+    fn test_split_last_word() {
+        assert_eq!(split_last_word("em"), (String::new(), "em"));
+        assert_eq!(split_last_word("send  em "), ("send ".to_string(), "em"));
+        assert_eq!(split_last_word("a b c"), ("a b ".to_string(), "c"));
+        assert_eq!(split_last_word(""), (String::new(), ""));
+    }
+
+    #[test]
+    fn test_compile_search_regex_guards() {
+        let regex = compile_search_regex(r"\d{4}").unwrap();
+        assert!(regex.is_match("pin 1234"));
+
+        // Repetições aninhadas geram um programa acima do limite
+        assert!(compile_search_regex(r"(\w{100}){100}").is_err());
+        assert!(compile_search_regex(&"a".repeat(MAX_REGEX_PATTERN_LEN + 1)).is_err());
+        assert!(compile_search_regex("  ").is_err());
+        assert!(compile_search_regex("(abc").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_popular_searches_window() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let database = Arc::new(Database::new(temp_file.path()).await.unwrap());
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        database.log_search("ancient", 1).await.unwrap();
+        database.log_search("ancient", 2).await.unwrap();
+        database.log_search("recent", now).await.unwrap();
+
+        let search_engine = SearchEngine::new(database).await.unwrap();
+        let popular = search_engine.get_popular_searches(10, 3600).await.unwrap();
+        assert_eq!(popular, vec!["recent".to_string()]);
+    }
+}
@@ -0,0 +1,81 @@
+//! Configuração da aplicação lida na inicialização.
+//!
+//! Os valores vêm de `keyai.toml` no diretório dos dados, se existir, e de
+//! variáveis `KEYAI_*`, que têm prioridade; seções são separadas por `__`
+//! (ex.: `KEYAI_SEARCH_TIMEOUTS__TEXT_SECS=5`). Campos ausentes ficam com o
+//! valor padrão.
+
+use std::path::Path;
+use anyhow::Result;
+use config::{Config, Environment, File, FileFormat, Map};
+use serde::{Serialize, Deserialize};
+
+use crate::commands::SearchTimeouts;
+
+/// Arquivo opcional de configuração, no diretório dos bancos
+pub const SETTINGS_FILE: &str = "keyai.toml";
+
+/// Prefixo das variáveis de ambiente que sobrescrevem o arquivo
+pub const SETTINGS_ENV_PREFIX: &str = "KEYAI";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Tempo limite dos comandos de busca (`[search_timeouts]`)
+    pub search_timeouts: SearchTimeouts,
+}
+
+impl AppSettings {
+    /// Lê `dir/keyai.toml` e as variáveis de ambiente
+    pub fn load(dir: &Path) -> Result<Self> {
+        Self::load_with_env(dir, None)
+    }
+
+    /// Como `load`, com as variáveis vindas de `env` em vez do processo
+    fn load_with_env(dir: &Path, env: Option<Map<String, String>>) -> Result<Self> {
+        let path = dir.join(SETTINGS_FILE);
+        let settings = Config::builder()
+            .add_source(File::from(path).format(FileFormat::Toml).required(false))
+            .add_source(
+                Environment::with_prefix(SETTINGS_ENV_PREFIX)
+                    .prefix_separator("_")
+                    .separator("__")
+                    .try_parsing(true)
+                    .source(env),
+            )
+            .build()?;
+        Ok(settings.try_deserialize()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_without_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = AppSettings::load_with_env(dir.path(), Some(Map::new())).unwrap();
+        assert_eq!(settings.search_timeouts.text_secs, SearchTimeouts::default().text_secs);
+    }
+
+    #[test]
+    fn test_file_and_env_override() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(SETTINGS_FILE),
+            "[search_timeouts]\ntext_secs = 3\nsemantic_secs = 40\n",
+        )
+        .unwrap();
+
+        let mut env = Map::new();
+        env.insert("KEYAI_SEARCH_TIMEOUTS__SEMANTIC_SECS".to_string(), "50".to_string());
+        // Outras variáveis KEYAI_* (portas, diretório de logs) são ignoradas
+        env.insert("KEYAI_METRICS_PORT".to_string(), "9100".to_string());
+
+        let settings = AppSettings::load_with_env(dir.path(), Some(env)).unwrap();
+        assert_eq!(settings.search_timeouts.text_secs, 3);
+        assert_eq!(settings.search_timeouts.semantic_secs, 50);
+        assert_eq!(settings.search_timeouts.hybrid_secs, SearchTimeouts::default().hybrid_secs);
+    }
+}
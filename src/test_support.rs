@@ -0,0 +1,44 @@
+//! Fixtures compartilhadas pelos testes dos módulos.
+
+use crate::agent::KeyEvent;
+#[cfg(any(feature = "rest-bridge", feature = "metrics-endpoint"))]
+use std::net::SocketAddr;
+
+/// Tecla comum pressionada no instante 0, sem janela nem sessão
+pub fn press(key: &str) -> KeyEvent {
+    press_at(key, 0)
+}
+
+/// Como `press`, no instante informado
+pub fn press_at(key: &str, timestamp: u64) -> KeyEvent {
+    KeyEvent {
+        timestamp,
+        key: key.to_string(),
+        event_type: "press".to_string(),
+        window_info: None,
+        is_modifier: false,
+        is_function_key: false,
+        session_id: None,
+        modifiers: Vec::new(),
+    }
+}
+
+/// Requisição HTTP/1.1 crua aos servidores de `local_http`; `headers` vem
+/// pronto, com cada linha terminada em `\r\n`. Retorna o status e o corpo
+#[cfg(any(feature = "rest-bridge", feature = "metrics-endpoint"))]
+pub async fn http_request(addr: SocketAddr, method: &str, path: &str, headers: &str, body: &str) -> (u16, String) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+        method, path, headers, body.len(), body
+    );
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
@@ -92,6 +92,7 @@ async fn test_event_filtering() {
         ignored_window_patterns: vec![r".*[Pp]assword.*".to_string()],
         ..Default::default()
     };
+    let patterns = Agent::compile_window_patterns(&config.ignored_window_patterns).unwrap();
 
     // Test modifier filtering
     let modifier_event = KeyEvent {
@@ -101,8 +102,10 @@ async fn test_event_filtering() {
         window_info: None,
         is_modifier: true,
         is_function_key: false,
+        session_id: None,
+        modifiers: Vec::new(),
     };
-    assert!(Agent::should_filter_event(&modifier_event, &config));
+    assert!(Agent::should_filter_event(&modifier_event, &config, &patterns));
 
     // Test function key filtering
     let function_event = KeyEvent {
@@ -112,8 +115,10 @@ async fn test_event_filtering() {
         window_info: None,
         is_modifier: false,
         is_function_key: true,
+        session_id: None,
+        modifiers: Vec::new(),
     };
-    assert!(Agent::should_filter_event(&function_event, &config));
+    assert!(Agent::should_filter_event(&function_event, &config, &patterns));
 
     // Test application filtering
     let app_event = KeyEvent {
@@ -128,8 +133,10 @@ async fn test_event_filtering() {
         }),
         is_modifier: false,
         is_function_key: false,
+        session_id: None,
+        modifiers: Vec::new(),
     };
-    assert!(Agent::should_filter_event(&app_event, &config));
+    assert!(Agent::should_filter_event(&app_event, &config, &patterns));
 
     // Test window pattern filtering
     let window_event = KeyEvent {
@@ -144,8 +151,10 @@ async fn test_event_filtering() {
         }),
         is_modifier: false,
         is_function_key: false,
+        session_id: None,
+        modifiers: Vec::new(),
     };
-    assert!(Agent::should_filter_event(&window_event, &config));
+    assert!(Agent::should_filter_event(&window_event, &config, &patterns));
 
     // Test normal event (should not be filtered)
     let normal_event = KeyEvent {
@@ -160,8 +169,10 @@ async fn test_event_filtering() {
         }),
         is_modifier: false,
         is_function_key: false,
+        session_id: None,
+        modifiers: Vec::new(),
     };
-    assert!(!Agent::should_filter_event(&normal_event, &config));
+    assert!(!Agent::should_filter_event(&normal_event, &config, &patterns));
 }
 
 #[tokio::test]
@@ -230,6 +241,8 @@ async fn test_key_event_structure() {
         }),
         is_modifier: false,
         is_function_key: false,
+        session_id: None,
+        modifiers: Vec::new(),
     };
     
     assert_eq!(key_event.key, "a");
@@ -322,4 +335,40 @@ async fn test_agent_double_start_stop() {
     // Should not error when stopping already stopped agent
     agent.stop().await.unwrap();
     assert!(!agent.is_running());
-} 
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_shutdown_persists_buffered_events() {
+    let masker = Masker::new();
+    let database = create_test_database().await.unwrap();
+
+    // Buffer grande e intervalo longo para que nenhum flush aconteça antes do shutdown
+    let config = AgentConfig {
+        buffer_size: 100,
+        flush_interval_secs: 3600,
+        enable_window_detection: false,
+        ..Default::default()
+    };
+
+    let mut agent = Agent::with_config(masker, database.clone(), config).await.unwrap();
+    agent.start().await.unwrap();
+
+    for i in 0..10 {
+        agent.submit_event(KeyEvent {
+            timestamp: 1000 + i,
+            key: "a".to_string(),
+            event_type: "press".to_string(),
+            window_info: None,
+            is_modifier: false,
+            is_function_key: false,
+            session_id: None,
+            modifiers: Vec::new(),
+        }).unwrap();
+    }
+
+    agent.shutdown().await.unwrap();
+    assert!(!agent.is_running());
+
+    let stats = database.get_stats().await.unwrap();
+    assert_eq!(stats.total_events, 10);
+}